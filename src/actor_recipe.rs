@@ -29,6 +29,7 @@
 //! - **System Concerns** - Putting it all together
 //!     - **System coordinator** - Lifecycle management and dependency injection → [`OrderSystem`]
 //!     - **Tracing setup** - Centralized observability configuration → [`setup_tracing`]
+//!     - **Event bus** - Publish/subscribe for domain events between actors → [`EventBusService`]
 //!
 //! ## Instructions
 //!
@@ -50,8 +51,8 @@
 //! ## Example Usage
 //!
 //! ```rust
-//! // Create the entire actor system
-//! let system = OrderSystem::new();
+//! // Create the entire actor system and wait until every actor is running
+//! let system = OrderSystem::start().await?;
 //!
 //! // Create a user (flows to UserService)
 //! let user_id = system.user_client.create_user(user).await?;
@@ -83,9 +84,10 @@
 //! INFO handle_create_order{order_id="order_1"}: User validation successful user_name="Alice"
 //! ```
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use tracing::{debug, error, info, instrument, warn, Instrument};
 
 // =============================================================================
@@ -94,24 +96,523 @@ use tracing::{debug, error, info, instrument, warn, Instrument};
 
 /// Generate client methods with oneshot channel boilerplate and automatic tracing.
 /// Client methods convert domain errors to String for API simplicity.
+/// Each attempt goes through the client's [`ClientPolicy`] (timeout, retry, circuit breaker),
+/// and every call is counted and timed in [`Metrics::global`].
 macro_rules! client_method {
     ($client:ty => fn $method:ident($($param:ident: $param_type:ty),*) -> $return_type:ty as $request:ident::$variant:ident) => {
         impl $client {
-            #[instrument(skip(self))]
+            #[instrument(skip(self), fields(request_id = tracing::field::Empty))]
             pub async fn $method(&self, $($param: $param_type),*) -> std::result::Result<$return_type, String> {
                 debug!("Sending request");
-                let (respond_to, response) = oneshot::channel();
-                self.sender.send($request::$variant {
-                    $($param,)*
-                    respond_to,
-                }).await.map_err(|e| e.to_string())?;
-
-                response.await.map_err(|e| e.to_string()).and_then(|result| result.map_err(|e| e.to_string()))
+                let started = tokio::time::Instant::now();
+                let result = RequestId::propagate(self.policy.call(|| {
+                    // Every attempt needs its own copy of the arguments
+                    $(let $param = $param.clone();)*
+                    async move {
+                        let (respond_to, response) = ServiceResponse::channel();
+                        self.policy.send(&self.sender, $request::$variant {
+                            $($param,)*
+                            respond_to,
+                        }).await?;
+
+                        response.await.map_err(|e| e.to_string())
+                    }
+                })).await;
+                Metrics::global().record(
+                    stringify!($request),
+                    stringify!($variant),
+                    started.elapsed(),
+                    result.is_ok(),
+                );
+                result
             }
         }
     };
 }
 
+// =============================================================================
+// CLOCK
+// =============================================================================
+
+/// Source of time for everything time-dependent (breakers, TTLs, timestamps).
+///
+/// **Pattern:** Components read time through a [`SharedClock`] rather than
+/// calling `Instant::now()` or `SystemTime::now()` themselves, so tests can
+/// swap in a [`TestClock`] and step time with `tokio::time::advance` instead of
+/// sleeping. Waiting still goes through `tokio::time::sleep`, which honours a
+/// paused runtime on its own.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// Monotonic time, for deadlines and elapsed durations
+    fn now(&self) -> tokio::time::Instant;
+
+    /// Wall-clock time, for timestamps
+    fn wall_time(&self) -> std::time::SystemTime;
+
+    /// Whole seconds since the Unix epoch
+    fn unix_secs(&self) -> u64 {
+        self.wall_time()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> tokio::time::Instant {
+        tokio::time::Instant::now()
+    }
+
+    fn wall_time(&self) -> std::time::SystemTime {
+        std::time::SystemTime::now()
+    }
+}
+
+/// Deterministic clock for tests.
+///
+/// Monotonic time is tokio's, so `tokio::time::pause`/`advance` drive it. Wall
+/// time starts at a fixed point and moves in lockstep, unlike `SystemTime::now()`
+/// which ignores a paused runtime.
+#[derive(Debug, Clone)]
+pub struct TestClock {
+    origin: tokio::time::Instant,
+    wall_origin: std::time::SystemTime,
+}
+
+impl TestClock {
+    pub fn new(wall_origin: std::time::SystemTime) -> Self {
+        Self {
+            origin: tokio::time::Instant::now(),
+            wall_origin,
+        }
+    }
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new(std::time::UNIX_EPOCH)
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> tokio::time::Instant {
+        tokio::time::Instant::now()
+    }
+
+    fn wall_time(&self) -> std::time::SystemTime {
+        self.wall_origin + self.origin.elapsed()
+    }
+}
+
+// =============================================================================
+// CLIENT POLICY
+// =============================================================================
+
+/// Per-client call behaviour shared by every generated method.
+///
+/// **Pattern:** Resilience settings live on the client handle, not in each call
+/// site, so orchestration code like `OrderService` gets them for free. Cloning a
+/// client and changing its policy gives a per-call override without touching
+/// other holders of the same actor.
+#[derive(Debug, Clone, Default)]
+pub struct ClientPolicy {
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    backpressure: Backpressure,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    caller: Option<String>,
+}
+
+/// What a client does when the actor's mailbox is full
+///
+/// A client can only wait or give up; it cannot evict requests already queued.
+/// Dropping the oldest request instead needs a queue the sender owns, so it is
+/// offered only by [`PriorityMailbox::with_drop_oldest`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait for room; queuing delay is unbounded
+    #[default]
+    Block,
+    /// Fail at once with the [`BUSY`] error, so the caller can shed or reroute the work
+    FailFast,
+}
+
+/// The error a [`Backpressure::FailFast`] client returns when the mailbox is
+/// full; match on it to tell "try later" apart from a failed request
+pub const BUSY: &str = "Busy: actor mailbox is full";
+
+/// Exponential backoff for transient failures (currently: timeouts).
+///
+/// Domain errors and a closed actor are never retried. Retrying a timed-out
+/// mutation can apply it twice if the actor was merely slow, so only enable
+/// retries on clients whose operations are idempotent or where that's acceptable.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Randomize each delay to 50-100% of its nominal value
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let capped = exponential.min(self.max_backoff);
+        if self.jitter {
+            capped.mul_f64(0.5 + random_fraction() / 2.0)
+        } else {
+            capped
+        }
+    }
+}
+
+/// A random value in [0, 1) without pulling in a RNG crate
+fn random_fraction() -> f64 {
+    (random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Randomly seeded bits from std, without pulling in a rand crate
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+impl ClientPolicy {
+    /// This policy without retries, for requests that must not run twice
+    fn without_retry(&self) -> Self {
+        Self {
+            retry: None,
+            ..self.clone()
+        }
+    }
+
+    /// Put one request in the actor's mailbox according to the backpressure setting
+    async fn send<R>(&self, sender: &mpsc::Sender<R>, request: R) -> Result<(), String> {
+        match self.backpressure {
+            Backpressure::Block => sender.send(request).await.map_err(|e| e.to_string()),
+            Backpressure::FailFast => sender.try_send(request).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => BUSY.to_string(),
+                mpsc::error::TrySendError::Closed(_) => "channel closed".to_string(),
+            }),
+        }
+    }
+
+    /// Run `attempt` under the timeout, retry and circuit breaker settings.
+    ///
+    /// An attempt resolves to `Err` only for transport failures (actor gone,
+    /// timeout); the inner `Result` is the service's answer and is passed through.
+    async fn call<T, E, F, Fut>(&self, mut attempt: F) -> Result<T, String>
+    where
+        E: std::fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<Result<T, E>, String>>,
+    {
+        // One token per logical call; retries of a timed-out attempt are free
+        if let Some(limiter) = &self.rate_limiter {
+            let caller = self.caller.as_deref().unwrap_or(ANONYMOUS_CALLER);
+            limiter.try_acquire(caller).map_err(|retry_after| {
+                format!(
+                    "Rate limited: retry after {}ms",
+                    retry_after.as_millis().max(1)
+                )
+            })?;
+        }
+
+        let max_attempts = self.retry.as_ref().map_or(1, |r| r.max_attempts.max(1));
+        let mut attempt_no = 1;
+
+        loop {
+            if let Some(breaker) = &self.breaker {
+                breaker.try_acquire()?;
+            }
+
+            let (outcome, timed_out) = match self.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, attempt()).await {
+                    Ok(outcome) => (outcome, false),
+                    Err(_) => (
+                        Err(format!("Request timed out after {}ms", timeout.as_millis())),
+                        true,
+                    ),
+                },
+                None => (attempt().await, false),
+            };
+
+            if let Some(breaker) = &self.breaker {
+                breaker.record(outcome.is_ok());
+            }
+
+            match outcome {
+                Ok(result) => return result.map_err(|e| e.to_string()),
+                Err(error) => match &self.retry {
+                    Some(retry) if timed_out && attempt_no < max_attempts => {
+                        let backoff = retry.backoff(attempt_no);
+                        warn!(
+                            attempt = attempt_no,
+                            max_attempts,
+                            backoff_ms = backoff.as_millis(),
+                            "Request timed out, retrying"
+                        );
+                        tokio::time::sleep(backoff).await;
+                        attempt_no += 1;
+                    }
+                    _ => {
+                        warn!(error = %error, attempts = attempt_no, "Request failed");
+                        return Err(error);
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Circuit breaker settings
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive transport failures before the circuit opens
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a trial request is let through
+    pub reset_timeout: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            reset_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Closed/open/half-open breaker shared by every clone of a client.
+///
+/// Only transport failures (timeouts, a closed actor) count; a `NotFound` from
+/// a healthy actor is a successful round trip. While open, calls fail
+/// immediately with "Circuit open" instead of piling onto a dead or overloaded
+/// actor.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: std::sync::Mutex<BreakerState>,
+    clock: SharedClock,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: tokio::time::Instant },
+    HalfOpen { since: tokio::time::Instant },
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: std::sync::Mutex::new(BreakerState::Closed { failures: 0 }),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.lock(), BreakerState::Closed { .. })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn try_acquire(&self) -> Result<(), String> {
+        let mut state = self.lock();
+        let now = self.clock.now();
+        match *state {
+            BreakerState::Closed { .. } => Ok(()),
+            // Also re-admit a trial if the previous one never reported back
+            BreakerState::Open { until } if now >= until => {
+                info!("Circuit half-open, allowing trial request");
+                *state = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+            BreakerState::HalfOpen { since } if now >= since + self.config.reset_timeout => {
+                *state = BreakerState::HalfOpen { since: now };
+                Ok(())
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                Err("Circuit open: downstream actor unavailable".to_string())
+            }
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut state = self.lock();
+        *state = match (*state, success) {
+            (BreakerState::Closed { .. }, true) => BreakerState::Closed { failures: 0 },
+            (_, true) => {
+                info!("Circuit closed");
+                BreakerState::Closed { failures: 0 }
+            }
+            (BreakerState::Closed { failures }, false)
+                if failures + 1 < self.config.failure_threshold =>
+            {
+                BreakerState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, false) => {
+                warn!(
+                    reset_timeout_ms = self.config.reset_timeout.as_millis(),
+                    "Circuit opened"
+                );
+                BreakerState::Open {
+                    until: self.clock.now() + self.config.reset_timeout,
+                }
+            }
+        };
+    }
+}
+
+/// Bucket key for clients that never called `with_caller`
+const ANONYMOUS_CALLER: &str = "anonymous";
+
+/// Token bucket settings for a [`RateLimiter`]
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Tokens added per second, i.e. the sustained request rate per caller
+    pub per_second: f64,
+    /// Bucket size: how many requests a caller may make back to back
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_second: 100.0,
+            burst: 100,
+        }
+    }
+}
+
+/// Per-caller token buckets shared by every client it is attached to.
+///
+/// Each client handle names its caller with `with_caller`; handles with the
+/// same identity draw from the same bucket even across services, so one
+/// `Arc<RateLimiter>` can cap a tenant's total traffic. A call that finds the
+/// bucket empty fails at once with "Rate limited: retry after Nms" and never
+/// reaches the mailbox, so a noisy caller can't crowd out the others.
+///
+/// Buckets that have refilled are dropped once per refill period, so callers
+/// that come and go (one identity per connection, say) don't pile up.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: std::sync::Mutex<Buckets>,
+    clock: SharedClock,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: tokio::time::Instant,
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    by_caller: HashMap<String, TokenBucket>,
+    /// When refilled buckets were last dropped
+    swept_at: Option<tokio::time::Instant>,
+}
+
+impl Buckets {
+    /// Drop every bucket that has refilled to `burst`, at most once per refill
+    /// period. A full bucket acts exactly like a missing one, so no caller's
+    /// next decision changes.
+    fn sweep(&mut self, now: tokio::time::Instant, burst: f64, per_second: f64) {
+        if per_second <= 0.0 {
+            return;
+        }
+        let refill = Duration::try_from_secs_f64(burst / per_second).unwrap_or(Duration::MAX);
+        let swept_at = *self.swept_at.get_or_insert(now);
+        if now.saturating_duration_since(swept_at) < refill {
+            return;
+        }
+        self.by_caller.retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.refilled_at);
+            bucket.tokens + elapsed.as_secs_f64() * per_second < burst
+        });
+        self.swept_at = Some(now);
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: std::sync::Mutex::new(Buckets::default()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Take one token from `caller`'s bucket, or return how long until one is available
+    pub fn try_acquire(&self, caller: &str) -> Result<(), Duration> {
+        let now = self.clock.now();
+        let burst = f64::from(self.config.burst.max(1));
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets.sweep(now, burst, self.config.per_second);
+        let bucket = buckets
+            .by_caller
+            .entry(caller.to_string())
+            .or_insert(TokenBucket {
+                tokens: burst,
+                refilled_at: now,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.config.per_second).min(burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.config.per_second > 0.0 {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.config.per_second,
+            ))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
+
 // =============================================================================
 // DOMAIN TYPES
 // =============================================================================
@@ -119,7 +620,9 @@ macro_rules! client_method {
 /// Business domain entities. Pure data structures with no actor-specific concerns.
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct User {
+    #[cfg_attr(feature = "serde", serde(default))]
     pub id: String,
     pub name: String,
     pub email: String,
@@ -136,6 +639,7 @@ impl User {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Product {
     pub id: String,
     pub name: String,
@@ -153,6 +657,7 @@ impl Product {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Order {
     pub id: String,
     pub user_id: String,
@@ -179,1156 +684,13206 @@ impl Order {
     }
 }
 
-// =============================================================================
-// MESSAGE ENUMS
-// =============================================================================
+/// Rules every stored entity must satisfy, whatever path it came in through.
+///
+/// Services re-check them after each mutation when [`INVARIANT_CHECKS`] is on
+/// and roll the mutation back on a violation. Builders validate *input*; this
+/// catches handler bugs that would otherwise corrupt the store silently.
+pub trait Invariants {
+    fn check_invariants(&self) -> Result<(), String>;
+}
 
-/// Typed message enums for actor communication. Each variant includes parameters
-/// and a oneshot channel for responses.
+/// Invariant checking runs in debug and test builds, and compiles away in release
+pub const INVARIANT_CHECKS: bool = cfg!(debug_assertions);
 
-#[derive(Debug)]
-pub enum UserRequest {
-    GetUser {
-        id: String,
-        respond_to: ServiceResponse<Option<User>, UserError>,
-    },
-    CreateUser {
-        user: User,
-        respond_to: ServiceResponse<String, UserError>,
-    },
-    UpdateUser {
-        id: String,
-        user: User,
-        respond_to: ServiceResponse<(), UserError>,
-    },
-    ListUsers {
-        respond_to: ServiceResponse<Vec<User>, UserError>,
-    },
-    Shutdown,
-    #[cfg(test)]
-    GetUserCount {
-        respond_to: ServiceResponse<usize, UserError>,
-    },
+impl Invariants for User {
+    fn check_invariants(&self) -> Result<(), String> {
+        if self.email.is_empty() {
+            return Err("user email must not be empty".to_string());
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
-pub enum ProductRequest {
-    GetProduct {
-        id: String,
-        respond_to: ServiceResponse<Option<Product>, ProductError>,
-    },
-    CheckStock {
-        id: String,
-        respond_to: ServiceResponse<u32, ProductError>,
-    },
-    ReserveStock {
-        id: String,
-        quantity: u32,
-        respond_to: ServiceResponse<(), ProductError>,
-    },
-    Shutdown,
+impl Invariants for Product {
+    fn check_invariants(&self) -> Result<(), String> {
+        if !self.price.is_finite() || self.price < 0.0 {
+            return Err(format!(
+                "product price must be non-negative, got {}",
+                self.price
+            ));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
-pub enum OrderRequest {
-    CreateOrder {
-        order: Order,
-        respond_to: ServiceResponse<String, OrderError>,
-    },
-    GetOrder {
-        id: String,
-        respond_to: ServiceResponse<Option<Order>, OrderError>,
-    },
-    Shutdown,
+impl Invariants for Order {
+    fn check_invariants(&self) -> Result<(), String> {
+        if self.quantity == 0 {
+            return Err("order quantity must be positive".to_string());
+        }
+        if !self.total.is_finite() || self.total < 0.0 {
+            return Err(format!(
+                "order total must be non-negative, got {}",
+                self.total
+            ));
+        }
+        Ok(())
+    }
 }
 
-// =============================================================================
-// USER SERVICE (SUB-ACTOR)
-// =============================================================================
+/// One rejected field of an incoming payload
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
 
-/// User-specific error types
-#[derive(Debug, Clone)]
-pub enum UserError {
-    NotFound(String),
-    AlreadyExists(String),
-    ValidationError(String),
-    DatabaseError(String),
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
 }
 
-impl std::fmt::Display for UserError {
+impl std::fmt::Display for FieldError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            UserError::NotFound(id) => write!(f, "User not found: {}", id),
-            UserError::AlreadyExists(id) => write!(f, "User already exists: {}", id),
-            UserError::ValidationError(msg) => write!(f, "User validation error: {}", msg),
-            UserError::DatabaseError(msg) => write!(f, "User database error: {}", msg),
-        }
+        write!(f, "{}: {}", self.field, self.message)
     }
 }
 
-impl std::error::Error for UserError {}
-
-/// Generic type aliases for service communication
-pub type ServiceResult<T, E> = std::result::Result<T, E>;
-pub type ServiceResponse<T, E> = oneshot::Sender<ServiceResult<T, E>>;
-
-/// Macro for clean error response handling
-macro_rules! send_error {
-    ($respond_to:expr, $error:expr) => {{
-        let _ = $respond_to.send(Err($error));
-        return;
-    }};
+/// Render field errors as one line, e.g. `name: required, email: ...`
+fn join_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(FieldError::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-/// User management actor with instrumented handlers. Demonstrates domain-specific
-/// sub-actor pattern with automatic tracing.
-/// <!-- anchor: user-service -->
-pub struct UserService {
-    receiver: mpsc::Receiver<UserRequest>,
-    users: HashMap<String, User>,
-    next_id: u64,
+/// Input rules for payloads arriving from outside (create and update requests).
+///
+/// Unlike [`Invariants`], which guard the store against handler bugs, these
+/// reject bad *requests*, and report every offending field at once so a
+/// caller can fix a form in one round trip. Builders and services run the
+/// same rules.
+pub trait Validate {
+    fn validate(&self) -> Result<(), Vec<FieldError>>;
 }
 
-impl UserService {
-    pub fn new(buffer_size: usize) -> (Self, UserClient) {
-        let (sender, receiver) = mpsc::channel(buffer_size);
-        let service = Self {
-            receiver,
-            users: HashMap::new(),
-            next_id: 1,
+impl Validate for User {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "required"));
+        }
+        let valid_email = match self.email.split_once('@') {
+            Some((local, domain)) => {
+                !local.is_empty()
+                    && !domain.is_empty()
+                    && !domain.contains('@')
+                    && !self.email.contains(char::is_whitespace)
+            }
+            None => false,
         };
-        let client = UserClient::new(sender);
-        (service, client)
+        if !valid_email {
+            errors.push(FieldError::new("email", "must look like name@domain"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
+}
 
-    /// Main actor loop with tracing
-    ///
-    /// **Pattern:** The run loop is instrumented at the top level and delegates
-    /// to specific handlers for each message type. This provides clean separation
-    /// and makes it easy to add new message types.
-    #[instrument(name = "user_service", skip(self))]
-    pub async fn run(mut self) {
-        info!("UserService starting");
+impl Validate for Product {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if self.id.is_empty() {
+            errors.push(FieldError::new("id", "required"));
+        }
+        if !self.price.is_finite() || self.price < 0.0 {
+            errors.push(FieldError::new("price", "must be a non-negative number"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
 
-        while let Some(msg) = self.receiver.recv().await {
-            match msg {
-                UserRequest::GetUser { id, respond_to } => {
-                    self.handle_get_user(id, respond_to);
-                }
-                UserRequest::CreateUser { user, respond_to } => {
-                    self.handle_create_user(user, respond_to).await;
-                }
-                UserRequest::UpdateUser {
-                    id,
-                    user,
-                    respond_to,
-                } => {
-                    self.handle_update_user(id, user, respond_to).await;
-                }
-                UserRequest::ListUsers { respond_to } => {
-                    self.handle_list_users(respond_to);
-                }
-                UserRequest::Shutdown => {
-                    info!("UserService shutting down");
-                    break;
-                }
-                #[cfg(test)]
-                UserRequest::GetUserCount { respond_to } => {
-                    let _ = respond_to.send(Ok(self.users.len()));
-                }
+impl Validate for Order {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        for (field, value) in [
+            ("id", &self.id),
+            ("user_id", &self.user_id),
+            ("product_id", &self.product_id),
+        ] {
+            if value.is_empty() {
+                errors.push(FieldError::new(field, "required"));
             }
         }
-
-        info!("UserService stopped");
+        if self.quantity == 0 {
+            errors.push(FieldError::new("quantity", "must be positive"));
+        }
+        if !self.total.is_finite() || self.total < 0.0 {
+            errors.push(FieldError::new("total", "must be a non-negative number"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
+}
 
-    /// **Sync Handler Example** - Fast, in-memory operation
-    ///
-    /// Use sync handlers for:
-    /// - In-memory data lookups
-    /// - Simple computations
-    /// - Operations that don't need `await`
-    ///
-    /// **Tracing:** Fields extract key business data, `skip` excludes large/sensitive data
-    #[instrument(fields(user_id = %id), skip(self, respond_to))]
-    fn handle_get_user(&self, id: String, respond_to: ServiceResponse<Option<User>, UserError>) {
-        debug!("Processing get_user request");
+// =============================================================================
+// DOMAIN TYPE BUILDERS
+// =============================================================================
 
-        let user = self.users.get(&id).cloned();
+// Builders for callers that assemble payloads from untrusted input (CLI, gateways).
+// Required fields are constructor arguments, so they can't be forgotten; `build()`
+// runs the same validation the services would, but before a message is ever sent.
 
-        match &user {
-            Some(user) => info!(user_name = %user.name, "User found"),
-            None => debug!("User not found"),
+impl User {
+    pub fn builder(name: impl Into<String>, email: impl Into<String>) -> UserBuilder {
+        UserBuilder {
+            name: name.into(),
+            email: email.into(),
         }
-
-        let _ = respond_to.send(Ok(user));
     }
+}
 
-    /// **Async Handler Example** - I/O operation with validation
-    ///
-    /// Use async handlers for:
-    /// - Database operations
-    /// - External API calls
-    /// - Complex validation logic
-    /// - Any operation that needs `await`
-    ///
-    /// **Security:** Skip the full `user` object but log specific safe fields
-    #[instrument(fields(user_name = %user.name, user_email = %user.email), skip(self, user, respond_to))]
-    async fn handle_create_user(
-        &mut self,
-        user: User,
-        respond_to: ServiceResponse<String, UserError>,
-    ) {
-        debug!("Processing create_user request");
-
-        let result = if user.email.is_empty() {
-            error!("Validation failed: empty email");
-            Err(UserError::ValidationError("Email required".to_string()))
-        } else {
-            let id = format!("user_{}", self.next_id);
-            self.next_id += 1;
-            self.users.insert(id.clone(), user);
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserBuilder {
+    name: String,
+    email: String,
+}
 
-            info!(user_id = %id, "User created successfully");
-            Ok(id)
-        };
+impl UserBuilder {
+    pub fn build(self) -> Result<User, UserError> {
+        let user = User::new(self.name, self.email);
+        user.validate().map_err(UserError::InvalidFields)?;
+        Ok(user)
+    }
+}
 
-        let _ = respond_to.send(result);
+impl Product {
+    pub fn builder(id: impl Into<String>, name: impl Into<String>) -> ProductBuilder {
+        ProductBuilder {
+            id: id.into(),
+            name: name.into(),
+            price: 0.0,
+        }
     }
+}
 
-    /// **Update Handler** - Modification operation with validation
-    #[instrument(fields(user_id = %id, user_name = %user.name), skip(self, user, respond_to))]
-    async fn handle_update_user(
-        &mut self,
-        id: String,
-        user: User,
-        respond_to: ServiceResponse<(), UserError>,
-    ) {
-        debug!("Processing update_user request");
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProductBuilder {
+    id: String,
+    name: String,
+    price: f64,
+}
 
-        let result = if self.users.contains_key(&id) {
-            self.users.insert(id.clone(), user);
-            info!("User updated successfully");
-            Ok(())
-        } else {
-            error!("User not found for update");
-            Err(UserError::NotFound(id))
-        };
+impl ProductBuilder {
+    pub fn price(mut self, price: f64) -> Self {
+        self.price = price;
+        self
+    }
 
-        let _ = respond_to.send(result);
+    pub fn build(self) -> Result<Product, ProductError> {
+        let product = Product::new(self.id, self.name, self.price);
+        product.validate().map_err(ProductError::InvalidFields)?;
+        Ok(product)
     }
+}
 
-    /// **Collection Handler** - Returns multiple items
-    #[instrument(skip(self, respond_to))]
-    fn handle_list_users(&self, respond_to: ServiceResponse<Vec<User>, UserError>) {
-        debug!("Processing list_users request");
+/// The fields of a [`Product`] to overwrite; unset fields are left alone
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProductPatch {
+    pub name: Option<String>,
+    pub price: Option<f64>,
+}
 
-        let users: Vec<User> = self.users.values().cloned().collect();
-        info!(user_count = users.len(), "Listed users");
+impl ProductPatch {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
 
-        let _ = respond_to.send(Ok(users));
+    pub fn price(mut self, price: f64) -> Self {
+        self.price = Some(price);
+        self
     }
-}
 
-// =============================================================================
-// USER CLIENT
-// =============================================================================
+    fn apply(&self, product: &mut Product) {
+        if let Some(name) = &self.name {
+            product.name = name.clone();
+        }
+        if let Some(price) = self.price {
+            product.price = price;
+        }
+    }
+}
 
-/// Client for UserService with macro-generated methods. Thin wrapper around
-/// message channels with automatic error handling.
+impl Order {
+    pub fn builder(
+        id: impl Into<String>,
+        user_id: impl Into<String>,
+        product_id: impl Into<String>,
+    ) -> OrderBuilder {
+        OrderBuilder {
+            id: id.into(),
+            user_id: user_id.into(),
+            product_id: product_id.into(),
+            quantity: 1,
+            total: 0.0,
+        }
+    }
+}
 
-#[derive(Clone)]
-pub struct UserClient {
-    sender: mpsc::Sender<UserRequest>,
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderBuilder {
+    id: String,
+    user_id: String,
+    product_id: String,
+    quantity: u32,
+    total: f64,
 }
 
-impl UserClient {
-    pub fn new(sender: mpsc::Sender<UserRequest>) -> Self {
-        Self { sender }
+impl OrderBuilder {
+    pub fn quantity(mut self, quantity: u32) -> Self {
+        self.quantity = quantity;
+        self
     }
 
-    /// Manual methods for special cases (no response needed)
-    #[instrument(skip(self))]
-    pub async fn shutdown(&self) -> Result<(), String> {
-        debug!("Sending shutdown request");
-        self.sender
-            .send(UserRequest::Shutdown)
-            .await
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    pub fn total(mut self, total: f64) -> Self {
+        self.total = total;
+        self
     }
-}
 
-// Generate client methods with automatic tracing
-client_method!(UserClient => fn get_user(id: String) -> Option<User> as UserRequest::GetUser);
-client_method!(UserClient => fn create_user(user: User) -> String as UserRequest::CreateUser);
-client_method!(UserClient => fn update_user(id: String, user: User) -> () as UserRequest::UpdateUser);
-client_method!(UserClient => fn list_users() -> Vec<User> as UserRequest::ListUsers);
-
-// Test-only method for internal state inspection
-// **Pattern:** Use #[cfg(test)] messages to extract actor internal state for testing
-#[cfg(test)]
-client_method!(UserClient => fn get_user_count() -> usize as UserRequest::GetUserCount);
+    pub fn build(self) -> Result<Order, OrderError> {
+        let order = Order::new(
+            self.id,
+            self.user_id,
+            self.product_id,
+            self.quantity,
+            self.total,
+        );
+        order.validate().map_err(OrderError::InvalidFields)?;
+        Ok(order)
+    }
+}
 
 // =============================================================================
-// INGREDIENT 6: PRODUCT SERVICE (SECOND SUB-ACTOR)
+// MESSAGE ENUMS
 // =============================================================================
 
-/// Product-specific error types
-#[derive(Debug, Clone)]
-pub enum ProductError {
-    NotFound(String),
-    InsufficientStock { requested: u32, available: u32 },
-    InvalidQuantity(u32),
-    DatabaseError(String),
+/// Mailbox health reported by an actor in answer to a `GetStats` probe.
+///
+/// The probe queues like any other request, so `queue_time` is what a request
+/// sent at the same moment would have waited, and `depth` is what is still
+/// queued behind it. `queue_time` is measured from the moment the client sent
+/// the probe, which [`ServiceResponse::sent_at`] records.
+///
+/// Actors also publish the same numbers as gauges for every message they take
+/// off the mailbox (see [`MailboxStats::publish`]), so a scraper sees mailbox
+/// health without anyone sending a probe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MailboxStats {
+    pub depth: usize,
+    pub capacity: usize,
+    pub queue_time: Duration,
 }
 
-impl std::fmt::Display for ProductError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ProductError::NotFound(id) => write!(f, "Product not found: {}", id),
-            ProductError::InsufficientStock {
-                requested,
-                available,
-            } => {
-                write!(
-                    f,
-                    "Insufficient stock: requested {}, available {}",
-                    requested, available
-                )
-            }
-            ProductError::InvalidQuantity(qty) => write!(f, "Invalid quantity: {}", qty),
-            ProductError::DatabaseError(msg) => write!(f, "Product database error: {}", msg),
+impl MailboxStats {
+    /// `batched` counts messages the actor took off the queue but has not handled yet
+    fn sample<R>(
+        receiver: &mpsc::Receiver<R>,
+        batched: usize,
+        sent_at: tokio::time::Instant,
+    ) -> Self {
+        Self {
+            depth: receiver.len() + batched,
+            capacity: receiver.max_capacity(),
+            queue_time: sent_at.elapsed(),
         }
     }
-}
 
-impl std::error::Error for ProductError {}
+    /// Fraction of the mailbox in use, from 0.0 to 1.0
+    pub fn saturation(&self) -> f64 {
+        self.depth as f64 / self.capacity.max(1) as f64
+    }
 
-/// ## Ingredient 6: Additional Sub-Actors
-///
-/// **Pattern:** Each domain gets its own actor following the same structure.
-/// This demonstrates how to scale the pattern to multiple domains.
+    /// Set the `actor_mailbox_*` gauges for `entity`, e.g. `entity="User"`
+    ///
+    /// Each sample replaces the previous one, so with several actors of one
+    /// entity (product shards) the gauges show whichever sampled last.
+    fn publish(&self, entity: &str) {
+        let labels = format!("entity=\"{}\"", entity);
+        let metrics = Metrics::global();
+        metrics.set_gauge(
+            "actor_mailbox_depth",
+            "Messages waiting in the actor mailbox",
+            &labels,
+            self.depth as i64,
+        );
+        metrics.set_gauge(
+            "actor_mailbox_capacity",
+            "Capacity of the actor mailbox",
+            &labels,
+            self.capacity as i64,
+        );
+        metrics.set_gauge(
+            "actor_mailbox_queue_time_ms",
+            "How long the last message waited in the mailbox",
+            &labels,
+            self.queue_time.as_millis() as i64,
+        );
+    }
+
+    /// Record as structured fields, warning once the mailbox is nearly full
+    fn log(&self, actor: &'static str) {
+        let queue_time_ms = self.queue_time.as_millis() as u64;
+        if self.saturation() >= 0.8 {
+            warn!(
+                target: MAILBOX_TARGET,
+                actor,
+                depth = self.depth,
+                capacity = self.capacity,
+                queue_time_ms,
+                "Mailbox nearly full"
+            );
+        } else {
+            debug!(
+                target: MAILBOX_TARGET,
+                actor,
+                depth = self.depth,
+                capacity = self.capacity,
+                queue_time_ms,
+                "Mailbox stats"
+            );
+        }
+    }
+}
+
+/// **Load Shedding** - Refuse work the actor cannot get to in time
 ///
-/// **Benefits:**
-/// - **Domain isolation** - Each actor is responsible for one business domain
-/// - **Independent scaling** - Actors can be scaled independently
-/// - **Clear boundaries** - Easy to understand what each actor does
-/// - **Parallel development** - Different teams can work on different actors
-/// <!-- anchor: product-service -->
-pub struct ProductService {
-    receiver: mpsc::Receiver<ProductRequest>,
-    products: HashMap<String, Product>,
-    stock: HashMap<String, u32>,
+/// Once `threshold` messages are waiting, sheddable requests are answered at
+/// once with an `Overloaded` error instead of joining an ever longer queue.
+/// Shedding stops only when the queue has drained to half the threshold, so a
+/// queue hovering around the threshold does not flip it on and off per message.
+/// Pings, stats probes and lifecycle messages are always served, so health
+/// checks keep reporting an overloaded actor as alive.
+#[derive(Debug, Clone, Copy)]
+struct LoadShedder {
+    threshold: usize,
+    /// Depth at or below which shedding stops again
+    resume: usize,
+    shedding: bool,
 }
 
-impl ProductService {
-    pub fn new(buffer_size: usize) -> (Self, ProductClient) {
-        let (sender, receiver) = mpsc::channel(buffer_size);
-        let service = Self {
-            receiver,
-            products: HashMap::new(),
-            stock: HashMap::new(),
+impl LoadShedder {
+    fn new(threshold: usize) -> Self {
+        let threshold = threshold.max(1);
+        Self {
+            threshold,
+            resume: threshold / 2,
+            shedding: false,
+        }
+    }
+
+    /// Whether to shed at queue `depth`; logs when shedding starts and stops
+    fn overloaded(&mut self, depth: usize, actor: &'static str) -> bool {
+        let overloaded = if self.shedding {
+            depth > self.resume
+        } else {
+            depth >= self.threshold
         };
-        let client = ProductClient::new(sender);
-        (service, client)
+        if overloaded != self.shedding {
+            self.shedding = overloaded;
+            if overloaded {
+                warn!(
+                    target: MAILBOX_TARGET,
+                    code = %EventCode::ActorOverloaded,
+                    actor,
+                    depth,
+                    threshold = self.threshold,
+                    "Overloaded, shedding requests"
+                );
+            } else {
+                info!(
+                    target: MAILBOX_TARGET,
+                    actor,
+                    depth,
+                    resume = self.resume,
+                    "Load back to normal, shedding off"
+                );
+            }
+        }
+        overloaded
     }
+}
 
-    #[instrument(name = "product_service", skip(self))]
-    pub async fn run(mut self) {
-        info!("ProductService starting");
+/// Typed message enums for actor communication. Each variant includes parameters
+/// and a oneshot channel for responses.
+
+#[derive(Debug)]
+pub enum UserRequest {
+    GetUser {
+        id: String,
+        respond_to: ServiceResponse<Option<User>, UserError>,
+    },
+    /// Every user with this email, via the email index rather than a scan
+    FindUsersByEmail {
+        email: String,
+        respond_to: ServiceResponse<Vec<User>, UserError>,
+    },
+    /// Every user in `ids` that exists, looked up in one actor pass
+    GetUsers {
+        ids: Vec<String>,
+        respond_to: ServiceResponse<HashMap<String, User>, UserError>,
+    },
+    /// Digest of every user, computed off the actor on the blocking pool
+    Checksum {
+        respond_to: ServiceResponse<u64, UserError>,
+    },
+    /// Slow, cancellable: abandoned as soon as the caller stops waiting
+    GenerateReport {
+        user_id: String,
+        respond_to: ServiceResponse<String, UserError>,
+    },
+    /// [`GenerateReport`](Self::GenerateReport) reporting each finished section
+    /// over `updates`, then the result; dropping the receiver cancels it
+    GenerateReportStreaming {
+        user_id: String,
+        updates: mpsc::Sender<Progress<String>>,
+    },
+    CreateUser {
+        user: User,
+        respond_to: ServiceResponse<String, UserError>,
+    },
+    /// Create every user or none; the IDs come back in input order
+    CreateUsers {
+        users: Vec<User>,
+        respond_to: ServiceResponse<Vec<String>, UserError>,
+    },
+    /// Hand out a fresh ID without creating anything yet
+    ReserveId {
+        respond_to: ServiceResponse<String, UserError>,
+    },
+    /// Create under a caller-supplied ID; fails if the ID is taken
+    CreateUserWithId {
+        id: String,
+        user: User,
+        respond_to: ServiceResponse<(), UserError>,
+    },
+    /// The ID of the user with `user.email`, creating the user if there is none
+    GetOrCreateUser {
+        user: User,
+        respond_to: ServiceResponse<String, UserError>,
+    },
+    UpdateUser {
+        id: String,
+        user: User,
+        respond_to: ServiceResponse<(), UserError>,
+    },
+    ListUsers {
+        respond_to: ServiceResponse<Vec<User>, UserError>,
+    },
+    /// Streams every user over `items` instead of collecting them into a `Vec`.
+    StreamUsers { items: mpsc::Sender<User> },
+    /// Subscribes to changes of a single user; `None` means the user does not exist.
+    WatchUser {
+        id: String,
+        respond_to: ServiceResponse<watch::Receiver<Option<User>>, UserError>,
+    },
+    /// Read-modify-write of one user in a single message
+    ModifyUser {
+        id: String,
+        modifier: Modifier<User, UserError>,
+        respond_to: ServiceResponse<(), UserError>,
+    },
+    Snapshot {
+        respond_to: ServiceResponse<UserSnapshot, UserError>,
+    },
+    /// Probe the mailbox
+    GetStats {
+        respond_to: ServiceResponse<MailboxStats, UserError>,
+    },
+    /// Liveness probe, answered as soon as it reaches the front of the mailbox
+    Ping {
+        respond_to: ServiceResponse<(), UserError>,
+    },
+    /// Merges `snapshot` into the live store, overwriting matching IDs
+    Import {
+        snapshot: UserSnapshot,
+        respond_to: ServiceResponse<usize, UserError>,
+    },
+    /// Refuse writes from now on but keep answering reads
+    Drain {
+        respond_to: ServiceResponse<(), UserError>,
+    },
+    /// Stop taking new requests, finish the queued ones, then acknowledge
+    Shutdown {
+        respond_to: ServiceResponse<(), UserError>,
+    },
+    /// How many users are stored
+    Count {
+        respond_to: ServiceResponse<usize, UserError>,
+    },
+    /// Whether `id` is stored, without cloning the user
+    Exists {
+        id: String,
+        respond_to: ServiceResponse<bool, UserError>,
+    },
+}
+
+impl UserRequest {
+    /// Lifecycle messages, exempt from fault injection
+    fn is_control(&self) -> bool {
+        matches!(
+            self,
+            UserRequest::Drain { .. } | UserRequest::Shutdown { .. }
+        )
+    }
+
+    /// What load shedding may refuse: everything but probes and lifecycle messages
+    fn is_sheddable(&self) -> bool {
+        !self.is_control()
+            && !matches!(
+                self,
+                UserRequest::Ping { .. } | UserRequest::GetStats { .. }
+            )
+    }
+}
+
+#[derive(Debug)]
+pub enum ProductRequest {
+    GetProduct {
+        id: String,
+        respond_to: ServiceResponse<Option<Product>, ProductError>,
+    },
+    /// Like `GetProduct`, but shares the stored product instead of copying it
+    GetProductShared {
+        id: String,
+        respond_to: ServiceResponse<Option<Arc<Product>>, ProductError>,
+    },
+    /// Every product in `ids` that exists, looked up in one actor pass
+    GetProducts {
+        ids: Vec<String>,
+        respond_to: ServiceResponse<HashMap<String, Product>, ProductError>,
+    },
+    CheckStock {
+        id: String,
+        respond_to: ServiceResponse<u32, ProductError>,
+    },
+    /// How many products are stored
+    Count {
+        respond_to: ServiceResponse<usize, ProductError>,
+    },
+    /// Whether `id` is stored, without cloning the product
+    Exists {
+        id: String,
+        respond_to: ServiceResponse<bool, ProductError>,
+    },
+    ReserveStock {
+        id: String,
+        quantity: u32,
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+    /// Put reserved units back, undoing a `ReserveStock`; never more than are reserved
+    ReleaseStock {
+        id: String,
+        quantity: u32,
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+    /// Take `quantity` out of stock for at most `ttl`; answers the hold's ID
+    HoldStock {
+        id: String,
+        quantity: u32,
+        ttl: Duration,
+        respond_to: ServiceResponse<u64, ProductError>,
+    },
+    /// Turn a hold on product `id` into a permanent reservation
+    ConfirmHold {
+        id: String,
+        hold: u64,
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+    /// Patch many products in one message; one result per patch, in order
+    UpdateProducts {
+        patches: Vec<(String, ProductPatch)>,
+        respond_to: ServiceResponse<Vec<Result<(), ProductError>>, ProductError>,
+    },
+    /// Read-modify-write of one product's stock level in a single message
+    ModifyStock {
+        id: String,
+        modifier: Modifier<u32, ProductError>,
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+    Snapshot {
+        respond_to: ServiceResponse<ProductSnapshot, ProductError>,
+    },
+    /// Probe the mailbox
+    GetStats {
+        respond_to: ServiceResponse<MailboxStats, ProductError>,
+    },
+    /// Liveness probe, answered as soon as it reaches the front of the mailbox
+    Ping {
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+    /// Merges `snapshot` into the live store, overwriting matching IDs
+    Import {
+        snapshot: ProductSnapshot,
+        respond_to: ServiceResponse<usize, ProductError>,
+    },
+    /// Refuse writes from now on but keep answering reads
+    Drain {
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+    /// Stop taking new requests, finish the queued ones, then acknowledge
+    Shutdown {
+        respond_to: ServiceResponse<(), ProductError>,
+    },
+}
+
+impl ProductRequest {
+    /// Lifecycle messages, exempt from fault injection
+    fn is_control(&self) -> bool {
+        matches!(
+            self,
+            ProductRequest::Drain { .. } | ProductRequest::Shutdown { .. }
+        )
+    }
+
+    /// What load shedding may refuse: everything but probes and lifecycle messages
+    fn is_sheddable(&self) -> bool {
+        !self.is_control()
+            && !matches!(
+                self,
+                ProductRequest::Ping { .. } | ProductRequest::GetStats { .. }
+            )
+    }
+
+    /// The product a request is about, if it names one
+    fn product_id(&self) -> Option<&str> {
+        match self {
+            ProductRequest::GetProduct { id, .. }
+            | ProductRequest::GetProductShared { id, .. }
+            | ProductRequest::CheckStock { id, .. }
+            | ProductRequest::Exists { id, .. }
+            | ProductRequest::ReserveStock { id, .. }
+            | ProductRequest::ReleaseStock { id, .. }
+            | ProductRequest::HoldStock { id, .. }
+            | ProductRequest::ConfirmHold { id, .. }
+            | ProductRequest::ModifyStock { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OrderRequest {
+    CreateOrder {
+        order: Order,
+        respond_to: ServiceResponse<String, OrderError>,
+    },
+    GetOrder {
+        id: String,
+        respond_to: ServiceResponse<Option<Order>, OrderError>,
+    },
+    /// How many orders are stored
+    Count {
+        respond_to: ServiceResponse<usize, OrderError>,
+    },
+    /// Whether `id` is stored, without cloning the order
+    Exists {
+        id: String,
+        respond_to: ServiceResponse<bool, OrderError>,
+    },
+    Snapshot {
+        respond_to: ServiceResponse<OrderSnapshot, OrderError>,
+    },
+    /// Probe the mailbox
+    GetStats {
+        respond_to: ServiceResponse<MailboxStats, OrderError>,
+    },
+    /// Liveness probe, answered as soon as it reaches the front of the mailbox
+    Ping {
+        respond_to: ServiceResponse<(), OrderError>,
+    },
+    /// Merges `snapshot` into the live store, overwriting matching IDs
+    Import {
+        snapshot: OrderSnapshot,
+        respond_to: ServiceResponse<usize, OrderError>,
+    },
+    /// Refuse writes from now on but keep answering reads
+    Drain {
+        respond_to: ServiceResponse<(), OrderError>,
+    },
+    /// Stop taking new requests, finish the queued ones, then acknowledge
+    Shutdown {
+        respond_to: ServiceResponse<(), OrderError>,
+    },
+}
+
+impl OrderRequest {
+    /// Lifecycle messages, exempt from fault injection
+    fn is_control(&self) -> bool {
+        matches!(
+            self,
+            OrderRequest::Drain { .. } | OrderRequest::Shutdown { .. }
+        )
+    }
+
+    /// What load shedding may refuse: everything but probes and lifecycle messages
+    fn is_sheddable(&self) -> bool {
+        !self.is_control()
+            && !matches!(
+                self,
+                OrderRequest::Ping { .. } | OrderRequest::GetStats { .. }
+            )
+    }
+}
+
+// =============================================================================
+// USER SERVICE (SUB-ACTOR)
+// =============================================================================
+
+/// User-specific error types
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UserError {
+    NotFound(String),
+    AlreadyExists(String),
+    ValidationError(String),
+    /// The payload failed [`Validate`]; every offending field is listed
+    InvalidFields(Vec<FieldError>),
+    DatabaseError(String),
+    /// The actor is draining and no longer accepts writes
+    Draining,
+    /// The actor shed the request because its mailbox is backed up
+    Overloaded,
+    /// A conditional update found the user in an unexpected state
+    PreconditionFailed(String),
+    /// The caller stopped waiting, so long-running work was abandoned
+    Cancelled,
+    /// The handler panicked before answering; the actor itself kept running
+    HandlerPanicked,
+}
+
+impl std::fmt::Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserError::NotFound(id) => write!(f, "User not found: {}", id),
+            UserError::AlreadyExists(id) => write!(f, "User already exists: {}", id),
+            UserError::ValidationError(msg) => write!(f, "User validation error: {}", msg),
+            UserError::InvalidFields(errors) => {
+                write!(f, "Invalid user: {}", join_field_errors(errors))
+            }
+            UserError::DatabaseError(msg) => write!(f, "User database error: {}", msg),
+            UserError::Draining => write!(f, "User service is draining"),
+            UserError::Overloaded => write!(f, "User service is overloaded"),
+            UserError::PreconditionFailed(id) => write!(f, "User precondition failed: {}", id),
+            UserError::Cancelled => write!(f, "User request cancelled"),
+            UserError::HandlerPanicked => write!(f, "User handler panicked"),
+        }
+    }
+}
+
+impl std::error::Error for UserError {}
+
+/// Generic type aliases for service communication
+pub type ServiceResult<T, E> = std::result::Result<T, E>;
+
+/// Identifies one end-to-end request across every actor it touches.
+///
+/// **Pattern:** The outermost client call mints the ID and records it as a
+/// `request_id` field on its span. [`ServiceResponse`] carries it through the
+/// mailbox and services handle the message inside [`RequestId::scope`], so any
+/// client call made by a handler (OrderService asking UserService, say) reuses
+/// the ID instead of minting its own. Grepping `request_id=req_7` then finds
+/// every log line of one order flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
+tokio::task_local! {
+    static CURRENT_REQUEST: Option<RequestId>;
+}
+
+impl RequestId {
+    fn next() -> Self {
+        static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed))
+    }
+
+    /// The ID of the request being handled on this task, if any
+    pub fn current() -> Option<Self> {
+        CURRENT_REQUEST.try_with(|id| *id).ok().flatten()
+    }
+
+    /// Run `f` with `id` as the current request ID
+    pub async fn scope<F: std::future::Future>(id: Option<Self>, f: F) -> F::Output {
+        CURRENT_REQUEST.scope(id, f).await
+    }
+
+    /// Run a client call under the current request ID, or under a fresh one
+    /// recorded on the current span when this is the outermost call
+    async fn propagate<F: std::future::Future>(f: F) -> F::Output {
+        let id = Self::current().unwrap_or_else(|| {
+            let id = Self::next();
+            tracing::Span::current().record("request_id", tracing::field::display(id));
+            id
+        });
+        Self::scope(Some(id), f).await
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "req_{}", self.0)
+    }
+}
+
+/// The reply half of a request: a oneshot sender plus the caller's span and
+/// request ID.
+///
+/// **Pattern:** The span is captured when the client builds the request, and
+/// the service runs the handler inside it. One trace then covers the client
+/// call, the actor's handling and the response, even though they run on
+/// different tasks.
+///
+/// **Panics:** A service arms each response with its `HandlerPanicked` error
+/// before dispatch. A response dropped without an answer, which is what a
+/// panicking handler leaves behind, sends that error instead of leaving the
+/// caller with a bare `channel closed`.
+#[derive(Debug)]
+pub struct ServiceResponse<T, E> {
+    /// Taken by [`send`](Self::send); `None` once answered
+    sender: Option<oneshot::Sender<ServiceResult<T, E>>>,
+    span: tracing::Span,
+    request_id: Option<RequestId>,
+    /// When the client created the request, for measuring time in the mailbox
+    sent_at: tokio::time::Instant,
+    /// Sent on drop if nobody answered
+    unanswered: Option<E>,
+}
+
+impl<T, E> ServiceResponse<T, E> {
+    /// A reply slot tied to the current span and request, and the receiver for the answer
+    pub fn channel() -> (Self, oneshot::Receiver<ServiceResult<T, E>>) {
+        let (sender, receiver) = oneshot::channel();
+        let respond_to = Self {
+            sender: Some(sender),
+            span: tracing::Span::current(),
+            request_id: RequestId::current(),
+            sent_at: tokio::time::Instant::now(),
+            unanswered: None,
+        };
+        (respond_to, receiver)
+    }
+
+    pub fn send(mut self, result: ServiceResult<T, E>) -> Result<(), ServiceResult<T, E>> {
+        self.unanswered = None;
+        match self.sender.take() {
+            Some(sender) => sender.send(result),
+            None => Err(result),
+        }
+    }
+
+    /// Answer with `error` if this response is dropped unanswered
+    fn answer_on_drop(&mut self, error: E) {
+        self.unanswered = Some(error);
+    }
+
+    /// The span of the client call waiting on this response
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// The request this response belongs to, if the caller was inside one
+    pub fn request_id(&self) -> Option<RequestId> {
+        self.request_id
+    }
+
+    /// When the client created the request; each retry gets a fresh response
+    pub fn sent_at(&self) -> tokio::time::Instant {
+        self.sent_at
+    }
+
+    /// Whether the caller has stopped waiting (dropped the call or timed out)
+    pub fn is_closed(&self) -> bool {
+        match &self.sender {
+            Some(sender) => sender.is_closed(),
+            None => true,
+        }
+    }
+
+    /// Resolves once the caller stops waiting; see [`Self::is_closed`]
+    pub async fn closed(&mut self) {
+        if let Some(sender) = &mut self.sender {
+            sender.closed().await
+        }
+    }
+}
+
+impl<T, E> Drop for ServiceResponse<T, E> {
+    fn drop(&mut self) {
+        if let (Some(sender), Some(error)) = (self.sender.take(), self.unanswered.take()) {
+            let _ = sender.send(Err(error));
+        }
+    }
+}
+
+/// Cooperative cancellation for long-running work.
+///
+/// Clones share one flag. The work checks [`is_cancelled`](Self::is_cancelled)
+/// at safe points, or `select!`s on [`cancelled`](Self::cancelled), and stops
+/// on its own terms, so it can clean up instead of being dropped mid-step.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<CancelState>);
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: std::sync::atomic::AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        if !self
+            .0
+            .cancelled
+            .swap(true, std::sync::atomic::Ordering::SeqCst)
+        {
+            self.0.notify.notify_waiters();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Resolves once [`cancel`](Self::cancel) has been called on any clone
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking, so a cancel in between still wakes us
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// One message of a streaming call: any number of steps, then exactly one `Done`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Progress<T> {
+    /// `done` of `total` stages finished, the latest being `stage`
+    Step {
+        done: usize,
+        total: usize,
+        stage: String,
+    },
+    /// The outcome; the stream ends after it
+    Done(Result<T, String>),
+}
+
+/// A client call running on its own task, so the caller can give up on it.
+///
+/// Cancelling drops the call's reply receiver; actors running cancellable work
+/// (see [`UserService::handle_generate_report`]) notice and stop.
+///
+/// ```rust
+/// let client = user_client.clone();
+/// let report = CallHandle::spawn(async move { client.generate_report(id).await });
+/// report.cancel();
+/// assert!(report.join().await.is_err());
+/// ```
+#[derive(Debug)]
+pub struct CallHandle<T> {
+    task: tokio::task::JoinHandle<Result<T, String>>,
+}
+
+impl<T: Send + 'static> CallHandle<T> {
+    /// Start `call` in the current span and request
+    pub fn spawn<F>(call: F) -> Self
+    where
+        F: std::future::Future<Output = Result<T, String>> + Send + 'static,
+    {
+        let call =
+            RequestId::scope(RequestId::current(), call).instrument(tracing::Span::current());
+        Self {
+            task: tokio::spawn(call),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.task.abort();
+    }
+
+    /// Wait for the answer; a cancelled call fails with "Request cancelled"
+    pub async fn join(self) -> Result<T, String> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) if e.is_cancelled() => Err("Request cancelled".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// A caller-supplied mutation shipped to an actor and run inside its loop.
+///
+/// The closure's own return value travels back on a separate oneshot (see
+/// [`Modifier::new`]), so message enums stay free of caller generics.
+pub struct Modifier<T, E>(ModifierFn<T, E>);
+
+type ModifierFn<T, E> = Box<dyn FnOnce(&mut T) -> Result<(), E> + Send>;
+
+impl<T, E> Modifier<T, E> {
+    /// Wrap `f`, returning the modifier plus a receiver for `f`'s output
+    pub fn new<R, F>(f: F) -> (Self, oneshot::Receiver<R>)
+    where
+        F: FnOnce(&mut T) -> Result<R, E> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (output, receiver) = oneshot::channel();
+        let modifier = Self(Box::new(move |value| {
+            let _ = output.send(f(value)?);
+            Ok(())
+        }));
+        (modifier, receiver)
+    }
+
+    fn apply(self, value: &mut T) -> Result<(), E> {
+        (self.0)(value)
+    }
+}
+
+impl<T, E> std::fmt::Debug for Modifier<T, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Modifier")
+    }
+}
+
+/// `caller_span()` and `request_id()` for a request enum: what `respond_to`
+/// carries in each listed variant, `None` for the rest. `variant()` names the
+/// message for logs, including the variants after `;` that have no `respond_to`.
+/// `reject()` answers a listed variant with an error and drops any other.
+macro_rules! caller_span {
+    ($request:ident, $error:ident => $($variant:ident),* $(; $($other:ident),*)? $(,)?) => {
+        impl $request {
+            fn reject(self, error: $error) {
+                match self {
+                    $($request::$variant { respond_to, .. } => {
+                        let _ = respond_to.send(Err(error));
+                    })*
+                    #[allow(unreachable_patterns)]
+                    _ => {}
+                }
+            }
+
+            fn variant(&self) -> &'static str {
+                match self {
+                    $($request::$variant { .. } => stringify!($variant),)*
+                    $($($request::$other { .. } => stringify!($other),)*)?
+                    #[allow(unreachable_patterns)]
+                    _ => stringify!($request),
+                }
+            }
+
+            fn caller_span(&self) -> Option<&tracing::Span> {
+                match self {
+                    $($request::$variant { respond_to, .. } => Some(respond_to.span()),)*
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                }
+            }
+
+            fn request_id(&self) -> Option<RequestId> {
+                match self {
+                    $($request::$variant { respond_to, .. } => respond_to.request_id(),)*
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                }
+            }
+
+            fn sent_at(&self) -> Option<tokio::time::Instant> {
+                match self {
+                    $($request::$variant { respond_to, .. } => Some(respond_to.sent_at()),)*
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                }
+            }
+
+            /// Make the response answer `error` if the handler drops it, e.g. by panicking
+            fn answer_on_drop(&mut self, error: $error) {
+                match self {
+                    $($request::$variant { respond_to, .. } => respond_to.answer_on_drop(error),)*
+                    #[allow(unreachable_patterns)]
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+caller_span!(UserRequest, UserError => GetUser, FindUsersByEmail, GetUsers, Checksum,
+    GenerateReport, CreateUser, CreateUsers, ReserveId, CreateUserWithId, GetOrCreateUser,
+    UpdateUser, ListUsers, WatchUser, ModifyUser, Snapshot, GetStats, Ping, Import, Drain,
+    Shutdown, Count, Exists; StreamUsers, GenerateReportStreaming);
+caller_span!(ProductRequest, ProductError => GetProduct, GetProductShared, GetProducts, CheckStock,
+    ReserveStock, ReleaseStock, HoldStock, ConfirmHold, UpdateProducts, ModifyStock, Snapshot,
+    GetStats, Ping, Import, Drain, Shutdown, Count, Exists);
+caller_span!(OrderRequest, OrderError => CreateOrder, GetOrder, Snapshot, GetStats, Ping, Import,
+    Drain, Shutdown, Count, Exists);
+
+/// Macro for clean error response handling
+macro_rules! send_error {
+    ($respond_to:expr, $error:expr) => {{
+        let _ = $respond_to.send(Err($error));
+        return;
+    }};
+}
+
+/// How [`UserService`] generates IDs for new users
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdStrategy {
+    /// `user_1`, `user_2`, ... - readable, and the counter survives snapshots
+    #[default]
+    Sequential,
+    /// `user_` plus 16 random hex digits - nothing to coordinate across stores
+    Random,
+}
+
+/// User management actor with instrumented handlers. Demonstrates domain-specific
+/// sub-actor pattern with automatic tracing.
+/// <!-- anchor: user-service -->
+pub struct UserService {
+    receiver: mpsc::Receiver<UserRequest>,
+    users: HashMap<String, User>,
+    /// Secondary index: email -> IDs of the users with that email
+    by_email: HashMap<String, BTreeSet<String>>,
+    watchers: HashMap<String, watch::Sender<Option<User>>>,
+    events: Option<EventBusClient>,
+    audit: Option<AuditClient>,
+    next_id: u64,
+    faults: Option<FaultInjector>,
+    slow_handler: Duration,
+    batch_size: usize,
+    /// Taken from the mailbox in the current batch but not yet handled
+    batched: usize,
+    shedder: Option<LoadShedder>,
+    draining: bool,
+    shutdown_acks: Vec<ServiceResponse<(), UserError>>,
+    on_stop: Option<StopHook<UserSnapshot>>,
+    id_strategy: IdStrategy,
+    capacity: Option<usize>,
+    /// Use order for LRU eviction: tick -> ID, oldest first
+    recency: BTreeMap<u64, String>,
+    last_used: HashMap<String, u64>,
+    next_tick: u64,
+    on_evict: Option<EvictHook<User>>,
+}
+
+impl UserService {
+    pub fn new(buffer_size: usize) -> (Self, UserClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            users: HashMap::new(),
+            by_email: HashMap::new(),
+            watchers: HashMap::new(),
+            events: None,
+            audit: None,
+            next_id: 1,
+            faults: None,
+            slow_handler: ActorConfig::default().slow_handler,
+            batch_size: ActorConfig::default().batch_size,
+            batched: 0,
+            shedder: None,
+            draining: false,
+            shutdown_acks: Vec::new(),
+            on_stop: None,
+            id_strategy: IdStrategy::default(),
+            capacity: None,
+            recency: BTreeMap::new(),
+            last_used: HashMap::new(),
+            next_tick: 0,
+            on_evict: None,
+        };
+        let client = UserClient::new(sender);
+        (service, client)
+    }
+
+    /// Publish lifecycle events to the given bus
+    pub fn with_event_bus(mut self, events: EventBusClient) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Record every mutation in the given audit log
+    pub fn with_audit(mut self, audit: AuditClient) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn with_id_strategy(mut self, id_strategy: IdStrategy) -> Self {
+        self.id_strategy = id_strategy;
+        self
+    }
+
+    /// Keep at most `capacity` users, evicting the least recently used.
+    ///
+    /// An evicted user is gone from this actor, so pair the limit with
+    /// [`on_evict`](Self::on_evict) to write it somewhere first.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Hand every user evicted by the capacity limit to `hook`, e.g. to persist it
+    pub fn on_evict(mut self, hook: impl FnMut(String, User) + Send + Sync + 'static) -> Self {
+        self.on_evict = Some(Box::new(hook));
+        self
+    }
+
+    /// Inject artificial latency and failures, for demos
+    pub fn with_faults(mut self, faults: FaultInjector) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Warn about any handler that takes longer than `threshold`
+    pub fn with_slow_handler(mut self, threshold: Duration) -> Self {
+        self.slow_handler = threshold;
+        self
+    }
+
+    /// Take up to `size` queued messages per wakeup; see [`ActorConfig::batch_size`]
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Refuse requests once `threshold` are queued; see [`ActorConfig::shed_threshold`]
+    pub fn with_load_shedding(mut self, threshold: usize) -> Self {
+        self.shedder = Some(LoadShedder::new(threshold));
+        self
+    }
+
+    /// Main actor loop with tracing
+    ///
+    /// **Pattern:** The run loop is instrumented at the top level and delegates
+    /// to specific handlers for each message type. This provides clean separation
+    /// and makes it easy to add new message types. A panicking handler loses
+    /// only its own message; the actor and its store carry on.
+    #[instrument(target = "actor::user", name = "user_service", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: USER_TARGET, "UserService starting");
+
+        let mut batch = Vec::with_capacity(self.batch_size);
+        while self.receiver.recv_many(&mut batch, self.batch_size).await > 0 {
+            self.batched = batch.len();
+            for msg in batch.drain(..) {
+                self.batched -= 1;
+                self.process(msg).await;
+            }
+        }
+
+        if let Some(hook) = self.on_stop.take() {
+            hook(self.snapshot());
+        }
+        for ack in self.shutdown_acks.drain(..) {
+            let _ = ack.send(Ok(()));
+        }
+        info!(target: USER_TARGET, "UserService stopped");
+    }
+
+    /// Run one message through fault injection, tracing and timing
+    async fn process(&mut self, mut msg: UserRequest) {
+        if let Some(sent_at) = msg.sent_at() {
+            MailboxStats::sample(&self.receiver, self.batched, sent_at).publish("User");
+        }
+        if let Some(shedder) = &mut self.shedder {
+            let depth = self.receiver.len() + self.batched;
+            if msg.is_sheddable() && shedder.overloaded(depth, USER_TARGET) {
+                msg.reject(UserError::Overloaded);
+                return;
+            }
+        }
+        if let Some(faults) = &self.faults {
+            if !msg.is_control() && !faults.inject().await {
+                return;
+            }
+        }
+        // Handle inside the caller's span and request so the trace continues across the mailbox
+        let span = msg
+            .caller_span()
+            .cloned()
+            .unwrap_or_else(tracing::Span::current);
+        let request_id = msg.request_id();
+        let variant = msg.variant();
+        msg.answer_on_drop(UserError::HandlerPanicked);
+        let started = tokio::time::Instant::now();
+        let handled = RequestId::scope(request_id, self.handle_message(msg).instrument(span));
+        if let Err(panic) = catch_unwind(std::pin::pin!(handled)).await {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::HandlerPanicked,
+                panic = panic_message(&panic),
+                "Handler panicked, request failed"
+            );
+        }
+        let elapsed = started.elapsed();
+        Metrics::global().record_handler("UserRequest", variant, elapsed);
+        if elapsed > self.slow_handler {
+            warn!(
+                target: USER_TARGET,
+                code = %EventCode::SlowHandler,
+                request = variant,
+                duration_ms = elapsed.as_millis(),
+                threshold_ms = self.slow_handler.as_millis(),
+                "Slow handler"
+            );
+        }
+    }
+
+    /// Dispatch one message
+    async fn handle_message(&mut self, msg: UserRequest) {
+        match msg {
+            UserRequest::GetUser { id, respond_to } => {
+                self.touch(&id);
+                self.handle_get_user(id, respond_to);
+            }
+            UserRequest::FindUsersByEmail { email, respond_to } => {
+                let users = self
+                    .ids_by_email(&email)
+                    .filter_map(|id| self.users.get(id).cloned())
+                    .collect();
+                let _ = respond_to.send(Ok(users));
+            }
+            UserRequest::GetUsers { ids, respond_to } => {
+                ids.iter().for_each(|id| self.touch(id));
+                self.handle_get_users(ids, respond_to);
+            }
+            UserRequest::Checksum { respond_to } => self.handle_checksum(respond_to),
+            UserRequest::GenerateReport {
+                user_id,
+                respond_to,
+            } => self.handle_generate_report(user_id, respond_to),
+            UserRequest::GenerateReportStreaming { user_id, updates } => {
+                self.handle_generate_report_streaming(user_id, updates)
+            }
+            UserRequest::CreateUser { user, respond_to } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_create_user(user, respond_to).await;
+            }
+            UserRequest::CreateUsers { users, respond_to } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_create_users(users, respond_to).await;
+            }
+            UserRequest::ReserveId { respond_to } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                let _ = respond_to.send(Ok(self.allocate_id()));
+            }
+            UserRequest::CreateUserWithId {
+                id,
+                user,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_create_user_with_id(id, user, respond_to).await;
+            }
+            UserRequest::GetOrCreateUser { user, respond_to } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_get_or_create_user(user, respond_to).await;
+            }
+            UserRequest::UpdateUser {
+                id,
+                user,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_update_user(id, user, respond_to).await;
+            }
+            UserRequest::ListUsers { respond_to } => {
+                self.handle_list_users(respond_to);
+            }
+            UserRequest::StreamUsers { items } => {
+                self.handle_stream_users(items);
+            }
+            UserRequest::WatchUser { id, respond_to } => {
+                self.handle_watch_user(id, respond_to);
+            }
+            UserRequest::ModifyUser {
+                id,
+                modifier,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_modify_user(id, modifier, respond_to).await;
+            }
+            UserRequest::Snapshot { respond_to } => {
+                let _ = respond_to.send(Ok(self.snapshot()));
+            }
+            UserRequest::GetStats { respond_to } => {
+                let stats =
+                    MailboxStats::sample(&self.receiver, self.batched, respond_to.sent_at());
+                stats.log(USER_TARGET);
+                let _ = respond_to.send(Ok(stats));
+            }
+            UserRequest::Ping { respond_to } => {
+                let _ = respond_to.send(Ok(()));
+            }
+            UserRequest::Import {
+                snapshot,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_import(snapshot, respond_to);
+            }
+            UserRequest::Drain { respond_to } => {
+                info!(target: USER_TARGET, "UserService draining, writes are refused from now on");
+                self.draining = true;
+                let _ = respond_to.send(Ok(()));
+            }
+            UserRequest::Shutdown { respond_to } => {
+                // Closing the mailbox lets the loop drain what's queued, then end
+                info!(target: USER_TARGET, "UserService shutting down, draining queued requests");
+                self.receiver.close();
+                self.shutdown_acks.push(respond_to);
+            }
+            UserRequest::Count { respond_to } => {
+                let _ = respond_to.send(Ok(self.users.len()));
+            }
+            UserRequest::Exists { id, respond_to } => {
+                let _ = respond_to.send(Ok(self.users.contains_key(&id)));
+            }
+        }
+    }
+
+    /// Pass a write's responder through, or answer `Draining` and swallow it
+    fn admit<T>(
+        &self,
+        respond_to: ServiceResponse<T, UserError>,
+    ) -> Option<ServiceResponse<T, UserError>> {
+        if self.draining {
+            debug!(target: USER_TARGET, "Write refused while draining");
+            let _ = respond_to.send(Err(UserError::Draining));
+            return None;
+        }
+        Some(respond_to)
+    }
+
+    /// **Sync Handler Example** - Fast, in-memory operation
+    ///
+    /// Use sync handlers for:
+    /// - In-memory data lookups
+    /// - Simple computations
+    /// - Operations that don't need `await`
+    ///
+    /// **Tracing:** Fields extract key business data, `skip` excludes large/sensitive data
+    #[instrument(target = "actor::user", fields(user_id = %id), skip(self, respond_to))]
+    fn handle_get_user(&self, id: String, respond_to: ServiceResponse<Option<User>, UserError>) {
+        debug!(target: USER_TARGET, "Processing get_user request");
+
+        let user = self.users.get(&id).cloned();
+
+        match &user {
+            Some(user) => info!(target: USER_TARGET, user_name = %user.name, "User found"),
+            None => debug!(target: USER_TARGET, "User not found"),
+        }
+
+        let _ = respond_to.send(Ok(user));
+    }
+
+    /// **Batch Read** - N lookups for the price of one round-trip
+    ///
+    /// Missing IDs are simply absent from the map, as `get_user` would
+    /// answer `None` for them.
+    #[instrument(target = "actor::user", fields(requested = ids.len()), skip_all)]
+    fn handle_get_users(
+        &self,
+        ids: Vec<String>,
+        respond_to: ServiceResponse<HashMap<String, User>, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing get_users request");
+
+        let users: HashMap<String, User> = ids
+            .into_iter()
+            .filter_map(|id| self.users.get(&id).cloned().map(|user| (id, user)))
+            .collect();
+        debug!(target: USER_TARGET, found = users.len(), "Users looked up");
+
+        let _ = respond_to.send(Ok(users));
+    }
+
+    /// **Async Handler Example** - I/O operation with validation
+    ///
+    /// Use async handlers for:
+    /// - Database operations
+    /// - External API calls
+    /// - Complex validation logic
+    /// - Any operation that needs `await`
+    ///
+    /// **Security:** Skip the full `user` object but log specific safe fields
+    #[instrument(
+        target = "actor::user",
+        fields(user_name = %user.name, user_email = %user.email),
+        skip(self, user, respond_to),
+    )]
+    async fn handle_create_user(
+        &mut self,
+        user: User,
+        respond_to: ServiceResponse<String, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing create_user request");
+
+        if let Err(errors) = user.validate() {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed"
+            );
+            send_error!(respond_to, UserError::InvalidFields(errors));
+        }
+
+        let id = self.allocate_id();
+        let result = self.insert_new_user(id, user).await;
+        let _ = respond_to.send(result);
+    }
+
+    /// **Bulk Create** - All or nothing, in one actor turn
+    ///
+    /// Every user is validated before any is stored, with field names
+    /// prefixed by the user's position in the batch, and no two users may
+    /// share an email, with each other or with a stored user. If an invariant check
+    /// then fails partway, the users already inserted are removed again, so
+    /// watchers, the audit log and the event bus only ever see a whole batch.
+    #[instrument(target = "actor::user", fields(count = users.len()), skip_all)]
+    async fn handle_create_users(
+        &mut self,
+        users: Vec<User>,
+        respond_to: ServiceResponse<Vec<String>, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing create_users request");
+
+        let mut errors = Vec::new();
+        for (i, user) in users.iter().enumerate() {
+            if let Err(invalid) = user.validate() {
+                errors.extend(
+                    invalid.into_iter().map(|error| {
+                        FieldError::new(format!("{}.{}", i, error.field), error.message)
+                    }),
+                );
+            }
+        }
+        if !errors.is_empty() {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed, batch rejected"
+            );
+            send_error!(respond_to, UserError::InvalidFields(errors));
+        }
+
+        let mut emails = BTreeSet::new();
+        for user in &users {
+            if !emails.insert(user.email.as_str()) {
+                send_error!(respond_to, UserError::AlreadyExists(user.email.clone()));
+            }
+            if let Err(e) = self.check_unique(&user.email, None) {
+                send_error!(respond_to, e);
+            }
+        }
+
+        let mut ids: Vec<String> = Vec::with_capacity(users.len());
+        for user in users {
+            let id = self.allocate_id();
+            self.put_user(id.clone(), user);
+            if let Err(e) = self.enforce_invariants(&id, None) {
+                for id in &ids {
+                    self.take_user(id);
+                }
+                send_error!(respond_to, e);
+            }
+            ids.push(id);
+        }
+        self.evict_over_capacity();
+        for id in &ids {
+            self.announce_new_user(id).await;
+        }
+
+        info!(target: USER_TARGET, count = ids.len(), "Users created");
+        let _ = respond_to.send(Ok(ids));
+    }
+
+    /// **Import-Friendly Create** - The caller supplies the ID
+    ///
+    /// IDs in the generated `user_N` form advance the counter, so a later
+    /// `create_user` never collides with them.
+    #[instrument(target = "actor::user", fields(user_id = %id), skip(self, user, respond_to))]
+    async fn handle_create_user_with_id(
+        &mut self,
+        id: String,
+        user: User,
+        respond_to: ServiceResponse<(), UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing create_user_with_id request");
+
+        if let Err(errors) = user.validate() {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed"
+            );
+            send_error!(respond_to, UserError::InvalidFields(errors));
+        }
+        if self.users.contains_key(&id) {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserAlreadyExists,
+                "User ID already taken"
+            );
+            send_error!(respond_to, UserError::AlreadyExists(id));
+        }
+        if let Some(n) = id.strip_prefix("user_").and_then(|n| n.parse::<u64>().ok()) {
+            self.next_id = self.next_id.max(n.saturating_add(1));
+        }
+
+        let result = self.insert_new_user(id, user).await.map(|_| ());
+        let _ = respond_to.send(result);
+    }
+
+    /// **Idempotent Create** - Lookup and insert in one actor turn
+    ///
+    /// Users are matched by email. Two concurrent calls for the same email
+    /// queue one behind the other, so the second always finds the user the
+    /// first created - no duplicate, and no error to retry on.
+    #[instrument(
+        target = "actor::user",
+        fields(user_email = %user.email),
+        skip(self, user, respond_to),
+    )]
+    async fn handle_get_or_create_user(
+        &mut self,
+        user: User,
+        respond_to: ServiceResponse<String, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing get_or_create_user request");
+
+        if let Some(id) = self.ids_by_email(&user.email).next().cloned() {
+            debug!(target: USER_TARGET, user_id = %id, "Existing user found");
+            let _ = respond_to.send(Ok(id));
+            return;
+        }
+        if let Err(errors) = user.validate() {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed"
+            );
+            send_error!(respond_to, UserError::InvalidFields(errors));
+        }
+
+        let id = self.allocate_id();
+        let result = self.insert_new_user(id, user).await;
+        let _ = respond_to.send(result);
+    }
+
+    /// Next generated ID; never reused, whether or not a user is created with it
+    fn allocate_id(&mut self) -> String {
+        match self.id_strategy {
+            IdStrategy::Sequential => {
+                let id = format!("user_{}", self.next_id);
+                self.next_id += 1;
+                id
+            }
+            IdStrategy::Random => loop {
+                let id = format!("user_{:016x}", random_u64());
+                if !self.users.contains_key(&id) {
+                    break id;
+                }
+            },
+        }
+    }
+
+    /// Shared tail of both create paths: store, verify, notify, publish
+    async fn insert_new_user(&mut self, id: String, user: User) -> Result<String, UserError> {
+        self.check_unique(&user.email, None)?;
+        self.put_user(id.clone(), user);
+        self.enforce_invariants(&id, None)?;
+        self.evict_over_capacity();
+        self.announce_new_user(&id).await;
+
+        info!(target: USER_TARGET, user_id = %id, "User created successfully");
+        Ok(id)
+    }
+
+    /// Tell watchers, the audit log and the event bus about a stored user
+    async fn announce_new_user(&mut self, id: &str) {
+        self.notify_watchers(id);
+
+        if let Some(audit) = &self.audit {
+            let after = self.users.get(id).map(|user| format!("{:?}", user));
+            let record = AuditRecord::new("User", id, AuditAction::Create, None, after);
+            audit.record(record).await;
+        }
+
+        if let Some(events) = &self.events {
+            events
+                .publish(DomainEvent::UserCreated {
+                    user_id: id.to_string(),
+                })
+                .await;
+        }
+    }
+
+    /// **Update Handler** - Modification operation with validation
+    #[instrument(
+        target = "actor::user",
+        fields(user_id = %id, user_name = %user.name),
+        skip(self, user, respond_to),
+    )]
+    async fn handle_update_user(
+        &mut self,
+        id: String,
+        user: User,
+        respond_to: ServiceResponse<(), UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing update_user request");
+
+        if let Err(errors) = user.validate() {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed"
+            );
+            send_error!(respond_to, UserError::InvalidFields(errors));
+        }
+
+        let result = if self.users.contains_key(&id) {
+            if let Err(e) = self.check_unique(&user.email, Some(&id)) {
+                send_error!(respond_to, e);
+            }
+            let after = format!("{:?}", user);
+            let previous = self.put_user(id.clone(), user);
+            let before = previous.as_ref().map(|user| format!("{:?}", user));
+            if let Err(e) = self.enforce_invariants(&id, previous) {
+                send_error!(respond_to, e);
+            }
+            self.notify_watchers(&id);
+
+            if let Some(audit) = &self.audit {
+                let record =
+                    AuditRecord::new("User", &id, AuditAction::Update, before, Some(after));
+                audit.record(record).await;
+            }
+
+            if let Some(events) = &self.events {
+                events
+                    .publish(DomainEvent::UserUpdated {
+                        user_id: id.clone(),
+                    })
+                    .await;
+            }
+
+            info!(target: USER_TARGET, "User updated successfully");
+            Ok(())
+        } else {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserNotFound,
+                "User not found for update"
+            );
+            Err(UserError::NotFound(id))
+        };
+
+        let _ = respond_to.send(result);
+    }
+
+    /// **Read-Modify-Write Handler** - Runs caller logic atomically
+    ///
+    /// The closure works on a copy; the store only changes if it succeeds and the
+    /// result still satisfies the invariants. No other message can interleave, so
+    /// there's no get-then-update race.
+    #[instrument(target = "actor::user", fields(user_id = %id), skip(self, modifier, respond_to))]
+    async fn handle_modify_user(
+        &mut self,
+        id: String,
+        modifier: Modifier<User, UserError>,
+        respond_to: ServiceResponse<(), UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing modify_user request");
+
+        let mut draft = match self.users.get(&id) {
+            Some(user) => user.clone(),
+            None => send_error!(respond_to, UserError::NotFound(id)),
+        };
+        if let Err(e) = modifier.apply(&mut draft) {
+            debug!(target: USER_TARGET, error = %e, "Modifier rejected the change");
+            send_error!(respond_to, e);
+        }
+        if INVARIANT_CHECKS {
+            if let Err(violation) = draft.check_invariants() {
+                error!(
+                    target: USER_TARGET,
+                    code = %EventCode::UserInvariantViolated,
+                    violation = %violation,
+                    "Invariant violated, change discarded"
+                );
+                send_error!(
+                    respond_to,
+                    UserError::ValidationError(format!("Invariant violated: {}", violation))
+                );
+            }
+        }
+        if let Err(errors) = draft.validate() {
+            error!(
+                target: USER_TARGET,
+                code = %EventCode::UserValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed, change discarded"
+            );
+            send_error!(respond_to, UserError::InvalidFields(errors));
+        }
+
+        if let Err(e) = self.check_unique(&draft.email, Some(&id)) {
+            send_error!(respond_to, e);
+        }
+
+        let after = format!("{:?}", draft);
+        let before = self
+            .put_user(id.clone(), draft)
+            .map(|user| format!("{:?}", user));
+        self.notify_watchers(&id);
+        if let Some(audit) = &self.audit {
+            let record = AuditRecord::new("User", &id, AuditAction::Update, before, Some(after));
+            audit.record(record).await;
+        }
+        if let Some(events) = &self.events {
+            events
+                .publish(DomainEvent::UserUpdated {
+                    user_id: id.clone(),
+                })
+                .await;
+        }
+
+        info!(target: USER_TARGET, "User modified successfully");
+        let _ = respond_to.send(Ok(()));
+    }
+
+    /// **Collection Handler** - Returns multiple items
+    #[instrument(target = "actor::user", skip(self, respond_to))]
+    fn handle_list_users(&self, respond_to: ServiceResponse<Vec<User>, UserError>) {
+        debug!(target: USER_TARGET, "Processing list_users request");
+
+        let users: Vec<User> = self.users.values().cloned().collect();
+        info!(target: USER_TARGET, user_count = users.len(), "Listed users");
+
+        let _ = respond_to.send(Ok(users));
+    }
+
+    /// **Streaming Handler** - Pushes items through a bounded channel
+    ///
+    /// The users are copied as of this message and fed to the channel from a
+    /// task of their own, as [`respond_blocking`] does for CPU-bound work. A
+    /// slow consumer therefore holds up only its own stream, never the actor,
+    /// and may call this service while it reads. A dropped receiver stops the
+    /// stream early.
+    #[instrument(target = "actor::user", skip(self, items))]
+    fn handle_stream_users(&self, items: mpsc::Sender<User>) {
+        debug!(target: USER_TARGET, "Processing stream_users request");
+
+        let users: Vec<User> = self.users.values().cloned().collect();
+        tokio::spawn(
+            async move {
+                let mut sent = 0;
+                for user in users {
+                    if items.send(user).await.is_err() {
+                        warn!(target: USER_TARGET, sent, "Stream receiver dropped, stopping early");
+                        return;
+                    }
+                    sent += 1;
+                }
+                info!(target: USER_TARGET, user_count = sent, "Streamed users");
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// **Subscription Handler** - Hands out a `watch` receiver for one user
+    ///
+    /// The receiver starts with the current value and sees every later create or
+    /// update, so reactive consumers and caches never need to poll.
+    #[instrument(target = "actor::user", fields(user_id = %id), skip(self, respond_to))]
+    fn handle_watch_user(
+        &mut self,
+        id: String,
+        respond_to: ServiceResponse<watch::Receiver<Option<User>>, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing watch_user request");
+
+        let current = self.users.get(&id).cloned();
+        let receiver = self
+            .watchers
+            .entry(id)
+            .or_insert_with(|| watch::channel(current).0)
+            .subscribe();
+
+        let _ = respond_to.send(Ok(receiver));
+    }
+
+    /// Hand the final state to `hook` when the actor stops, e.g. to persist it
+    pub fn on_stop(mut self, hook: impl FnOnce(UserSnapshot) + Send + Sync + 'static) -> Self {
+        self.on_stop = Some(Box::new(hook));
+        self
+    }
+
+    /// Load previously captured state before the actor is spawned
+    pub fn restore(mut self, snapshot: UserSnapshot) -> Self {
+        self.users.clear();
+        self.by_email.clear();
+        for (id, user) in snapshot.users {
+            self.put_user(id, user);
+        }
+        self.evict_over_capacity();
+        self.next_id = snapshot.next_id;
+        self
+    }
+
+    fn snapshot(&self) -> UserSnapshot {
+        UserSnapshot {
+            users: self.users.clone(),
+            next_id: self.next_id,
+        }
+    }
+
+    #[instrument(target = "actor::user", skip(self, snapshot, respond_to))]
+    fn handle_import(
+        &mut self,
+        snapshot: UserSnapshot,
+        respond_to: ServiceResponse<usize, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing import request");
+
+        let count = snapshot.users.len();
+        // Never hand out an ID the imported data already uses
+        self.next_id = self.next_id.max(snapshot.next_id);
+        for (id, user) in snapshot.users {
+            self.put_user(id.clone(), user);
+            self.notify_watchers(&id);
+        }
+        self.evict_over_capacity();
+
+        info!(target: USER_TARGET, user_count = count, "Users imported");
+        let _ = respond_to.send(Ok(count));
+    }
+
+    /// Verify the stored user after a mutation, restoring `previous` on violation
+    fn enforce_invariants(&mut self, id: &str, previous: Option<User>) -> Result<(), UserError> {
+        if !INVARIANT_CHECKS {
+            return Ok(());
+        }
+        let violation = match self.users.get(id).map(Invariants::check_invariants) {
+            Some(Err(violation)) => violation,
+            _ => return Ok(()),
+        };
+
+        error!(
+            target: USER_TARGET,
+            code = %EventCode::UserInvariantViolated,
+            user_id = %id,
+            violation = %violation,
+            "Invariant violated, rolling back"
+        );
+        match previous {
+            Some(previous) => self.put_user(id.to_string(), previous),
+            None => self.take_user(id),
+        };
+        Err(UserError::ValidationError(format!(
+            "Invariant violated: {}",
+            violation
+        )))
+    }
+
+    /// **Secondary Index** - Every write to `users` goes through here
+    ///
+    /// The actor owns both maps, so keeping them in step needs no locking;
+    /// it only needs discipline: nothing else may touch `users` directly.
+    ///
+    /// Never evicts: a write can still be rolled back, so callers run
+    /// [`evict_over_capacity`](Self::evict_over_capacity) once it has committed.
+    fn put_user(&mut self, id: String, user: User) -> Option<User> {
+        self.by_email
+            .entry(user.email.clone())
+            .or_default()
+            .insert(id.clone());
+        let previous = self.users.insert(id.clone(), user);
+        self.touch(&id);
+        if let Some(previous) = &previous {
+            if previous.email != self.users[&id].email {
+                self.unindex_email(&previous.email, &id);
+            }
+        }
+        previous
+    }
+
+    fn take_user(&mut self, id: &str) -> Option<User> {
+        let user = self.users.remove(id)?;
+        self.unindex_email(&user.email, id);
+        if let Some(tick) = self.last_used.remove(id) {
+            self.recency.remove(&tick);
+        }
+        Some(user)
+    }
+
+    /// Mark `id` as the most recently used user
+    fn touch(&mut self, id: &str) {
+        if self.capacity.is_none() || !self.users.contains_key(id) {
+            return;
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        if let Some(previous) = self.last_used.insert(id.to_string(), tick) {
+            self.recency.remove(&previous);
+        }
+        self.recency.insert(tick, id.to_string());
+    }
+
+    /// **Bounded Store** - Drop least recently used users over the limit
+    ///
+    /// The user just written is the most recent, so it is never the victim.
+    /// Run only after a write has committed, so a rejected one evicts nobody.
+    fn evict_over_capacity(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        while self.users.len() > capacity {
+            let Some((_, id)) = self.recency.pop_first() else {
+                break;
+            };
+            self.last_used.remove(&id);
+            let Some(user) = self.take_user(&id) else {
+                continue;
+            };
+            info!(target: USER_TARGET, user_id = %id, capacity, "User evicted");
+            if let Some(hook) = self.on_evict.as_mut() {
+                hook(id, user);
+            }
+        }
+    }
+
+    fn unindex_email(&mut self, email: &str, id: &str) {
+        if let Some(ids) = self.by_email.get_mut(email) {
+            ids.remove(id);
+            if ids.is_empty() {
+                self.by_email.remove(email);
+            }
+        }
+    }
+
+    fn ids_by_email(&self, email: &str) -> impl Iterator<Item = &String> {
+        self.by_email.get(email).into_iter().flatten()
+    }
+
+    /// **Unique Constraint** - Fail with `AlreadyExists` if a user other than
+    /// `id` owns `email`
+    ///
+    /// Checked before every create and update. Imports and restores are
+    /// trusted as-is, which is why the index still maps an email to a set.
+    fn check_unique(&self, email: &str, id: Option<&str>) -> Result<(), UserError> {
+        let Some(owner) = self
+            .ids_by_email(email)
+            .find(|owner| Some(owner.as_str()) != id)
+        else {
+            return Ok(());
+        };
+        error!(
+            target: USER_TARGET,
+            code = %EventCode::UserAlreadyExists,
+            user_id = %owner,
+            "Email already taken"
+        );
+        Err(UserError::AlreadyExists(email.to_string()))
+    }
+
+    /// Push the latest state of `id` to its watchers, forgetting abandoned channels
+    fn notify_watchers(&mut self, id: &str) {
+        if let Some(watcher) = self.watchers.get(id) {
+            if watcher.receiver_count() == 0 {
+                self.watchers.remove(id);
+            } else {
+                watcher.send_replace(self.users.get(id).cloned());
+            }
+        }
+    }
+}
+
+// =============================================================================
+// USER CLIENT
+// =============================================================================
+
+/// Client for UserService with macro-generated methods. Thin wrapper around
+/// message channels with automatic error handling.
+
+#[derive(Clone)]
+pub struct UserClient {
+    sender: mpsc::Sender<UserRequest>,
+    policy: ClientPolicy,
+}
+
+impl UserClient {
+    pub fn new(sender: mpsc::Sender<UserRequest>) -> Self {
+        Self {
+            sender,
+            policy: ClientPolicy::default(),
+        }
+    }
+
+    /// Bound every request made through this handle. Clone first for a per-call
+    /// override: `client.clone().with_timeout(d).get_...(..)`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.policy.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry timed-out requests with exponential backoff (needs a timeout to trigger)
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.policy.retry = Some(retry);
+        self
+    }
+
+    /// Fail fast once the actor keeps failing; clones made after this share the breaker
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.policy.breaker = Some(Arc::new(CircuitBreaker::new(config)));
+        self
+    }
+
+    /// Choose between waiting and failing fast when the mailbox is full
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.policy.backpressure = backpressure;
+        self
+    }
+
+    /// Draw one token per call from `limiter`, in the bucket named by [`Self::with_caller`]
+    pub fn with_rate_limit(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.policy.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Identify the caller behind this handle for rate limiting
+    pub fn with_caller(mut self, caller: impl Into<String>) -> Self {
+        self.policy.caller = Some(caller.into());
+        self
+    }
+
+    /// Deliver `request` to this actor after `delay`, via `scheduler`. The reply
+    /// is discarded; keep the handle to cancel the delivery before it fires.
+    pub async fn send_after(
+        &self,
+        scheduler: &SchedulerClient,
+        delay: Duration,
+        request: UserRequest,
+    ) -> Result<TimerHandle, String> {
+        scheduler
+            .send_after(delay, self.sender.clone(), request)
+            .await
+    }
+
+    /// Manual methods for special cases. Shutdown bypasses the client policy:
+    /// it must reach the actor even with the breaker open. Resolves once every
+    /// request queued ahead of it has been handled.
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        let (respond_to, response) = ServiceResponse::channel();
+        self.sender
+            .send(UserRequest::Shutdown { respond_to })
+            .await
+            .map_err(|e| e.to_string())?;
+        response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+
+    /// Atomically read and modify one user inside the actor.
+    ///
+    /// `f` runs on a copy of the stored user; returning `Err` discards the change.
+    /// Whatever `f` returns on success is handed back to the caller. A timed-out
+    /// modify is never retried, since it may already have been applied.
+    #[instrument(skip(self, f), fields(request_id = tracing::field::Empty))]
+    pub async fn modify_user<R, F>(&self, id: String, f: F) -> Result<R, String>
+    where
+        F: FnOnce(&mut User) -> Result<R, UserError> + Send + 'static,
+        R: Send + 'static,
+    {
+        debug!("Sending modify request");
+        let (modifier, output) = Modifier::new(f);
+        let mut modifier = Some(modifier);
+        let policy = self.policy.without_retry();
+        RequestId::propagate(policy.call(|| {
+            let id = id.clone();
+            let modifier = modifier.take();
+            async move {
+                let modifier = modifier.ok_or_else(|| "modify_user is not retried".to_string())?;
+                let (respond_to, response) = ServiceResponse::channel();
+                self.policy
+                    .send(
+                        &self.sender,
+                        UserRequest::ModifyUser {
+                            id,
+                            modifier,
+                            respond_to,
+                        },
+                    )
+                    .await?;
+                response.await.map_err(|e| e.to_string())
+            }
+        }))
+        .await?;
+        output.await.map_err(|e| e.to_string())
+    }
+
+    /// Compare-and-swap update: replace the user only if `predicate` holds for
+    /// the stored value, returning the new user.
+    ///
+    /// A false predicate fails with [`UserError::PreconditionFailed`] and leaves
+    /// the store untouched. Check and write happen in one actor turn.
+    #[instrument(skip(self, predicate, user))]
+    pub async fn update_user_if<P>(
+        &self,
+        id: String,
+        predicate: P,
+        user: User,
+    ) -> Result<User, String>
+    where
+        P: FnOnce(&User) -> bool + Send + 'static,
+    {
+        let key = id.clone();
+        self.modify_user(id, move |current| {
+            if !predicate(current) {
+                return Err(UserError::PreconditionFailed(key));
+            }
+            *current = user;
+            Ok(current.clone())
+        })
+        .await
+    }
+
+    /// Generate a report, receiving a [`Progress::Step`] per finished section
+    /// and the report itself as the final [`Progress::Done`]. Drop the receiver
+    /// to cancel.
+    #[instrument(skip(self))]
+    pub async fn generate_report_streaming(
+        &self,
+        user_id: String,
+    ) -> Result<mpsc::Receiver<Progress<String>>, String> {
+        debug!("Sending streaming report request");
+        let (updates, receiver) = mpsc::channel(REPORT_SECTIONS.len() + 1);
+        self.policy
+            .send(
+                &self.sender,
+                UserRequest::GenerateReportStreaming { user_id, updates },
+            )
+            .await?;
+        Ok(receiver)
+    }
+
+    /// Stream all users instead of receiving them in one `Vec`.
+    ///
+    /// At most `chunk_size` users are buffered on the way to the caller, and the
+    /// actor keeps serving other requests while the caller reads. The stream
+    /// ends when the receiver yields `None`.
+    #[instrument(skip(self))]
+    pub async fn list_users_stream(
+        &self,
+        chunk_size: usize,
+    ) -> Result<mpsc::Receiver<User>, String> {
+        debug!("Sending stream request");
+        let (items, receiver) = mpsc::channel(chunk_size.max(1));
+        self.sender
+            .send(UserRequest::StreamUsers { items })
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(receiver)
+    }
+}
+
+// Generate client methods with automatic tracing
+client_method!(UserClient => fn get_user(id: String) -> Option<User> as UserRequest::GetUser);
+client_method!(UserClient => fn find_users_by_email(email: String) -> Vec<User> as UserRequest::FindUsersByEmail);
+client_method!(UserClient => fn get_users(ids: Vec<String>) -> HashMap<String, User> as UserRequest::GetUsers);
+client_method!(UserClient => fn create_user(user: User) -> String as UserRequest::CreateUser);
+client_method!(UserClient => fn reserve_id() -> String as UserRequest::ReserveId);
+client_method!(UserClient => fn create_users(users: Vec<User>) -> Vec<String> as UserRequest::CreateUsers);
+client_method!(UserClient => fn create_user_with_id(id: String, user: User) -> () as UserRequest::CreateUserWithId);
+client_method!(UserClient => fn get_or_create_user(user: User) -> String as UserRequest::GetOrCreateUser);
+client_method!(UserClient => fn update_user(id: String, user: User) -> () as UserRequest::UpdateUser);
+client_method!(UserClient => fn list_users() -> Vec<User> as UserRequest::ListUsers);
+client_method!(UserClient => fn watch_user(id: String) -> watch::Receiver<Option<User>> as UserRequest::WatchUser);
+client_method!(UserClient => fn snapshot() -> UserSnapshot as UserRequest::Snapshot);
+client_method!(UserClient => fn get_stats() -> MailboxStats as UserRequest::GetStats);
+client_method!(UserClient => fn ping() -> () as UserRequest::Ping);
+client_method!(UserClient => fn generate_report(user_id: String) -> String as UserRequest::GenerateReport);
+client_method!(UserClient => fn checksum() -> u64 as UserRequest::Checksum);
+client_method!(UserClient => fn import(snapshot: UserSnapshot) -> usize as UserRequest::Import);
+client_method!(UserClient => fn drain() -> () as UserRequest::Drain);
+client_method!(UserClient => fn count() -> usize as UserRequest::Count);
+client_method!(UserClient => fn exists(id: String) -> bool as UserRequest::Exists);
+
+// =============================================================================
+// INGREDIENT 6: PRODUCT SERVICE (SECOND SUB-ACTOR)
+// =============================================================================
+
+/// Product-specific error types
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProductError {
+    NotFound(String),
+    InsufficientStock {
+        requested: u32,
+        available: u32,
+    },
+    InvalidQuantity(u32),
+    ValidationError(String),
+    /// The payload failed [`Validate`]; every offending field is listed
+    InvalidFields(Vec<FieldError>),
+    DatabaseError(String),
+    /// The actor is draining and no longer accepts writes
+    Draining,
+    /// The actor shed the request because its mailbox is backed up
+    Overloaded,
+    /// No such stock hold: it was confirmed already, or it expired
+    HoldNotFound(u64),
+    /// A release asked for more units than are reserved for the product
+    NotReserved {
+        requested: u32,
+        reserved: u32,
+    },
+    /// The handler panicked before answering; the actor itself kept running
+    HandlerPanicked,
+}
+
+impl std::fmt::Display for ProductError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProductError::NotFound(id) => write!(f, "Product not found: {}", id),
+            ProductError::InsufficientStock {
+                requested,
+                available,
+            } => {
+                write!(
+                    f,
+                    "Insufficient stock: requested {}, available {}",
+                    requested, available
+                )
+            }
+            ProductError::InvalidQuantity(qty) => write!(f, "Invalid quantity: {}", qty),
+            ProductError::ValidationError(msg) => write!(f, "Product validation error: {}", msg),
+            ProductError::InvalidFields(errors) => {
+                write!(f, "Invalid product: {}", join_field_errors(errors))
+            }
+            ProductError::DatabaseError(msg) => write!(f, "Product database error: {}", msg),
+            ProductError::Draining => write!(f, "Product service is draining"),
+            ProductError::Overloaded => write!(f, "Product service is overloaded"),
+            ProductError::HoldNotFound(hold) => write!(f, "Stock hold not found: {}", hold),
+            ProductError::NotReserved {
+                requested,
+                reserved,
+            } => {
+                write!(
+                    f,
+                    "Cannot release {} units, only {} reserved",
+                    requested, reserved
+                )
+            }
+            ProductError::HandlerPanicked => write!(f, "Product handler panicked"),
+        }
+    }
+}
+
+impl std::error::Error for ProductError {}
+
+/// ## Ingredient 6: Additional Sub-Actors
+///
+/// **Pattern:** Each domain gets its own actor following the same structure.
+/// This demonstrates how to scale the pattern to multiple domains.
+///
+/// **Benefits:**
+/// - **Domain isolation** - Each actor is responsible for one business domain
+/// - **Independent scaling** - Actors can be scaled independently
+/// - **Clear boundaries** - Easy to understand what each actor does
+/// - **Parallel development** - Different teams can work on different actors
+/// <!-- anchor: product-service -->
+pub struct ProductService {
+    receiver: mpsc::Receiver<ProductRequest>,
+    /// Shared so reads hand out a pointer; writes swap in a new `Arc` (copy-on-write)
+    products: HashMap<String, Arc<Product>>,
+    stock: HashMap<String, u32>,
+    /// Units reserved and not released yet; caps what `release_stock` may return
+    reserved: HashMap<String, u32>,
+    /// Set once [`read_view`](Self::read_view) is called; republished on every product write
+    view: Option<watch::Sender<ProductView>>,
+    holds: HashMap<u64, StockHold>,
+    next_hold: u64,
+    clock: SharedClock,
+    tick_interval: Duration,
+    events: Option<EventBusClient>,
+    /// Events recorded by mutations and not yet accepted by `events`
+    outbox: Outbox,
+    outbox_capacity: usize,
+    /// Outbox length last added to the `actor_outbox_pending` gauge
+    outbox_reported: usize,
+    audit: Option<AuditClient>,
+    faults: Option<FaultInjector>,
+    slow_handler: Duration,
+    batch_size: usize,
+    /// Taken from the mailbox in the current batch but not yet handled
+    batched: usize,
+    shedder: Option<LoadShedder>,
+    draining: bool,
+    shutdown_acks: Vec<ServiceResponse<(), ProductError>>,
+    on_stop: Option<StopHook<ProductSnapshot>>,
+}
+
+impl ProductService {
+    pub fn new(buffer_size: usize) -> (Self, ProductClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            products: HashMap::new(),
+            stock: HashMap::new(),
+            reserved: HashMap::new(),
+            view: None,
+            holds: HashMap::new(),
+            next_hold: 1,
+            clock: Arc::new(SystemClock),
+            tick_interval: ActorConfig::default().tick_interval,
+            events: None,
+            outbox: Outbox::default(),
+            outbox_capacity: 1000,
+            outbox_reported: 0,
+            audit: None,
+            faults: None,
+            slow_handler: ActorConfig::default().slow_handler,
+            batch_size: ActorConfig::default().batch_size,
+            batched: 0,
+            shedder: None,
+            draining: false,
+            shutdown_acks: Vec::new(),
+            on_stop: None,
+        };
+        let client = ProductClient::new(sender);
+        (service, client)
+    }
+
+    /// Publish lifecycle events to the given bus
+    pub fn with_event_bus(mut self, events: EventBusClient) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Record every mutation in the given audit log
+    pub fn with_audit(mut self, audit: AuditClient) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Inject artificial latency and failures, for demos
+    pub fn with_faults(mut self, faults: FaultInjector) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Warn about any handler that takes longer than `threshold`
+    pub fn with_slow_handler(mut self, threshold: Duration) -> Self {
+        self.slow_handler = threshold;
+        self
+    }
+
+    /// Take up to `size` queued messages per wakeup; see [`ActorConfig::batch_size`]
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Refuse requests once `threshold` are queued; see [`ActorConfig::shed_threshold`]
+    pub fn with_load_shedding(mut self, threshold: usize) -> Self {
+        self.shedder = Some(LoadShedder::new(threshold));
+        self
+    }
+
+    /// Read time from `clock` when stamping and expiring stock holds
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Refuse requests that record an event with `Overloaded` while this many
+    /// events wait undelivered
+    pub fn with_outbox_capacity(mut self, capacity: usize) -> Self {
+        self.outbox_capacity = capacity.max(1);
+        self
+    }
+
+    /// How often [`on_tick`](Self::on_tick) runs, e.g. to sweep expired stock
+    /// holds; at least once per millisecond, as a tokio interval cannot be zero
+    pub fn with_tick_interval(mut self, interval: Duration) -> Self {
+        self.tick_interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Hand the final state to `hook` when the actor stops, e.g. to persist it
+    pub fn on_stop(mut self, hook: impl FnOnce(ProductSnapshot) + Send + Sync + 'static) -> Self {
+        self.on_stop = Some(Box::new(hook));
+        self
+    }
+
+    /// Load previously captured state before the actor is spawned
+    pub fn restore(mut self, snapshot: ProductSnapshot) -> Self {
+        self.products = share_products(snapshot.products);
+        self.stock = snapshot.stock;
+        self.reserved = snapshot.reserved;
+        self.outbox = snapshot.outbox;
+        self.publish_view();
+        self
+    }
+
+    /// Turn on the read-optimized mode and return a handle to it.
+    ///
+    /// Call before spawning. From then on the actor republishes its product
+    /// map after every product write, which costs one map copy per write.
+    pub fn read_view(&mut self) -> ProductReadView {
+        let receiver = match &self.view {
+            Some(view) => view.subscribe(),
+            None => {
+                let (view, receiver) = watch::channel(Arc::new(self.products.clone()));
+                self.view = Some(view);
+                receiver
+            }
+        };
+        ProductReadView {
+            shards: vec![receiver],
+        }
+    }
+
+    fn publish_view(&self) {
+        if let Some(view) = &self.view {
+            view.send_replace(Arc::new(self.products.clone()));
+        }
+    }
+
+    fn snapshot(&self) -> ProductSnapshot {
+        // Holds live only in memory, so a snapshot counts held units as released
+        let mut stock = self.stock.clone();
+        for hold in self.holds.values() {
+            *stock.entry(hold.product_id.clone()).or_default() += hold.quantity;
+        }
+        ProductSnapshot {
+            products: self
+                .products
+                .iter()
+                .map(|(id, product)| (id.clone(), Product::clone(product)))
+                .collect(),
+            stock,
+            reserved: self.reserved.clone(),
+            outbox: self.outbox.clone(),
+        }
+    }
+
+    /// Store `event` alongside the state change that produced it; the run
+    /// loop relays it to the bus once the handler is done
+    fn record_event(&mut self, event: DomainEvent) {
+        if self.events.is_some() {
+            self.outbox.push(event);
+        }
+    }
+
+    /// Whether a request that records an event must be refused: an event that
+    /// cannot be kept would be lost, so the change is not made at all
+    fn outbox_full(&self) -> bool {
+        if self.events.is_none() || self.outbox.len() < self.outbox_capacity {
+            return false;
+        }
+        warn!(
+            target: PRODUCT_TARGET,
+            code = %EventCode::OutboxOverflow,
+            capacity = self.outbox_capacity,
+            "Outbox full, request refused"
+        );
+        Metrics::global().add_counter(
+            "actor_outbox_dropped_total",
+            "Requests refused because the outbox was full",
+            "entity=\"Product\"",
+            1,
+        );
+        true
+    }
+
+    async fn relay_outbox(&mut self) {
+        if let Some(events) = &self.events {
+            self.outbox.relay(events).await;
+        }
+        self.report_outbox();
+    }
+
+    /// Bring the `actor_outbox_pending` gauge up to date. Shards each add
+    /// their own change, so the gauge is the total across them.
+    fn report_outbox(&mut self) {
+        let pending = self.outbox.len();
+        if pending != self.outbox_reported {
+            Metrics::global().add_gauge(
+                "actor_outbox_pending",
+                "Events waiting in an outbox for the event bus",
+                "entity=\"Product\"",
+                pending as i64 - self.outbox_reported as i64,
+            );
+            self.outbox_reported = pending;
+        }
+    }
+
+    #[instrument(target = "actor::product", skip(self, snapshot, respond_to))]
+    fn handle_import(
+        &mut self,
+        snapshot: ProductSnapshot,
+        respond_to: ServiceResponse<usize, ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing import request");
+
+        // Import is how products are created, so it validates like a create.
+        // Field names are prefixed with the product ID; nothing is applied on failure.
+        let mut errors = Vec::new();
+        for (id, product) in &snapshot.products {
+            if let Err(invalid) = product.validate() {
+                errors.extend(invalid.into_iter().map(|error| {
+                    FieldError::new(format!("{}.{}", id, error.field), error.message)
+                }));
+            }
+        }
+        if !errors.is_empty() {
+            error!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::ProductValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed, import rejected"
+            );
+            send_error!(respond_to, ProductError::InvalidFields(errors));
+        }
+
+        let count = snapshot.products.len();
+        self.products.extend(share_products(snapshot.products));
+        self.stock.extend(snapshot.stock);
+        self.reserved.extend(snapshot.reserved);
+        self.outbox.append(snapshot.outbox);
+        self.publish_view();
+
+        info!(target: PRODUCT_TARGET, product_count = count, "Products imported");
+        let _ = respond_to.send(Ok(count));
+    }
+
+    #[instrument(target = "actor::product", name = "product_service", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: PRODUCT_TARGET, "ProductService starting");
+
+        let mut ticks = tokio::time::interval(self.tick_interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut batch = Vec::with_capacity(self.batch_size);
+        loop {
+            tokio::select! {
+                received = self.receiver.recv_many(&mut batch, self.batch_size) => {
+                    if received == 0 {
+                        break;
+                    }
+                    self.batched = received;
+                    for msg in batch.drain(..) {
+                        self.batched -= 1;
+                        self.process(msg).await;
+                        self.relay_outbox().await;
+                    }
+                }
+                _ = ticks.tick() => self.on_tick().await,
+            }
+        }
+
+        if let Some(hook) = self.on_stop.take() {
+            hook(self.snapshot());
+        }
+        for ack in self.shutdown_acks.drain(..) {
+            let _ = ack.send(Ok(()));
+        }
+        info!(target: PRODUCT_TARGET, "ProductService stopped");
+    }
+
+    /// Run one message through fault injection, tracing and timing
+    async fn process(&mut self, mut msg: ProductRequest) {
+        if let Some(sent_at) = msg.sent_at() {
+            MailboxStats::sample(&self.receiver, self.batched, sent_at).publish("Product");
+        }
+        if let Some(shedder) = &mut self.shedder {
+            let depth = self.receiver.len() + self.batched;
+            if msg.is_sheddable() && shedder.overloaded(depth, PRODUCT_TARGET) {
+                msg.reject(ProductError::Overloaded);
+                return;
+            }
+        }
+        if let Some(faults) = &self.faults {
+            if !msg.is_control() && !faults.inject().await {
+                return;
+            }
+        }
+        // Handle inside the caller's span and request so the trace continues across the mailbox
+        let span = msg
+            .caller_span()
+            .cloned()
+            .unwrap_or_else(tracing::Span::current);
+        let request_id = msg.request_id();
+        let variant = msg.variant();
+        msg.answer_on_drop(ProductError::HandlerPanicked);
+        let started = tokio::time::Instant::now();
+        let handled = RequestId::scope(request_id, self.handle_message(msg).instrument(span));
+        if let Err(panic) = catch_unwind(std::pin::pin!(handled)).await {
+            error!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::HandlerPanicked,
+                panic = panic_message(&panic),
+                "Handler panicked, request failed"
+            );
+        }
+        let elapsed = started.elapsed();
+        Metrics::global().record_handler("ProductRequest", variant, elapsed);
+        if elapsed > self.slow_handler {
+            warn!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::SlowHandler,
+                request = variant,
+                duration_ms = elapsed.as_millis(),
+                threshold_ms = self.slow_handler.as_millis(),
+                "Slow handler"
+            );
+        }
+    }
+
+    /// Dispatch one message
+    async fn handle_message(&mut self, msg: ProductRequest) {
+        match msg {
+            ProductRequest::GetProduct { id, respond_to } => {
+                self.handle_get_product(id, respond_to);
+            }
+            ProductRequest::GetProductShared { id, respond_to } => {
+                let _ = respond_to.send(Ok(self.products.get(&id).cloned()));
+            }
+            ProductRequest::GetProducts { ids, respond_to } => {
+                self.handle_get_products(ids, respond_to);
+            }
+            ProductRequest::CheckStock { id, respond_to } => {
+                self.handle_check_stock(id, respond_to);
+            }
+            ProductRequest::Count { respond_to } => {
+                let _ = respond_to.send(Ok(self.products.len()));
+            }
+            ProductRequest::Exists { id, respond_to } => {
+                let _ = respond_to.send(Ok(self.products.contains_key(&id)));
+            }
+            ProductRequest::ReserveStock {
+                id,
+                quantity,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_reserve_stock(id, quantity, respond_to).await;
+            }
+            ProductRequest::ReleaseStock {
+                id,
+                quantity,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_release_stock(id, quantity, respond_to).await;
+            }
+            ProductRequest::HoldStock {
+                id,
+                quantity,
+                ttl,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_hold_stock(id, quantity, ttl, respond_to).await;
+            }
+            ProductRequest::ConfirmHold {
+                id,
+                hold,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_confirm_hold(id, hold, respond_to).await;
+            }
+            ProductRequest::UpdateProducts {
+                patches,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_update_products(patches, respond_to).await;
+            }
+            ProductRequest::ModifyStock {
+                id,
+                modifier,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_modify_stock(id, modifier, respond_to).await;
+            }
+            ProductRequest::Snapshot { respond_to } => {
+                let _ = respond_to.send(Ok(self.snapshot()));
+            }
+            ProductRequest::GetStats { respond_to } => {
+                let stats =
+                    MailboxStats::sample(&self.receiver, self.batched, respond_to.sent_at());
+                stats.log(PRODUCT_TARGET);
+                let _ = respond_to.send(Ok(stats));
+            }
+            ProductRequest::Ping { respond_to } => {
+                let _ = respond_to.send(Ok(()));
+            }
+            ProductRequest::Import {
+                snapshot,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_import(snapshot, respond_to);
+            }
+            ProductRequest::Drain { respond_to } => {
+                info!(
+                    target: PRODUCT_TARGET,
+                    "ProductService draining, writes are refused from now on"
+                );
+                self.draining = true;
+                let _ = respond_to.send(Ok(()));
+            }
+            ProductRequest::Shutdown { respond_to } => {
+                // Closing the mailbox lets the loop drain what's queued, then end
+                info!(
+                    target: PRODUCT_TARGET,
+                    "ProductService shutting down, draining queued requests"
+                );
+                self.receiver.close();
+                self.shutdown_acks.push(respond_to);
+            }
+        }
+    }
+
+    /// Pass a write's responder through, or answer `Draining` and swallow it
+    fn admit<T>(
+        &self,
+        respond_to: ServiceResponse<T, ProductError>,
+    ) -> Option<ServiceResponse<T, ProductError>> {
+        if self.draining {
+            debug!(target: PRODUCT_TARGET, "Write refused while draining");
+            let _ = respond_to.send(Err(ProductError::Draining));
+            return None;
+        }
+        Some(respond_to)
+    }
+
+    #[instrument(target = "actor::product", fields(product_id = %id), skip(self, respond_to))]
+    fn handle_get_product(
+        &self,
+        id: String,
+        respond_to: ServiceResponse<Option<Product>, ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing get_product request");
+
+        let product = self
+            .products
+            .get(&id)
+            .map(|product| Product::clone(product));
+
+        match &product {
+            Some(product) => {
+                info!(
+                    target: PRODUCT_TARGET,
+                    product_name = %product.name,
+                    price = %product.price,
+                    "Product found"
+                )
+            }
+            None => debug!(target: PRODUCT_TARGET, "Product not found"),
+        }
+
+        let _ = respond_to.send(Ok(product));
+    }
+
+    #[instrument(target = "actor::product", fields(requested = ids.len()), skip_all)]
+    fn handle_get_products(
+        &self,
+        ids: Vec<String>,
+        respond_to: ServiceResponse<HashMap<String, Product>, ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing get_products request");
+
+        let products: HashMap<String, Product> = ids
+            .into_iter()
+            .filter_map(|id| {
+                let product = self.products.get(&id)?;
+                Some((id, Product::clone(product)))
+            })
+            .collect();
+        debug!(target: PRODUCT_TARGET, found = products.len(), "Products looked up");
+
+        let _ = respond_to.send(Ok(products));
+    }
+
+    #[instrument(target = "actor::product", fields(product_id = %id), skip(self, respond_to))]
+    fn handle_check_stock(&self, id: String, respond_to: ServiceResponse<u32, ProductError>) {
+        debug!(target: PRODUCT_TARGET, "Processing check_stock request");
+
+        let stock = self.stock.get(&id).copied().unwrap_or(0);
+        info!(target: PRODUCT_TARGET, stock_level = stock, "Stock checked");
+
+        let _ = respond_to.send(Ok(stock));
+    }
+
+    /// Read-modify-write of a stock level; the change is committed only on success
+    #[instrument(
+        target = "actor::product",
+        fields(product_id = %id),
+        skip(self, modifier, respond_to),
+    )]
+    async fn handle_modify_stock(
+        &mut self,
+        id: String,
+        modifier: Modifier<u32, ProductError>,
+        respond_to: ServiceResponse<(), ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing modify_stock request");
+
+        let Some(current_stock) = self.stock.get_mut(&id) else {
+            send_error!(respond_to, ProductError::NotFound(id));
+        };
+        let before = *current_stock;
+        let mut draft = before;
+        if let Err(e) = modifier.apply(&mut draft) {
+            send_error!(respond_to, e);
+        }
+        *current_stock = draft;
+        info!(target: PRODUCT_TARGET, stock_level = draft, "Stock modified");
+
+        if let Some(audit) = &self.audit {
+            let record = AuditRecord::new(
+                "Stock",
+                &id,
+                AuditAction::Update,
+                Some(before.to_string()),
+                Some(draft.to_string()),
+            );
+            audit.record(record).await;
+        }
+
+        let _ = respond_to.send(Ok(()));
+    }
+
+    /// **Bulk Patch** - One message, one result per item
+    ///
+    /// Unlike `create_users`, items succeed or fail independently: a missing
+    /// product or an invalid price fails only its own patch, and the product
+    /// is left untouched.
+    #[instrument(target = "actor::product", fields(count = patches.len()), skip_all)]
+    async fn handle_update_products(
+        &mut self,
+        patches: Vec<(String, ProductPatch)>,
+        respond_to: ServiceResponse<Vec<Result<(), ProductError>>, ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing update_products request");
+
+        let mut results = Vec::with_capacity(patches.len());
+        for (id, patch) in patches {
+            results.push(self.apply_patch(id, &patch).await);
+        }
+
+        self.publish_view();
+
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        info!(target: PRODUCT_TARGET, updated = results.len() - failed, failed, "Products patched");
+        let _ = respond_to.send(Ok(results));
+    }
+
+    async fn apply_patch(&mut self, id: String, patch: &ProductPatch) -> Result<(), ProductError> {
+        let Some(product) = self.products.get_mut(&id) else {
+            return Err(ProductError::NotFound(id));
+        };
+        let mut draft = Product::clone(product);
+        patch.apply(&mut draft);
+        if let Err(errors) = draft.validate() {
+            error!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::ProductValidationFailed,
+                product_id = %id,
+                fields = %join_field_errors(&errors),
+                "Validation failed, patch skipped"
+            );
+            return Err(ProductError::InvalidFields(errors));
+        }
+        // Readers still holding the old `Arc` keep a consistent, unpatched product
+        let before = std::mem::replace(product, Arc::new(draft));
+
+        if let Some(audit) = &self.audit {
+            let record = AuditRecord::new(
+                "Product",
+                &id,
+                AuditAction::Update,
+                Some(format!("{:?}", before)),
+                self.products
+                    .get(&id)
+                    .map(|product| format!("{:?}", product)),
+            );
+            audit.record(record).await;
+        }
+        Ok(())
+    }
+
+    #[instrument(
+        target = "actor::product",
+        fields(product_id = %id, quantity = %quantity),
+        skip(self, respond_to),
+    )]
+    async fn handle_reserve_stock(
+        &mut self,
+        id: String,
+        quantity: u32,
+        respond_to: ServiceResponse<(), ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing reserve_stock request");
+
+        if self.outbox_full() {
+            send_error!(respond_to, ProductError::Overloaded);
+        }
+
+        let result = match self.stock.get_mut(&id) {
+            Some(current_stock) => {
+                if *current_stock >= quantity {
+                    let before = *current_stock;
+                    *current_stock -= quantity;
+                    info!(
+                        target: PRODUCT_TARGET,
+                        remaining_stock = *current_stock,
+                        "Stock reserved successfully"
+                    );
+
+                    if let Some(audit) = &self.audit {
+                        let record = AuditRecord::new(
+                            "Stock",
+                            &id,
+                            AuditAction::Action("ReserveStock"),
+                            Some(before.to_string()),
+                            Some(current_stock.to_string()),
+                        );
+                        audit.record(record).await;
+                    }
+
+                    self.record_event(DomainEvent::StockReserved {
+                        product_id: id.clone(),
+                        quantity,
+                    });
+                    self.add_reserved(&id, quantity);
+
+                    Ok(())
+                } else {
+                    error!(
+                        target: PRODUCT_TARGET,
+                        code = %EventCode::InsufficientStock,
+                        available = *current_stock,
+                        requested = quantity,
+                        "Insufficient stock"
+                    );
+                    Err(ProductError::InsufficientStock {
+                        requested: quantity,
+                        available: *current_stock,
+                    })
+                }
+            }
+            None => {
+                error!(
+                    target: PRODUCT_TARGET,
+                    code = %EventCode::ProductNotFound,
+                    "Product not found"
+                );
+                Err(ProductError::NotFound(id))
+            }
+        };
+
+        let _ = respond_to.send(result);
+    }
+
+    fn add_reserved(&mut self, id: &str, quantity: u32) {
+        let units = self.reserved.entry(id.to_string()).or_default();
+        *units = units.saturating_add(quantity);
+    }
+
+    /// **Compensating Action** - The inverse of `reserve_stock`
+    ///
+    /// Used to undo a reservation when a later step of the workflow that made
+    /// it fails, so the units don't stay out of stock for an order that was
+    /// never placed. Only reserved units can come back: a release larger than
+    /// what is outstanding for the product is refused, so stock is never
+    /// created from nothing.
+    #[instrument(
+        target = "actor::product",
+        fields(product_id = %id, quantity = %quantity),
+        skip(self, respond_to),
+    )]
+    async fn handle_release_stock(
+        &mut self,
+        id: String,
+        quantity: u32,
+        respond_to: ServiceResponse<(), ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing release_stock request");
+
+        if self.outbox_full() {
+            send_error!(respond_to, ProductError::Overloaded);
+        }
+        if quantity == 0 {
+            send_error!(respond_to, ProductError::InvalidQuantity(quantity));
+        }
+        let Some(current_stock) = self.stock.get_mut(&id) else {
+            error!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::ProductNotFound,
+                "Product not found"
+            );
+            send_error!(respond_to, ProductError::NotFound(id));
+        };
+        let reserved = self.reserved.get(&id).copied().unwrap_or(0);
+        if quantity > reserved {
+            error!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::InvalidQuantity,
+                reserved,
+                "Release exceeds reserved units"
+            );
+            send_error!(
+                respond_to,
+                ProductError::NotReserved {
+                    requested: quantity,
+                    reserved,
+                }
+            );
+        }
+
+        let before = *current_stock;
+        *current_stock = current_stock.saturating_add(quantity);
+        info!(
+            target: PRODUCT_TARGET,
+            remaining_stock = *current_stock,
+            "Stock released"
+        );
+        if quantity == reserved {
+            self.reserved.remove(&id);
+        } else if let Some(units) = self.reserved.get_mut(&id) {
+            *units -= quantity;
+        }
+
+        if let Some(audit) = &self.audit {
+            let record = AuditRecord::new(
+                "Stock",
+                &id,
+                AuditAction::Action("ReleaseStock"),
+                Some(before.to_string()),
+                Some(current_stock.to_string()),
+            );
+            audit.record(record).await;
+        }
+
+        self.record_event(DomainEvent::StockReleased {
+            product_id: id,
+            quantity,
+        });
+
+        let _ = respond_to.send(Ok(()));
+    }
+
+    /// **TTL Entity** - A reservation that lapses unless confirmed in time
+    ///
+    /// The units leave stock at once, exactly as with `reserve_stock`, so a
+    /// hold can never be oversold. The run loop's tick returns them when
+    /// the hold expires, e.g. when a checkout is abandoned.
+    #[instrument(
+        target = "actor::product",
+        fields(product_id = %id, quantity = %quantity, ttl_ms = ttl.as_millis()),
+        skip(self, respond_to),
+    )]
+    async fn handle_hold_stock(
+        &mut self,
+        id: String,
+        quantity: u32,
+        ttl: Duration,
+        respond_to: ServiceResponse<u64, ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing hold_stock request");
+
+        let Some(current_stock) = self.stock.get_mut(&id) else {
+            send_error!(respond_to, ProductError::NotFound(id));
+        };
+        if *current_stock < quantity {
+            error!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::InsufficientStock,
+                available = *current_stock,
+                requested = quantity,
+                "Insufficient stock"
+            );
+            send_error!(
+                respond_to,
+                ProductError::InsufficientStock {
+                    requested: quantity,
+                    available: *current_stock,
+                }
+            );
+        }
+        *current_stock -= quantity;
+
+        let hold = self.next_hold;
+        self.next_hold += 1;
+        let expires_at = self.clock.now() + ttl;
+        self.holds.insert(
+            hold,
+            StockHold {
+                product_id: id.clone(),
+                quantity,
+                expires_at,
+            },
+        );
+        info!(target: PRODUCT_TARGET, hold, "Stock held");
+
+        if let Some(audit) = &self.audit {
+            let record = AuditRecord::new(
+                "Stock",
+                &id,
+                AuditAction::Action("HoldStock"),
+                None,
+                Some(format!("hold {} of {}", hold, quantity)),
+            );
+            audit.record(record).await;
+        }
+
+        let _ = respond_to.send(Ok(hold));
+    }
+
+    #[instrument(target = "actor::product", fields(product_id = %id, hold), skip(self, respond_to))]
+    async fn handle_confirm_hold(
+        &mut self,
+        id: String,
+        hold: u64,
+        respond_to: ServiceResponse<(), ProductError>,
+    ) {
+        debug!(target: PRODUCT_TARGET, "Processing confirm_hold request");
+
+        if self.outbox_full() {
+            send_error!(respond_to, ProductError::Overloaded);
+        }
+
+        let quantity = match self.holds.get(&hold) {
+            Some(held) if held.product_id == id => held.quantity,
+            _ => {
+                error!(
+                    target: PRODUCT_TARGET,
+                    code = %EventCode::HoldNotFound,
+                    "Stock hold not found"
+                );
+                send_error!(respond_to, ProductError::HoldNotFound(hold));
+            }
+        };
+        self.holds.remove(&hold);
+        self.add_reserved(&id, quantity);
+        info!(target: PRODUCT_TARGET, quantity, "Stock hold confirmed");
+
+        self.record_event(DomainEvent::StockReserved {
+            product_id: id,
+            quantity,
+        });
+
+        let _ = respond_to.send(Ok(()));
+    }
+
+    /// **Self-Scheduled Tick** - Periodic housekeeping inside the actor loop
+    ///
+    /// The run loop `select!`s a tokio interval against the mailbox, so
+    /// time-based work runs between messages with the same exclusive access to
+    /// state as a handler, and nothing outside the actor has to poll it. A tick
+    /// never interrupts a batch; under load it is delayed, not skipped.
+    async fn on_tick(&mut self) {
+        self.expire_holds().await;
+        // Also retries events the bus refused earlier
+        self.relay_outbox().await;
+    }
+
+    /// Return the units of every lapsed hold to stock
+    async fn expire_holds(&mut self) {
+        let now = self.clock.now();
+        let expired: Vec<u64> = self
+            .holds
+            .iter()
+            .filter(|(_, held)| held.expires_at <= now)
+            .map(|(hold, _)| *hold)
+            .collect();
+
+        for hold in expired {
+            let Some(held) = self.holds.remove(&hold) else {
+                continue;
+            };
+            *self.stock.entry(held.product_id.clone()).or_default() += held.quantity;
+            warn!(
+                target: PRODUCT_TARGET,
+                code = %EventCode::HoldExpired,
+                product_id = %held.product_id,
+                hold,
+                quantity = held.quantity,
+                "Stock hold expired, units returned"
+            );
+
+            if let Some(audit) = &self.audit {
+                let record = AuditRecord::new(
+                    "Stock",
+                    &held.product_id,
+                    AuditAction::Action("HoldExpired"),
+                    Some(format!("hold {} of {}", hold, held.quantity)),
+                    None,
+                );
+                audit.record(record).await;
+            }
+
+            self.record_event(DomainEvent::HoldExpired {
+                product_id: held.product_id,
+                hold,
+                quantity: held.quantity,
+            });
+        }
+    }
+}
+
+/// The product map as last published by a [`ProductService`]
+type ProductView = Arc<HashMap<String, Arc<Product>>>;
+
+/// ## Read-Optimized Products
+///
+/// **Pattern:** Reads that queue behind slow writes in the mailbox wait for
+/// no good reason. In read-optimized mode the actor publishes an immutable
+/// copy of its product map through a `watch` channel after each write, and
+/// this handle reads that copy directly: no message, no queue, and a
+/// slow handler cannot delay it.
+///
+/// The actor stays the only writer, so reads may briefly lag a write that
+/// is in flight, but never see one half-applied. Stock levels change on
+/// every order and are not published; ask the actor for those.
+///
+/// ```rust,ignore
+/// let (mut service, products) = ProductService::new(100);
+/// let catalog = service.read_view();
+/// tokio::spawn(service.run());
+/// let widget = catalog.get("p1");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProductReadView {
+    /// One published map per shard, indexed like the shards themselves
+    shards: Vec<watch::Receiver<ProductView>>,
+}
+
+impl ProductReadView {
+    pub fn get(&self, id: &str) -> Option<Arc<Product>> {
+        let shard = shard_for(id, self.shards.len());
+        self.shards[shard].borrow().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Arc<Product>> {
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.borrow().values().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.borrow().len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn share_products(products: HashMap<String, Product>) -> HashMap<String, Arc<Product>> {
+    products
+        .into_iter()
+        .map(|(id, product)| (id, Arc::new(product)))
+        .collect()
+}
+
+/// Stock taken out for a limited time; see `ProductRequest::HoldStock`
+#[derive(Debug)]
+struct StockHold {
+    product_id: String,
+    quantity: u32,
+    expires_at: tokio::time::Instant,
+}
+
+#[derive(Clone)]
+pub struct ProductClient {
+    sender: mpsc::Sender<ProductRequest>,
+    policy: ClientPolicy,
+}
+
+impl ProductClient {
+    pub fn new(sender: mpsc::Sender<ProductRequest>) -> Self {
+        Self {
+            sender,
+            policy: ClientPolicy::default(),
+        }
+    }
+
+    /// Bound every request made through this handle. Clone first for a per-call
+    /// override: `client.clone().with_timeout(d).get_...(..)`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.policy.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry timed-out requests with exponential backoff (needs a timeout to trigger)
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.policy.retry = Some(retry);
+        self
+    }
+
+    /// Fail fast once the actor keeps failing; clones made after this share the breaker
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.policy.breaker = Some(Arc::new(CircuitBreaker::new(config)));
+        self
+    }
+
+    /// Choose between waiting and failing fast when the mailbox is full
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.policy.backpressure = backpressure;
+        self
+    }
+
+    /// Draw one token per call from `limiter`, in the bucket named by [`Self::with_caller`]
+    pub fn with_rate_limit(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.policy.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Identify the caller behind this handle for rate limiting
+    pub fn with_caller(mut self, caller: impl Into<String>) -> Self {
+        self.policy.caller = Some(caller.into());
+        self
+    }
+
+    /// Deliver `request` to this actor after `delay`, via `scheduler`. The reply
+    /// is discarded; keep the handle to cancel the delivery before it fires.
+    pub async fn send_after(
+        &self,
+        scheduler: &SchedulerClient,
+        delay: Duration,
+        request: ProductRequest,
+    ) -> Result<TimerHandle, String> {
+        scheduler
+            .send_after(delay, self.sender.clone(), request)
+            .await
+    }
+
+    /// Atomically read and modify a stock level inside the actor, e.g. "decrement
+    /// only while above a threshold". Same semantics as [`UserClient::modify_user`].
+    #[instrument(skip(self, f), fields(request_id = tracing::field::Empty))]
+    pub async fn modify_stock<R, F>(&self, id: String, f: F) -> Result<R, String>
+    where
+        F: FnOnce(&mut u32) -> Result<R, ProductError> + Send + 'static,
+        R: Send + 'static,
+    {
+        debug!("Sending modify request");
+        let (modifier, output) = Modifier::new(f);
+        let mut modifier = Some(modifier);
+        let policy = self.policy.without_retry();
+        RequestId::propagate(policy.call(|| {
+            let id = id.clone();
+            let modifier = modifier.take();
+            async move {
+                let modifier = modifier.ok_or_else(|| "modify_stock is not retried".to_string())?;
+                let (respond_to, response) = ServiceResponse::channel();
+                self.policy
+                    .send(
+                        &self.sender,
+                        ProductRequest::ModifyStock {
+                            id,
+                            modifier,
+                            respond_to,
+                        },
+                    )
+                    .await?;
+                response.await.map_err(|e| e.to_string())
+            }
+        }))
+        .await?;
+        output.await.map_err(|e| e.to_string())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        let (respond_to, response) = ServiceResponse::channel();
+        self.sender
+            .send(ProductRequest::Shutdown { respond_to })
+            .await
+            .map_err(|e| e.to_string())?;
+        response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Generate product client methods
+client_method!(ProductClient => fn get_product(id: String) -> Option<Product> as ProductRequest::GetProduct);
+client_method!(ProductClient => fn get_product_shared(id: String) -> Option<Arc<Product>> as ProductRequest::GetProductShared);
+client_method!(ProductClient => fn update_products(patches: Vec<(String, ProductPatch)>) -> Vec<Result<(), ProductError>> as ProductRequest::UpdateProducts);
+client_method!(ProductClient => fn count() -> usize as ProductRequest::Count);
+client_method!(ProductClient => fn exists(id: String) -> bool as ProductRequest::Exists);
+client_method!(ProductClient => fn get_products(ids: Vec<String>) -> HashMap<String, Product> as ProductRequest::GetProducts);
+client_method!(ProductClient => fn check_stock(id: String) -> u32 as ProductRequest::CheckStock);
+client_method!(ProductClient => fn reserve_stock(id: String, quantity: u32) -> () as ProductRequest::ReserveStock);
+client_method!(ProductClient => fn release_stock(id: String, quantity: u32) -> () as ProductRequest::ReleaseStock);
+client_method!(ProductClient => fn hold_stock(id: String, quantity: u32, ttl: Duration) -> u64 as ProductRequest::HoldStock);
+client_method!(ProductClient => fn confirm_hold(id: String, hold: u64) -> () as ProductRequest::ConfirmHold);
+client_method!(ProductClient => fn snapshot() -> ProductSnapshot as ProductRequest::Snapshot);
+client_method!(ProductClient => fn get_stats() -> MailboxStats as ProductRequest::GetStats);
+client_method!(ProductClient => fn ping() -> () as ProductRequest::Ping);
+client_method!(ProductClient => fn import(snapshot: ProductSnapshot) -> usize as ProductRequest::Import);
+client_method!(ProductClient => fn drain() -> () as ProductRequest::Drain);
+
+// =============================================================================
+// INGREDIENT 7: ROOT ACTOR (ORCHESTRATOR)
+// =============================================================================
+
+/// Order-specific error types
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OrderError {
+    NotFound(String),
+    InvalidProduct(String),
+    InvalidUser(String),
+    InsufficientStock(String),
+    ValidationError(String),
+    /// The payload failed [`Validate`]; every offending field is listed
+    InvalidFields(Vec<FieldError>),
+    DatabaseError(String),
+    /// The actor is draining and no longer accepts writes
+    Draining,
+    /// The actor shed the request because its mailbox is backed up
+    Overloaded,
+    /// An order with this ID was already placed
+    AlreadyExists(String),
+    /// The handler panicked before answering; the actor itself kept running
+    HandlerPanicked,
+}
+
+impl std::fmt::Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::NotFound(id) => write!(f, "Order not found: {}", id),
+            OrderError::InvalidProduct(id) => write!(f, "Invalid product: {}", id),
+            OrderError::InvalidUser(id) => write!(f, "Invalid user: {}", id),
+            OrderError::InsufficientStock(msg) => write!(f, "Insufficient stock: {}", msg),
+            OrderError::ValidationError(msg) => write!(f, "Order validation error: {}", msg),
+            OrderError::InvalidFields(errors) => {
+                write!(f, "Invalid order: {}", join_field_errors(errors))
+            }
+            OrderError::DatabaseError(msg) => write!(f, "Order database error: {}", msg),
+            OrderError::Draining => write!(f, "Order service is draining"),
+            OrderError::Overloaded => write!(f, "Order service is overloaded"),
+            OrderError::AlreadyExists(id) => write!(f, "Order already exists: {}", id),
+            OrderError::HandlerPanicked => write!(f, "Order handler panicked"),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+/// ## Ingredient 7: Root Actor for Orchestration
+///
+/// **Pattern:** Root actors coordinate multiple sub-actors to implement complex
+/// business workflows. They don't store domain data themselves - instead they
+/// delegate to specialized sub-actors.
+///
+/// **Why This Works:**
+/// - **Separation of concerns** - Root actor focuses on orchestration
+/// - **Composition** - Complex operations built from simpler sub-operations
+/// - **Error handling** - Centralized error handling and rollback logic
+/// - **Tracing** - Full request flow visible across multiple actors
+///
+/// **Delegation Pattern:**
+/// 1. Validate inputs using sub-actors
+/// 2. Perform business logic steps in sequence
+/// 3. Handle errors and rollbacks appropriately
+/// 4. Return results to caller
+pub struct OrderService {
+    receiver: mpsc::Receiver<OrderRequest>,
+    user_client: UserClient,
+    product_client: ProductClient,
+    orders: HashMap<String, Order>,
+    events: Option<EventBusClient>,
+    audit: Option<AuditClient>,
+    faults: Option<FaultInjector>,
+    slow_handler: Duration,
+    batch_size: usize,
+    /// Taken from the mailbox in the current batch but not yet handled
+    batched: usize,
+    shedder: Option<LoadShedder>,
+    draining: bool,
+    shutdown_acks: Vec<ServiceResponse<(), OrderError>>,
+    on_stop: Option<StopHook<OrderSnapshot>>,
+}
+
+impl OrderService {
+    pub fn new(
+        buffer_size: usize,
+        user_client: UserClient,
+        product_client: ProductClient,
+    ) -> (Self, OrderClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            user_client,
+            product_client,
+            orders: HashMap::new(),
+            events: None,
+            audit: None,
+            faults: None,
+            slow_handler: ActorConfig::default().slow_handler,
+            batch_size: ActorConfig::default().batch_size,
+            batched: 0,
+            shedder: None,
+            draining: false,
+            shutdown_acks: Vec::new(),
+            on_stop: None,
+        };
+        let client = OrderClient::new(sender);
+        (service, client)
+    }
+
+    /// Publish lifecycle events to the given bus
+    pub fn with_event_bus(mut self, events: EventBusClient) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Record every mutation in the given audit log
+    pub fn with_audit(mut self, audit: AuditClient) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Inject artificial latency and failures, for demos
+    pub fn with_faults(mut self, faults: FaultInjector) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Warn about any handler that takes longer than `threshold`
+    pub fn with_slow_handler(mut self, threshold: Duration) -> Self {
+        self.slow_handler = threshold;
+        self
+    }
+
+    /// Take up to `size` queued messages per wakeup; see [`ActorConfig::batch_size`]
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Refuse requests once `threshold` are queued; see [`ActorConfig::shed_threshold`]
+    pub fn with_load_shedding(mut self, threshold: usize) -> Self {
+        self.shedder = Some(LoadShedder::new(threshold));
+        self
+    }
+
+    /// Hand the final state to `hook` when the actor stops, e.g. to persist it
+    pub fn on_stop(mut self, hook: impl FnOnce(OrderSnapshot) + Send + Sync + 'static) -> Self {
+        self.on_stop = Some(Box::new(hook));
+        self
+    }
+
+    /// Load previously captured state before the actor is spawned
+    pub fn restore(mut self, snapshot: OrderSnapshot) -> Self {
+        self.orders = snapshot.orders;
+        self
+    }
+
+    fn snapshot(&self) -> OrderSnapshot {
+        OrderSnapshot {
+            orders: self.orders.clone(),
+        }
+    }
+
+    #[instrument(target = "actor::order", skip(self, snapshot, respond_to))]
+    fn handle_import(
+        &mut self,
+        snapshot: OrderSnapshot,
+        respond_to: ServiceResponse<usize, OrderError>,
+    ) {
+        debug!(target: ORDER_TARGET, "Processing import request");
+
+        let count = snapshot.orders.len();
+        self.orders.extend(snapshot.orders);
+
+        info!(target: ORDER_TARGET, order_count = count, "Orders imported");
+        let _ = respond_to.send(Ok(count));
+    }
+
+    #[instrument(target = "actor::order", name = "order_service", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: ORDER_TARGET, "OrderService starting");
+
+        let mut batch = Vec::with_capacity(self.batch_size);
+        while self.receiver.recv_many(&mut batch, self.batch_size).await > 0 {
+            self.batched = batch.len();
+            for msg in batch.drain(..) {
+                self.batched -= 1;
+                self.process(msg).await;
+            }
+        }
+
+        if let Some(hook) = self.on_stop.take() {
+            hook(self.snapshot());
+        }
+        for ack in self.shutdown_acks.drain(..) {
+            let _ = ack.send(Ok(()));
+        }
+        info!(target: ORDER_TARGET, "OrderService stopped");
+    }
+
+    /// Run one message through fault injection, tracing and timing
+    async fn process(&mut self, mut msg: OrderRequest) {
+        if let Some(sent_at) = msg.sent_at() {
+            MailboxStats::sample(&self.receiver, self.batched, sent_at).publish("Order");
+        }
+        if let Some(shedder) = &mut self.shedder {
+            let depth = self.receiver.len() + self.batched;
+            if msg.is_sheddable() && shedder.overloaded(depth, ORDER_TARGET) {
+                msg.reject(OrderError::Overloaded);
+                return;
+            }
+        }
+        if let Some(faults) = &self.faults {
+            if !msg.is_control() && !faults.inject().await {
+                return;
+            }
+        }
+        // Handle inside the caller's span and request so the trace continues across the mailbox
+        let span = msg
+            .caller_span()
+            .cloned()
+            .unwrap_or_else(tracing::Span::current);
+        let request_id = msg.request_id();
+        let variant = msg.variant();
+        msg.answer_on_drop(OrderError::HandlerPanicked);
+        let started = tokio::time::Instant::now();
+        let handled = RequestId::scope(request_id, self.handle_message(msg).instrument(span));
+        if let Err(panic) = catch_unwind(std::pin::pin!(handled)).await {
+            error!(
+                target: ORDER_TARGET,
+                code = %EventCode::HandlerPanicked,
+                panic = panic_message(&panic),
+                "Handler panicked, request failed"
+            );
+        }
+        let elapsed = started.elapsed();
+        Metrics::global().record_handler("OrderRequest", variant, elapsed);
+        if elapsed > self.slow_handler {
+            warn!(
+                target: ORDER_TARGET,
+                code = %EventCode::SlowHandler,
+                request = variant,
+                duration_ms = elapsed.as_millis(),
+                threshold_ms = self.slow_handler.as_millis(),
+                "Slow handler"
+            );
+        }
+    }
+
+    /// Dispatch one message
+    async fn handle_message(&mut self, msg: OrderRequest) {
+        match msg {
+            OrderRequest::CreateOrder { order, respond_to } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_create_order(order, respond_to).await;
+            }
+            OrderRequest::GetOrder { id, respond_to } => {
+                self.handle_get_order(id, respond_to);
+            }
+            OrderRequest::Count { respond_to } => {
+                let _ = respond_to.send(Ok(self.orders.len()));
+            }
+            OrderRequest::Exists { id, respond_to } => {
+                let _ = respond_to.send(Ok(self.orders.contains_key(&id)));
+            }
+            OrderRequest::Snapshot { respond_to } => {
+                let _ = respond_to.send(Ok(self.snapshot()));
+            }
+            OrderRequest::GetStats { respond_to } => {
+                let stats =
+                    MailboxStats::sample(&self.receiver, self.batched, respond_to.sent_at());
+                stats.log(ORDER_TARGET);
+                let _ = respond_to.send(Ok(stats));
+            }
+            OrderRequest::Ping { respond_to } => {
+                let _ = respond_to.send(Ok(()));
+            }
+            OrderRequest::Import {
+                snapshot,
+                respond_to,
+            } => {
+                let Some(respond_to) = self.admit(respond_to) else {
+                    return;
+                };
+                self.handle_import(snapshot, respond_to);
+            }
+            OrderRequest::Drain { respond_to } => {
+                info!(
+                    target: ORDER_TARGET,
+                    "OrderService draining, writes are refused from now on"
+                );
+                self.draining = true;
+                let _ = respond_to.send(Ok(()));
+            }
+            OrderRequest::Shutdown { respond_to } => {
+                // Closing the mailbox lets the loop drain what's queued, then end
+                info!(target: ORDER_TARGET, "OrderService shutting down, draining queued requests");
+                self.receiver.close();
+                self.shutdown_acks.push(respond_to);
+            }
+        }
+    }
+
+    /// Pass a write's responder through, or answer `Draining` and swallow it
+    fn admit<T>(
+        &self,
+        respond_to: ServiceResponse<T, OrderError>,
+    ) -> Option<ServiceResponse<T, OrderError>> {
+        if self.draining {
+            debug!(target: ORDER_TARGET, "Write refused while draining");
+            let _ = respond_to.send(Err(OrderError::Draining));
+            return None;
+        }
+        Some(respond_to)
+    }
+
+    /// **Orchestration Handler Example** - Coordinates multiple sub-actors
+    ///
+    /// This demonstrates the core orchestration pattern:
+    /// 1. **Validate user** via UserService
+    /// 2. **Validate product** via ProductService  
+    /// 3. **Reserve stock** via ProductService
+    /// 4. **Create order** locally
+    ///
+    /// **Error Handling:** A duplicate order ID is refused before anything else
+    /// runs. Steps 1-3 run as a [`Saga`]: if one fails, the steps before it are
+    /// compensated, and if the local create fails afterwards the saga is
+    /// compensated as a whole, so a reservation is never leaked.
+    ///
+    /// **Tracing:** The full workflow is traced across multiple actors, making
+    /// debugging complex flows much easier.
+    #[instrument(
+        target = "actor::order",
+        fields(
+            order_id = %order.id,
+            user_id = %order.user_id,
+            product_id = %order.product_id,
+            quantity = %order.quantity,
+            total = %order.total
+        ),
+        skip(self, order, respond_to)
+    )]
+    async fn handle_create_order(
+        &mut self,
+        order: Order,
+        respond_to: ServiceResponse<String, OrderError>,
+    ) {
+        info!(target: ORDER_TARGET, "Processing create_order request");
+
+        if let Err(errors) = order.validate() {
+            error!(
+                target: ORDER_TARGET,
+                code = %EventCode::OrderValidationFailed,
+                fields = %join_field_errors(&errors),
+                "Validation failed"
+            );
+            send_error!(respond_to, OrderError::InvalidFields(errors));
+        }
+
+        // The actor handles nothing else until this order is done, so a local
+        // conflict is caught here, before any other actor is touched
+        if self.orders.contains_key(&order.id) {
+            error!(
+                target: ORDER_TARGET,
+                code = %EventCode::OrderAlreadyExists,
+                "Order ID already taken"
+            );
+            send_error!(respond_to, OrderError::AlreadyExists(order.id));
+        }
+
+        // Steps 1-3: validate user and product, then reserve stock, as a saga
+        let (users, products) = (self.user_client.clone(), self.product_client.clone());
+        let (reserve, release) = (products.clone(), products.clone());
+        let (user_id, product_id) = (order.user_id.clone(), order.product_id.clone());
+        let (reserve_id, release_id) = (product_id.clone(), product_id.clone());
+        let quantity = order.quantity;
+        let saga = Saga::new("create_order")
+            .step("validate_user", move || Self::validate_user(users, user_id))
+            .step("validate_product", move || {
+                Self::validate_product(products, product_id)
+            })
+            .compensated_step(
+                "reserve_stock",
+                move || Self::reserve_stock(reserve, reserve_id, quantity),
+                move || Self::release_stock(release, release_id, quantity),
+            );
+        let reservation = match saga.run().await {
+            Ok(reservation) => reservation,
+            Err(error) => send_error!(respond_to, error),
+        };
+
+        // Step 4: Create order (local operation). Anything failing from here
+        // on hands the reserved units back.
+        self.orders.insert(order.id.clone(), order.clone());
+        if INVARIANT_CHECKS {
+            if let Err(violation) = order.check_invariants() {
+                error!(
+                    target: ORDER_TARGET,
+                    code = %EventCode::OrderInvariantViolated,
+                    violation = %violation,
+                    "Invariant violated, rolling back"
+                );
+                self.orders.remove(&order.id);
+                reservation.compensate().await;
+                send_error!(
+                    respond_to,
+                    OrderError::ValidationError(format!("Invariant violated: {}", violation))
+                );
+            }
+        }
+
+        if let Some(audit) = &self.audit {
+            let record = AuditRecord::new(
+                "Order",
+                &order.id,
+                AuditAction::Create,
+                None,
+                Some(format!("{:?}", order)),
+            );
+            audit.record(record).await;
+        }
+
+        if let Some(events) = &self.events {
+            events
+                .publish(DomainEvent::OrderCreated {
+                    order_id: order.id.clone(),
+                    user_id: order.user_id.clone(),
+                    product_id: order.product_id.clone(),
+                    quantity: order.quantity,
+                })
+                .await;
+        }
+
+        info!(target: ORDER_TARGET, "Order created successfully");
+        let _ = respond_to.send(Ok(order.id));
+    }
+
+    #[instrument(target = "actor::order", fields(order_id = %id), skip(self, respond_to))]
+    fn handle_get_order(&self, id: String, respond_to: ServiceResponse<Option<Order>, OrderError>) {
+        debug!(target: ORDER_TARGET, "Processing get_order request");
+
+        let order = self.orders.get(&id).cloned();
+
+        match &order {
+            Some(order) => info!(target: ORDER_TARGET, total = %order.total, "Order found"),
+            None => debug!(target: ORDER_TARGET, "Order not found"),
+        }
+
+        let _ = respond_to.send(Ok(order));
+    }
+
+    /// Saga step 1: the user must exist
+    async fn validate_user(users: UserClient, user_id: String) -> Result<(), OrderError> {
+        match users.get_user(user_id.clone()).await {
+            Ok(Some(user)) => {
+                info!(target: ORDER_TARGET, user_name = %user.name, "User validation successful");
+                Ok(())
+            }
+            Ok(None) => {
+                error!(target: ORDER_TARGET, code = %EventCode::OrderUserInvalid, "User not found");
+                Err(OrderError::InvalidUser(user_id))
+            }
+            Err(e) => {
+                error!(
+                    target: ORDER_TARGET,
+                    code = %EventCode::OrderUserInvalid,
+                    error = %e,
+                    "User validation failed"
+                );
+                Err(OrderError::InvalidUser(format!(
+                    "User validation failed: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Saga step 2: the product must exist
+    async fn validate_product(
+        products: ProductClient,
+        product_id: String,
+    ) -> Result<(), OrderError> {
+        match products.get_product_shared(product_id.clone()).await {
+            Ok(Some(product)) => {
+                info!(
+                    target: ORDER_TARGET,
+                    product_name = %product.name,
+                    price = %product.price,
+                    "Product validation successful"
+                );
+                Ok(())
+            }
+            Ok(None) => {
+                error!(
+                    target: ORDER_TARGET,
+                    code = %EventCode::OrderProductInvalid,
+                    "Product not found"
+                );
+                Err(OrderError::InvalidProduct(product_id))
+            }
+            Err(e) => {
+                error!(
+                    target: ORDER_TARGET,
+                    code = %EventCode::OrderProductInvalid,
+                    error = %e,
+                    "Product validation failed"
+                );
+                Err(OrderError::InvalidProduct(format!(
+                    "Product validation failed: {}",
+                    e
+                )))
+            }
+        }
+    }
+
+    /// Saga step 3: take the units out of stock, compensated by [`Self::release_stock`]
+    async fn reserve_stock(
+        products: ProductClient,
+        product_id: String,
+        quantity: u32,
+    ) -> Result<(), OrderError> {
+        if let Err(e) = products.reserve_stock(product_id, quantity).await {
+            error!(
+                target: ORDER_TARGET,
+                code = %EventCode::StockReservationFailed,
+                error = %e,
+                "Stock reservation failed"
+            );
+            return Err(OrderError::InsufficientStock(format!(
+                "Stock reservation failed: {}",
+                e
+            )));
+        }
+        info!(target: ORDER_TARGET, "Stock reserved successfully");
+        Ok(())
+    }
+
+    /// Put reserved units back. A failure here can only be logged: the units
+    /// stay out of stock until someone reconciles them.
+    async fn release_stock(products: ProductClient, product_id: String, quantity: u32) {
+        match products.release_stock(product_id, quantity).await {
+            Ok(()) => info!(target: ORDER_TARGET, quantity, "Stock reservation released"),
+            Err(e) => error!(
+                target: ORDER_TARGET,
+                code = %EventCode::StockReservationFailed,
+                error = %e,
+                "Could not release stock reservation"
+            ),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OrderClient {
+    sender: mpsc::Sender<OrderRequest>,
+    policy: ClientPolicy,
+}
+
+impl OrderClient {
+    pub fn new(sender: mpsc::Sender<OrderRequest>) -> Self {
+        Self {
+            sender,
+            policy: ClientPolicy::default(),
+        }
+    }
+
+    /// Bound every request made through this handle. Clone first for a per-call
+    /// override: `client.clone().with_timeout(d).get_...(..)`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.policy.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry timed-out requests with exponential backoff (needs a timeout to trigger)
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.policy.retry = Some(retry);
+        self
+    }
+
+    /// Fail fast once the actor keeps failing; clones made after this share the breaker
+    pub fn with_circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.policy.breaker = Some(Arc::new(CircuitBreaker::new(config)));
+        self
+    }
+
+    /// Choose between waiting and failing fast when the mailbox is full
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> Self {
+        self.policy.backpressure = backpressure;
+        self
+    }
+
+    /// Draw one token per call from `limiter`, in the bucket named by [`Self::with_caller`]
+    pub fn with_rate_limit(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.policy.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Identify the caller behind this handle for rate limiting
+    pub fn with_caller(mut self, caller: impl Into<String>) -> Self {
+        self.policy.caller = Some(caller.into());
+        self
+    }
+
+    /// Deliver `request` to this actor after `delay`, via `scheduler`. The reply
+    /// is discarded; keep the handle to cancel the delivery before it fires.
+    pub async fn send_after(
+        &self,
+        scheduler: &SchedulerClient,
+        delay: Duration,
+        request: OrderRequest,
+    ) -> Result<TimerHandle, String> {
+        scheduler
+            .send_after(delay, self.sender.clone(), request)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        let (respond_to, response) = ServiceResponse::channel();
+        self.sender
+            .send(OrderRequest::Shutdown { respond_to })
+            .await
+            .map_err(|e| e.to_string())?;
+        response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Generate order client methods
+client_method!(OrderClient => fn create_order(order: Order) -> String as OrderRequest::CreateOrder);
+client_method!(OrderClient => fn get_order(id: String) -> Option<Order> as OrderRequest::GetOrder);
+client_method!(OrderClient => fn count() -> usize as OrderRequest::Count);
+client_method!(OrderClient => fn exists(id: String) -> bool as OrderRequest::Exists);
+client_method!(OrderClient => fn snapshot() -> OrderSnapshot as OrderRequest::Snapshot);
+client_method!(OrderClient => fn get_stats() -> MailboxStats as OrderRequest::GetStats);
+client_method!(OrderClient => fn ping() -> () as OrderRequest::Ping);
+client_method!(OrderClient => fn import(snapshot: OrderSnapshot) -> usize as OrderRequest::Import);
+client_method!(OrderClient => fn drain() -> () as OrderRequest::Drain);
+
+// =============================================================================
+// INGREDIENT 8: SYSTEM COORDINATOR
+// =============================================================================
+
+/// ## Ingredient 8: System Coordinator
+///
+/// **Pattern:** The coordinator manages the lifecycle of the entire actor system.
+/// It handles startup, dependency injection, and graceful shutdown.
+///
+/// **Responsibilities:**
+/// - **Start sub-actors first** - Ensure dependencies are available
+/// - **Supervise** - Every service runs under a [`Supervisor`] that restarts it on panic
+/// - **Inject dependencies** - Pass sub-actor clients to root actors
+/// - **Manage handles** - Track all spawned tasks for proper cleanup
+/// - **Graceful shutdown** - Shutdown in dependency order and wait for completion
+///
+/// **Benefits:**
+/// - **Single point of control** - Easy to manage the entire system
+/// - **Proper initialization order** - Dependencies started before dependents
+/// - **Clean shutdown** - No zombie processes or resource leaks
+/// - **Error handling** - Centralized error handling for system-wide issues
+pub struct OrderSystem {
+    pub order_client: OrderClient,
+    pub user_client: UserClient,
+    pub product_client: ProductClient,
+    pub event_bus: EventBusClient,
+    /// Present when built with [`OrderSystemBuilder::audit_log`]
+    pub audit: Option<AuditClient>,
+    faults: DemoFaults,
+    handles: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl Default for OrderSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderSystem {
+    /// Create and start the entire actor system with default settings
+    ///
+    /// See [`OrderSystemBuilder::build`] for the startup order.
+    pub fn new() -> Self {
+        Self::restore(SystemSnapshot::default())
+    }
+
+    /// Start the system with every service pre-loaded from `snapshot`
+    pub fn restore(snapshot: SystemSnapshot) -> Self {
+        Self::restore_with(snapshot, RestartPolicies::default())
+    }
+
+    /// Like [`OrderSystem::restore`], choosing how each service is restarted after a panic
+    pub fn restore_with(snapshot: SystemSnapshot, restart: RestartPolicies) -> Self {
+        Self::builder()
+            .snapshot(snapshot)
+            .restart_policies(restart)
+            .build()
+    }
+
+    /// Create the system with default settings and wait until every actor is
+    /// running; see [`OrderSystemBuilder::start`]
+    pub async fn start() -> Result<Self, StartupError> {
+        Self::builder().start().await
+    }
+
+    /// Configure buffer sizes, ID generation, timeouts or mock clients before starting
+    pub fn builder() -> OrderSystemBuilder {
+        OrderSystemBuilder::default()
+    }
+
+    /// Change injected latency and failures while the system runs
+    pub fn set_demo_profile(&self, profile: DemoProfile) {
+        info!(?profile, "Applying demo profile");
+        self.faults.users.set(profile.users);
+        self.faults.products.set(profile.products);
+        self.faults.orders.set(profile.orders);
+    }
+
+    /// Gracefully shutdown the entire actor system
+    ///
+    /// **Shutdown Order:**
+    /// 1. Shutdown root actors first (they depend on sub-actors); each one
+    ///    drains its queued requests before acknowledging
+    /// 2. Shutdown sub-actors  
+    /// 3. Shutdown the event bus last (everyone publishes to it)
+    /// 4. Wait for all tasks to complete
+    ///
+    /// **Error Handling:** Log errors but continue shutdown to prevent hangs
+    #[instrument(skip(self))]
+    pub async fn shutdown(self) -> Result<(), String> {
+        info!("Shutting down order system");
+
+        // Shutdown in dependency order (root actors first)
+        let _ = self.order_client.shutdown().await;
+        let _ = self.user_client.shutdown().await;
+        let _ = self.product_client.shutdown().await;
+        let _ = self.event_bus.shutdown().await;
+        if let Some(audit) = &self.audit {
+            let _ = audit.shutdown().await;
+        }
+
+        // Wait for all services to finish
+        for handle in self.handles {
+            if let Err(e) = handle.await {
+                error!(error = ?e, "Service shutdown error");
+            }
+        }
+
+        info!("Order system shutdown complete");
+        Ok(())
+    }
+
+    /// Capture the state of every service
+    ///
+    /// Each service is snapshotted independently, so an order created between
+    /// the calls may reference stock that the product snapshot already reflects.
+    /// Quiesce traffic first when that matters.
+    #[instrument(skip(self))]
+    pub async fn snapshot(&self) -> Result<SystemSnapshot, String> {
+        Ok(SystemSnapshot {
+            users: self.user_client.snapshot().await?,
+            products: self.product_client.snapshot().await?,
+            orders: self.order_client.snapshot().await?,
+        })
+    }
+
+    /// A [`HealthMonitor`] for the three services, pinging every `interval`.
+    /// A ping still unanswered when the next round is due counts as failed.
+    pub fn health_monitor(
+        &self,
+        interval: Duration,
+    ) -> (HealthMonitor, watch::Receiver<SystemHealth>) {
+        let (monitor, health) = HealthMonitor::new(interval, interval);
+        let monitor = monitor
+            .register("users", self.user_client.clone())
+            .register("products", self.product_client.clone())
+            .register("orders", self.order_client.clone());
+        (monitor, health)
+    }
+
+    /// Start the system from a snapshot file written by [`OrderSystem::shutdown_to_snapshot`]
+    #[cfg(feature = "serde")]
+    pub fn new_from_snapshot(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        Ok(Self::restore(SystemSnapshot::load(path)?))
+    }
+
+    /// Look up a type-erased client by service name (`"users"`, `"products"`, `"orders"`)
+    #[cfg(feature = "serde")]
+    pub fn dyn_client(&self, name: &str) -> Option<Box<dyn DynClient>> {
+        match name {
+            "users" => Some(Box::new(self.user_client.clone())),
+            "products" => Some(Box::new(self.product_client.clone())),
+            "orders" => Some(Box::new(self.order_client.clone())),
+            _ => None,
+        }
+    }
+
+    /// Snapshot every service to `path`, then shut down gracefully
+    #[cfg(feature = "serde")]
+    #[instrument(skip(self, path))]
+    pub async fn shutdown_to_snapshot(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(), String> {
+        self.snapshot().await?.save(path)?;
+        self.shutdown().await
+    }
+
+    /// Abort handles for every top-level task, for a forced stop
+    fn abort_handles(&self) -> Vec<tokio::task::AbortHandle> {
+        self.handles
+            .iter()
+            .map(tokio::task::JoinHandle::abort_handle)
+            .collect()
+    }
+}
+
+/// Settings every actor of an [`OrderSystem`] gets unless overridden
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActorConfig {
+    /// Capacity of the actor's mailbox
+    pub mailbox_size: usize,
+    /// How long callers wait on this actor per call: the clients the
+    /// [`OrderSystem`] hands out, and the order service's calls to users and
+    /// products
+    pub call_timeout: Duration,
+    /// Handlers slower than this are logged as warnings
+    pub slow_handler: Duration,
+    /// Most messages taken from the mailbox per wakeup. Draining a burst in
+    /// one go saves a trip through the scheduler per message; 1 restores the
+    /// one-at-a-time loop. Messages still run strictly in order.
+    pub batch_size: usize,
+    /// Queue depth at which the actor starts refusing requests with an
+    /// `Overloaded` error, until the queue drains to half of it; `None` lets
+    /// the queue (and latency) grow
+    pub shed_threshold: Option<usize>,
+    /// How often the actor runs its periodic housekeeping (products: expiring
+    /// stock holds)
+    pub tick_interval: Duration,
+    /// Audit records kept per entity type this actor writes (products: both
+    /// `Product` and `Stock`), oldest dropped first; `usize::MAX` keeps them all
+    pub audit_retention: usize,
+}
+
+impl Default for ActorConfig {
+    fn default() -> Self {
+        Self {
+            mailbox_size: 100,
+            call_timeout: Duration::from_secs(5),
+            slow_handler: Duration::from_millis(100),
+            batch_size: 32,
+            shed_threshold: None,
+            tick_interval: Duration::from_secs(1),
+            audit_retention: 10_000,
+        }
+    }
+}
+
+/// Per-actor deviations from [`SystemConfig::defaults`]; `None` inherits
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActorOverrides {
+    pub mailbox_size: Option<usize>,
+    pub call_timeout: Option<Duration>,
+    pub slow_handler: Option<Duration>,
+    pub batch_size: Option<usize>,
+    pub shed_threshold: Option<usize>,
+    pub tick_interval: Option<Duration>,
+    pub audit_retention: Option<usize>,
+}
+
+/// Global actor settings with per-entity-type overrides.
+///
+/// Overrides are resolved field by field, so an actor can change its mailbox
+/// size and still pick up a later change to the default timeout.
+/// [`OrderSystemBuilder::try_build`] validates the resolved values before
+/// anything is spawned.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemConfig {
+    pub defaults: ActorConfig,
+    pub users: ActorOverrides,
+    pub products: ActorOverrides,
+    pub orders: ActorOverrides,
+}
+
+impl SystemConfig {
+    /// The effective settings for one actor
+    pub fn resolve(&self, overrides: &ActorOverrides) -> ActorConfig {
+        ActorConfig {
+            mailbox_size: overrides.mailbox_size.unwrap_or(self.defaults.mailbox_size),
+            call_timeout: overrides.call_timeout.unwrap_or(self.defaults.call_timeout),
+            slow_handler: overrides.slow_handler.unwrap_or(self.defaults.slow_handler),
+            batch_size: overrides.batch_size.unwrap_or(self.defaults.batch_size),
+            shed_threshold: overrides.shed_threshold.or(self.defaults.shed_threshold),
+            tick_interval: overrides
+                .tick_interval
+                .unwrap_or(self.defaults.tick_interval),
+            audit_retention: overrides
+                .audit_retention
+                .unwrap_or(self.defaults.audit_retention),
+        }
+    }
+
+    /// Check every resolved actor config, naming the first one that is unusable
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (actor, overrides) in [
+            ("users", &self.users),
+            ("products", &self.products),
+            ("orders", &self.orders),
+        ] {
+            let config = self.resolve(overrides);
+            if config.mailbox_size == 0 {
+                return Err(ConfigError::InvalidMailboxSize(actor));
+            }
+            if config.call_timeout.is_zero() {
+                return Err(ConfigError::InvalidTimeout(actor));
+            }
+            if config.tick_interval.is_zero() {
+                return Err(ConfigError::InvalidTickInterval(actor));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configuration error types
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    InvalidMailboxSize(&'static str),
+    InvalidTimeout(&'static str),
+    InvalidTickInterval(&'static str),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::InvalidMailboxSize(actor) => {
+                write!(f, "{}: mailbox size must be at least 1", actor)
+            }
+            ConfigError::InvalidTimeout(actor) => {
+                write!(f, "{}: call timeout must be non-zero", actor)
+            }
+            ConfigError::InvalidTickInterval(actor) => {
+                write!(f, "{}: tick interval must be non-zero", actor)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Why [`OrderSystemBuilder::start`] gave up
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartupError {
+    Config(ConfigError),
+    /// An actor did not answer its readiness ping within its call timeout
+    ActorNotReady {
+        actor: &'static str,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for StartupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartupError::Config(e) => write!(f, "Invalid config: {}", e),
+            StartupError::ActorNotReady { actor, reason } => {
+                write!(f, "{} did not start: {}", actor, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StartupError {}
+
+impl From<ConfigError> for StartupError {
+    fn from(e: ConfigError) -> Self {
+        StartupError::Config(e)
+    }
+}
+
+/// Startup configuration for [`OrderSystem`]; see [`OrderSystem::builder`].
+///
+/// Supplying a client (e.g. one whose sender a test drives by hand) replaces the
+/// corresponding service: it is not spawned, its snapshot part is ignored, and
+/// the order service talks to the supplied client instead.
+pub struct OrderSystemBuilder {
+    snapshot: SystemSnapshot,
+    restart: RestartPolicies,
+    config: SystemConfig,
+    product_shards: usize,
+    id_strategy: IdStrategy,
+    audit: bool,
+    user_client: Option<UserClient>,
+    product_client: Option<ProductClient>,
+}
+
+impl Default for OrderSystemBuilder {
+    fn default() -> Self {
+        Self {
+            snapshot: SystemSnapshot::default(),
+            restart: RestartPolicies::default(),
+            config: SystemConfig::default(),
+            product_shards: 1,
+            id_strategy: IdStrategy::default(),
+            audit: false,
+            user_client: None,
+            product_client: None,
+        }
+    }
+}
+
+impl OrderSystemBuilder {
+    /// Pre-load every service from `snapshot`
+    pub fn snapshot(mut self, snapshot: SystemSnapshot) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+
+    pub fn restart_policies(mut self, restart: RestartPolicies) -> Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Replace all actor settings; the per-actor setters below adjust this
+    pub fn config(mut self, config: SystemConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn user_buffer(mut self, size: usize) -> Self {
+        self.config.users.mailbox_size = Some(size);
+        self
+    }
+
+    pub fn product_buffer(mut self, size: usize) -> Self {
+        self.config.products.mailbox_size = Some(size);
+        self
+    }
+
+    /// Spread products over `shards` services; see [`ShardedProductService`]
+    pub fn product_shards(mut self, shards: usize) -> Self {
+        self.product_shards = shards;
+        self
+    }
+
+    pub fn order_buffer(mut self, size: usize) -> Self {
+        self.config.orders.mailbox_size = Some(size);
+        self
+    }
+
+    pub fn id_strategy(mut self, id_strategy: IdStrategy) -> Self {
+        self.id_strategy = id_strategy;
+        self
+    }
+
+    /// Start an [`AuditService`] and have every service record its mutations there
+    pub fn audit_log(mut self, enabled: bool) -> Self {
+        self.audit = enabled;
+        self
+    }
+
+    /// Default per-call timeout for every actor and the clients the system hands out
+    pub fn sub_actor_timeout(mut self, timeout: Duration) -> Self {
+        self.config.defaults.call_timeout = timeout;
+        self
+    }
+
+    /// Use `client` instead of spawning a `UserService`
+    pub fn user_client(mut self, client: UserClient) -> Self {
+        self.user_client = Some(client);
+        self
+    }
+
+    /// Use `client` instead of spawning a `ProductService`
+    pub fn product_client(mut self, client: ProductClient) -> Self {
+        self.product_client = Some(client);
+        self
+    }
+
+    /// Like [`OrderSystemBuilder::try_build`], panicking on an invalid config
+    pub fn build(self) -> OrderSystem {
+        self.try_build()
+            .unwrap_or_else(|e| panic!("Invalid OrderSystem config: {}", e))
+    }
+
+    /// Like [`OrderSystemBuilder::try_build`], but only returns once every
+    /// actor has answered a `Ping`, i.e. its run loop is taking requests.
+    ///
+    /// `try_build` hands back clients while the actors may still be starting;
+    /// requests simply queue until they are. Use `start` when a service that
+    /// cannot come up should fail startup instead. On failure every spawned
+    /// task is aborted.
+    #[instrument(name = "order_system_start", skip(self))]
+    pub async fn start(self) -> Result<OrderSystem, StartupError> {
+        let config = self.config.clone();
+        let system = self.try_build()?;
+        let actors: [(&'static str, &ActorOverrides, &dyn HealthProbe); 3] = [
+            ("users", &config.users, &system.user_client),
+            ("products", &config.products, &system.product_client),
+            ("orders", &config.orders, &system.order_client),
+        ];
+        for (actor, overrides, probe) in actors {
+            let timeout = config.resolve(overrides).call_timeout;
+            let reason = match tokio::time::timeout(timeout, probe.probe()).await {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => e,
+                Err(_) => format!("not ready after {:?}", timeout),
+            };
+            error!(actor, %reason, "Actor failed to start");
+            for handle in system.abort_handles() {
+                handle.abort();
+            }
+            return Err(StartupError::ActorNotReady { actor, reason });
+        }
+        info!("Every actor ready");
+        Ok(system)
+    }
+
+    /// Validate the config, then create and start the system. Must run inside
+    /// a tokio runtime.
+    ///
+    /// **Startup Order:**
+    /// 1. Start the event bus (every other actor publishes to it)
+    /// 2. Start sub-actors (UserService, ProductService)
+    /// 3. Start root actors (OrderService) with sub-actor clients
+    /// 4. Return coordinator with all clients for external use, each bounded by
+    ///    its actor's `call_timeout` (clients passed to the builder are kept as is)
+    #[instrument(name = "order_system", skip(self))]
+    pub fn try_build(self) -> Result<OrderSystem, ConfigError> {
+        let Self {
+            snapshot,
+            restart,
+            config,
+            product_shards,
+            id_strategy,
+            audit,
+            user_client,
+            product_client,
+        } = self;
+        config.validate()?;
+        let user_config = config.resolve(&config.users);
+        let product_config = config.resolve(&config.products);
+        let user_buffer = user_config.mailbox_size;
+        let product_buffer = product_config.mailbox_size;
+        let order_config = config.resolve(&config.orders);
+        let order_buffer = order_config.mailbox_size;
+        let mut handles = Vec::new();
+
+        info!("Starting order system");
+
+        // Start the event bus before anything that publishes to it
+        let (event_bus_service, event_bus) = EventBusService::new(100, 256);
+        handles.push(tokio::spawn(event_bus_service.run()));
+        let faults = DemoFaults::default();
+        let audit = audit.then(|| {
+            let (audit_service, audit) = AuditService::new(100);
+            let audit_service = audit_service
+                .with_entity_retention("User", user_config.audit_retention)
+                .with_entity_retention("Product", product_config.audit_retention)
+                .with_entity_retention("Stock", product_config.audit_retention)
+                .with_entity_retention("Order", order_config.audit_retention);
+            handles.push(tokio::spawn(audit_service.run()));
+            audit
+        });
+
+        // Start sub-actors first (no dependencies). Each runs under a supervisor
+        // that respawns it after a panic; only the first incarnation gets the
+        // snapshot, a restarted actor starts empty.
+        let user_client = user_client.unwrap_or_else(|| {
+            let mut users = Some(snapshot.users);
+            let bus = event_bus.clone();
+            let injector = faults.users.clone();
+            let audit = audit.clone();
+            let (user_supervisor, user_sender) =
+                Supervisor::new("user_service", user_buffer, move || {
+                    let (user_service, user_client) = UserService::new(user_buffer);
+                    let mut user_service = user_service
+                        .restore(users.take().unwrap_or_default())
+                        .with_event_bus(bus.clone())
+                        .with_faults(injector.clone())
+                        .with_slow_handler(user_config.slow_handler)
+                        .with_batch_size(user_config.batch_size)
+                        .with_id_strategy(id_strategy);
+                    if let Some(audit) = &audit {
+                        user_service = user_service.with_audit(audit.clone());
+                    }
+                    if let Some(threshold) = user_config.shed_threshold {
+                        user_service = user_service.with_load_shedding(threshold);
+                    }
+                    (user_client.sender, tokio::spawn(user_service.run()))
+                });
+            let user_supervisor = user_supervisor.with_restart_policy(restart.users);
+            handles.push(tokio::spawn(user_supervisor.run()));
+            UserClient::new(user_sender).with_timeout(user_config.call_timeout)
+        });
+
+        let product_client = product_client.unwrap_or_else(|| {
+            let mut products = Some(snapshot.products);
+            let bus = event_bus.clone();
+            let injector = faults.products.clone();
+            let audit = audit.clone();
+            let (product_supervisor, product_sender) =
+                Supervisor::new("product_service", product_buffer, move || {
+                    let snapshot = products.take().unwrap_or_default();
+                    if product_shards > 1 {
+                        let (product_service, product_client) =
+                            ShardedProductService::new(product_shards, product_buffer);
+                        let mut product_service = product_service
+                            .restore(snapshot)
+                            .with_event_bus(bus.clone())
+                            .with_faults(injector.clone())
+                            .with_slow_handler(product_config.slow_handler)
+                            .with_batch_size(product_config.batch_size)
+                            .with_tick_interval(product_config.tick_interval);
+                        if let Some(audit) = &audit {
+                            product_service = product_service.with_audit(audit.clone());
+                        }
+                        if let Some(threshold) = product_config.shed_threshold {
+                            product_service = product_service.with_load_shedding(threshold);
+                        }
+                        return (product_client.sender, tokio::spawn(product_service.run()));
+                    }
+                    let (product_service, product_client) = ProductService::new(product_buffer);
+                    let mut product_service = product_service
+                        .restore(snapshot)
+                        .with_event_bus(bus.clone())
+                        .with_faults(injector.clone())
+                        .with_slow_handler(product_config.slow_handler)
+                        .with_batch_size(product_config.batch_size)
+                        .with_tick_interval(product_config.tick_interval);
+                    if let Some(audit) = &audit {
+                        product_service = product_service.with_audit(audit.clone());
+                    }
+                    if let Some(threshold) = product_config.shed_threshold {
+                        product_service = product_service.with_load_shedding(threshold);
+                    }
+                    (product_client.sender, tokio::spawn(product_service.run()))
+                });
+            let product_supervisor = product_supervisor.with_restart_policy(restart.products);
+            handles.push(tokio::spawn(product_supervisor.run()));
+            ProductClient::new(product_sender).with_timeout(product_config.call_timeout)
+        });
+
+        // Start root actor with sub-actor clients (dependency injection)
+        // A stuck or dead sub-actor must not stall order processing forever
+        let order_user_client = user_client
+            .clone()
+            .with_timeout(user_config.call_timeout)
+            .with_circuit_breaker(CircuitBreakerConfig::default());
+        let order_product_client = product_client
+            .clone()
+            .with_timeout(product_config.call_timeout)
+            .with_circuit_breaker(CircuitBreakerConfig::default());
+        let mut orders = Some(snapshot.orders);
+        let bus = event_bus.clone();
+        let injector = faults.orders.clone();
+        let order_audit = audit.clone();
+        let (order_supervisor, order_sender) =
+            Supervisor::new("order_service", order_buffer, move || {
+                let (order_service, order_client) = OrderService::new(
+                    order_buffer,
+                    order_user_client.clone(),
+                    order_product_client.clone(),
+                );
+                let mut order_service = order_service
+                    .restore(orders.take().unwrap_or_default())
+                    .with_event_bus(bus.clone())
+                    .with_faults(injector.clone())
+                    .with_slow_handler(order_config.slow_handler)
+                    .with_batch_size(order_config.batch_size);
+                if let Some(audit) = &order_audit {
+                    order_service = order_service.with_audit(audit.clone());
+                }
+                if let Some(threshold) = order_config.shed_threshold {
+                    order_service = order_service.with_load_shedding(threshold);
+                }
+                (order_client.sender, tokio::spawn(order_service.run()))
+            });
+        let order_supervisor = order_supervisor.with_restart_policy(restart.orders);
+        handles.push(tokio::spawn(order_supervisor.run()));
+        let order_client = OrderClient::new(order_sender).with_timeout(order_config.call_timeout);
+
+        info!("Order system started successfully");
+
+        Ok(OrderSystem {
+            order_client,
+            user_client,
+            product_client,
+            event_bus,
+            audit,
+            faults,
+            handles,
+        })
+    }
+}
+
+/// Runs the application until it finishes or the process is told to stop,
+/// then shuts the [`OrderSystem`] down within a grace period.
+///
+/// **Pattern:** The graceful path is [`OrderSystem::shutdown`], which stops
+/// actors in dependency order and lets each drain its mailbox. If that takes
+/// longer than the grace period the remaining tasks are aborted, so a stuck
+/// handler can delay exit but never block it.
+#[derive(Debug, Clone)]
+pub struct ShutdownController {
+    grace_period: Duration,
+}
+
+impl ShutdownController {
+    pub fn new(grace_period: Duration) -> Self {
+        Self { grace_period }
+    }
+
+    /// Resolves on ctrl-c, or SIGTERM on Unix
+    pub async fn wait_for_signal() {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminate) => {
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => info!("Received ctrl-c"),
+                        _ = terminate.recv() => info!("Received SIGTERM"),
+                    }
+                    return;
+                }
+                Err(e) => warn!(error = %e, "Cannot listen for SIGTERM, ctrl-c only"),
+            }
+        }
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => info!("Received ctrl-c"),
+            // Without a signal handler the only way out is the work finishing
+            Err(e) => {
+                error!(error = %e, "Cannot listen for ctrl-c");
+                std::future::pending::<()>().await
+            }
+        }
+    }
+
+    /// Drive `work` to completion; `None` if a signal arrived first
+    pub async fn run_until_signal<T>(
+        &self,
+        work: impl std::future::Future<Output = T>,
+    ) -> Option<T> {
+        tokio::select! {
+            output = work => Some(output),
+            _ = Self::wait_for_signal() => None,
+        }
+    }
+
+    #[instrument(
+        name = "shutdown_controller",
+        skip(self, system),
+        fields(grace_ms = self.grace_period.as_millis()),
+    )]
+    pub async fn shutdown(&self, system: OrderSystem) -> Result<(), String> {
+        let stragglers = system.abort_handles();
+        match tokio::time::timeout(self.grace_period, system.shutdown()).await {
+            Ok(result) => result,
+            Err(_) => {
+                let remaining = stragglers.iter().filter(|task| !task.is_finished()).count();
+                error!(remaining, "Grace period elapsed, aborting remaining tasks");
+                for task in stragglers {
+                    task.abort();
+                }
+                Err(format!(
+                    "Shutdown exceeded grace period of {}ms, {} tasks aborted",
+                    self.grace_period.as_millis(),
+                    remaining
+                ))
+            }
+        }
+    }
+}
+
+// =============================================================================
+// INGREDIENT 9: TRACING SETUP
+// =============================================================================
+
+/// Stable codes for significant events and errors, for alert rules and runbooks.
+///
+/// **Pattern:** Log lines carry the code as a `code` field and every domain
+/// error maps to one via `code()`, so an alert keyed on `ORD-001` keeps working
+/// when a message is reworded. Codes are never reused or renumbered; retire a
+/// variant instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventCode {
+    /// `USR-001` - User not found
+    UserNotFound,
+    /// `USR-002` - User ID already taken
+    UserAlreadyExists,
+    /// `USR-003` - User payload rejected
+    UserValidationFailed,
+    /// `USR-004` - User invariant violated, change rolled back
+    UserInvariantViolated,
+    /// `USR-005` - Conditional user update found an unexpected state
+    UserPreconditionFailed,
+    /// `USR-006` - User storage failure
+    UserStoreFailed,
+    /// `PRD-001` - Product not found
+    ProductNotFound,
+    /// `PRD-002` - Stock reservation exceeds stock on hand
+    InsufficientStock,
+    /// `PRD-003` - Quantity out of range
+    InvalidQuantity,
+    /// `PRD-004` - Product payload rejected
+    ProductValidationFailed,
+    /// `PRD-005` - Product storage failure
+    ProductStoreFailed,
+    /// `PRD-006` - Stock hold unknown, already confirmed or expired
+    HoldNotFound,
+    /// `PRD-007` - Stock hold expired and its units were returned
+    HoldExpired,
+    /// `ORD-001` - Order could not reserve stock
+    StockReservationFailed,
+    /// `ORD-002` - Order references an unknown or unreachable user
+    OrderUserInvalid,
+    /// `ORD-003` - Order references an unknown or unreachable product
+    OrderProductInvalid,
+    /// `ORD-004` - Order invariant violated, order rolled back
+    OrderInvariantViolated,
+    /// `ORD-005` - Order not found
+    OrderNotFound,
+    /// `ORD-006` - Order payload rejected
+    OrderValidationFailed,
+    /// `ORD-007` - Order storage failure
+    OrderStoreFailed,
+    /// `ORD-008` - Order ID already taken
+    OrderAlreadyExists,
+    /// `SYS-001` - Write refused while the actor drains
+    ActorDraining,
+    /// `SYS-002` - Handler panicked, its caller was answered with an error
+    HandlerPanicked,
+    /// `SYS-003` - Supervisor restarted a panicked actor
+    ActorRestarted,
+    /// `SYS-004` - Supervisor gave up on a panicked actor
+    RestartsExhausted,
+    /// `SYS-005` - Undeliverable request parked in the dead letter queue
+    DeadLetterRecorded,
+    /// `SYS-006` - Invariant spanning several actors found violated
+    ConsistencyViolation,
+    /// `SYS-007` - Handler exceeded its actor's slow-handler threshold
+    SlowHandler,
+    /// `SYS-008` - Mailbox backed up past the shedding threshold, requests refused
+    ActorOverloaded,
+    /// `SYS-009` - Caller went away, long-running work abandoned
+    RequestCancelled,
+    /// `SYS-010` - Outbox full, a mutation refused until the relay catches up
+    OutboxOverflow,
+}
+
+impl EventCode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            EventCode::UserNotFound => "USR-001",
+            EventCode::UserAlreadyExists => "USR-002",
+            EventCode::UserValidationFailed => "USR-003",
+            EventCode::UserInvariantViolated => "USR-004",
+            EventCode::UserPreconditionFailed => "USR-005",
+            EventCode::UserStoreFailed => "USR-006",
+            EventCode::ProductNotFound => "PRD-001",
+            EventCode::InsufficientStock => "PRD-002",
+            EventCode::InvalidQuantity => "PRD-003",
+            EventCode::ProductValidationFailed => "PRD-004",
+            EventCode::ProductStoreFailed => "PRD-005",
+            EventCode::HoldNotFound => "PRD-006",
+            EventCode::HoldExpired => "PRD-007",
+            EventCode::StockReservationFailed => "ORD-001",
+            EventCode::OrderUserInvalid => "ORD-002",
+            EventCode::OrderProductInvalid => "ORD-003",
+            EventCode::OrderInvariantViolated => "ORD-004",
+            EventCode::OrderNotFound => "ORD-005",
+            EventCode::OrderValidationFailed => "ORD-006",
+            EventCode::OrderStoreFailed => "ORD-007",
+            EventCode::OrderAlreadyExists => "ORD-008",
+            EventCode::ActorDraining => "SYS-001",
+            EventCode::HandlerPanicked => "SYS-002",
+            EventCode::ActorRestarted => "SYS-003",
+            EventCode::RestartsExhausted => "SYS-004",
+            EventCode::DeadLetterRecorded => "SYS-005",
+            EventCode::ConsistencyViolation => "SYS-006",
+            EventCode::SlowHandler => "SYS-007",
+            EventCode::ActorOverloaded => "SYS-008",
+            EventCode::RequestCancelled => "SYS-009",
+            EventCode::OutboxOverflow => "SYS-010",
+        }
+    }
+}
+
+impl std::fmt::Display for EventCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl UserError {
+    pub fn code(&self) -> EventCode {
+        match self {
+            UserError::NotFound(_) => EventCode::UserNotFound,
+            UserError::AlreadyExists(_) => EventCode::UserAlreadyExists,
+            UserError::ValidationError(_) | UserError::InvalidFields(_) => {
+                EventCode::UserValidationFailed
+            }
+            UserError::DatabaseError(_) => EventCode::UserStoreFailed,
+            UserError::PreconditionFailed(_) => EventCode::UserPreconditionFailed,
+            UserError::Draining => EventCode::ActorDraining,
+            UserError::Overloaded => EventCode::ActorOverloaded,
+            UserError::Cancelled => EventCode::RequestCancelled,
+            UserError::HandlerPanicked => EventCode::HandlerPanicked,
+        }
+    }
+}
+
+impl ProductError {
+    pub fn code(&self) -> EventCode {
+        match self {
+            ProductError::NotFound(_) => EventCode::ProductNotFound,
+            ProductError::InsufficientStock { .. } => EventCode::InsufficientStock,
+            ProductError::InvalidQuantity(_) | ProductError::NotReserved { .. } => {
+                EventCode::InvalidQuantity
+            }
+            ProductError::ValidationError(_) | ProductError::InvalidFields(_) => {
+                EventCode::ProductValidationFailed
+            }
+            ProductError::DatabaseError(_) => EventCode::ProductStoreFailed,
+            ProductError::Draining => EventCode::ActorDraining,
+            ProductError::Overloaded => EventCode::ActorOverloaded,
+            ProductError::HoldNotFound(_) => EventCode::HoldNotFound,
+            ProductError::HandlerPanicked => EventCode::HandlerPanicked,
+        }
+    }
+}
+
+impl OrderError {
+    pub fn code(&self) -> EventCode {
+        match self {
+            OrderError::NotFound(_) => EventCode::OrderNotFound,
+            OrderError::InvalidProduct(_) => EventCode::OrderProductInvalid,
+            OrderError::InvalidUser(_) => EventCode::OrderUserInvalid,
+            OrderError::InsufficientStock(_) => EventCode::StockReservationFailed,
+            OrderError::ValidationError(_) | OrderError::InvalidFields(_) => {
+                EventCode::OrderValidationFailed
+            }
+            OrderError::DatabaseError(_) => EventCode::OrderStoreFailed,
+            OrderError::Draining => EventCode::ActorDraining,
+            OrderError::Overloaded => EventCode::ActorOverloaded,
+            OrderError::AlreadyExists(_) => EventCode::OrderAlreadyExists,
+            OrderError::HandlerPanicked => EventCode::HandlerPanicked,
+        }
+    }
+}
+
+/// Tracing targets, one per actor type. `#[instrument]` needs a literal, so
+/// spans repeat these strings; keep the two in sync.
+const USER_TARGET: &str = "actor::user";
+const PRODUCT_TARGET: &str = "actor::product";
+const ORDER_TARGET: &str = "actor::order";
+const EVENT_BUS_TARGET: &str = "actor::event_bus";
+const SUPERVISOR_TARGET: &str = "actor::supervisor";
+const DEAD_LETTERS_TARGET: &str = "actor::dead_letters";
+const REGISTRY_TARGET: &str = "actor::registry";
+const POOL_TARGET: &str = "actor::pool";
+const MAILBOX_TARGET: &str = "actor::mailbox";
+const METRICS_TARGET: &str = "actor::metrics";
+const CONSISTENCY_TARGET: &str = "actor::consistency";
+const HEALTH_TARGET: &str = "actor::health";
+const AUDIT_TARGET: &str = "actor::audit";
+const SCHEDULER_TARGET: &str = "actor::scheduler";
+const JOB_TARGET: &str = "actor::jobs";
+const SAGA_TARGET: &str = "actor::saga";
+const TRANSACTION_TARGET: &str = "actor::transaction";
+
+/// ## Ingredient 9: Production-Ready Tracing Setup
+///
+/// **Pattern:** Configure tracing once at application startup for the entire process.
+/// All actors and spans automatically use this configuration.
+///
+/// **Key Features:**
+/// - **Environment-based filtering** - Use `RUST_LOG` env var to control verbosity
+/// - **Built-in timing** - See how long each operation takes
+/// - **Structured output** - Easy to parse and search logs
+/// - **Compact format** - Readable but not verbose
+///
+/// **Usage:**
+/// ```bash
+/// RUST_LOG=debug cargo run    # Show debug logs
+/// RUST_LOG=info cargo run     # Show info logs only  
+/// RUST_LOG=warn cargo run     # Show warnings and errors only
+///
+/// # Every actor logs under its own target, so one can be turned up alone:
+/// RUST_LOG=info,actor::product=debug cargo run
+/// RUST_LOG=warn,actor=info cargo run   # all actors, nothing else
+/// LOG_FORMAT=pretty cargo run          # see TracingConfig::from_env
+/// ```
+fn setup_tracing() {
+    TracingConfig::from_env().init();
+}
+
+/// How log lines are laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One short line per event, span context abbreviated
+    #[default]
+    Compact,
+    /// One line per event with full span context
+    Full,
+    /// Multi-line, for reading a single flow by eye
+    Pretty,
+}
+
+/// Everything [`setup_tracing`] configures, for applications that want to
+/// choose rather than take the defaults
+#[derive(Debug, Clone)]
+pub struct TracingConfig {
+    /// Filter used when `RUST_LOG` is unset
+    pub default_filter: String,
+    pub format: LogFormat,
+    /// Colour output; turn off when logs go to a file or collector
+    pub ansi: bool,
+    /// Also log each span as it closes, with its busy and idle time
+    pub span_timing: bool,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            default_filter: "info".to_string(),
+            format: LogFormat::default(),
+            ansi: true,
+            span_timing: false,
+        }
+    }
+}
+
+impl TracingConfig {
+    /// Defaults, adjusted by `LOG_FORMAT` (compact, full or pretty),
+    /// `LOG_ANSI=0` and `LOG_SPAN_TIMING=1`
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        match std::env::var("LOG_FORMAT").as_deref() {
+            Ok("full") => config.format = LogFormat::Full,
+            Ok("pretty") => config.format = LogFormat::Pretty,
+            _ => {}
+        }
+        if std::env::var("LOG_ANSI").as_deref() == Ok("0") {
+            config.ansi = false;
+        }
+        if std::env::var("LOG_SPAN_TIMING").as_deref() == Ok("1") {
+            config.span_timing = true;
+        }
+        config
+    }
+
+    /// Install as the global subscriber. Panics if one is already set.
+    pub fn init(self) {
+        use tracing_subscriber::fmt::format::FmtSpan;
+
+        let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(&self.default_filter));
+        let span_events = if self.span_timing {
+            FmtSpan::CLOSE
+        } else {
+            FmtSpan::NONE
+        };
+        let builder = tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
+            .with_timer(tracing_subscriber::fmt::time::uptime())
+            .with_ansi(self.ansi)
+            .with_span_events(span_events);
+
+        // Each format is its own subscriber type, hence one `init` per arm
+        match self.format {
+            LogFormat::Compact => builder.compact().init(),
+            LogFormat::Full => builder.init(),
+            LogFormat::Pretty => builder.pretty().init(),
+        }
+    }
+}
+
+// =============================================================================
+// INGREDIENT 10: HANDLER PATTERNS
+// =============================================================================
+
+/// ## Ingredient 10: Advanced Handler Patterns
+///
+/// Beyond basic request-response, actors often need to handle different types of operations:
+///
+/// ### Sync vs Async Handlers
+///
+/// **Sync Handlers** (fast, in-memory):
+/// ```rust
+/// fn handle_get_user_sync(&self, id: String, respond_to: oneshot::Sender<...>) {
+///     let result = self.users.get(&id).cloned(); // No await!
+///     let _ = respond_to.send(Ok(result));
+/// }
+/// ```
+///
+/// **Async Handlers** (I/O, validation):
+/// ```rust
+/// async fn handle_create_user_async(&mut self, user: User, respond_to: oneshot::Sender<...>) {
+///     // Async email validation
+///     validate_email_externally(&user.email).await?;
+///     let id = self.create_user_internal(user);
+///     let _ = respond_to.send(Ok(id));
+/// }
+/// ```
+///
+/// ### Background Operations
+///
+/// **Return Immediately, Work Continues:**
+/// ```rust
+/// fn handle_send_email_background(&self, user_id: String, respond_to: oneshot::Sender<...>) {
+///     // Return success immediately
+///     let _ = respond_to.send(Ok(()));
+///     
+///     // Spawn background work
+///     tokio::spawn(async move {
+///         send_welcome_email(user_id).await;
+///     });
+/// }
+/// ```
+///
+/// **Return Job ID, Work Continues:**
+/// ```rust
+/// fn handle_generate_report(&self, user_id: String, respond_to: oneshot::Sender<...>) {
+///     let job_id = generate_job_id();
+///     let _ = respond_to.send(Ok(job_id.clone()));
+///     
+///     tokio::spawn(async move {
+///         let report = generate_report(user_id).await;
+///         save_report(job_id, report).await;
+///     });
+/// }
+/// ```
+///
+/// ### When to Use Each Pattern
+///
+/// - **Sync**: Fast lookups, in-memory operations, simple computations
+/// - **Async**: Database calls, external APIs, file I/O, complex validation
+/// - **Background**: Email sending, report generation, cleanup tasks, analytics
+///
+/// Example of a background operation that returns immediately and continues work
+impl UserService {
+    /// **Background Handler Example** - Task owns the response channel
+    ///
+    /// This pattern shows how the spawned task can take ownership of respond_to
+    /// and send the response after the work completes.
+    #[instrument(target = "actor::user", fields(user_id = %user_id), skip(self, respond_to))]
+    pub async fn handle_send_welcome_email_background(
+        &self,
+        user_id: String,
+        respond_to: ServiceResponse<(), UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing send_welcome_email request");
+
+        // Spawn background task - it takes ownership of respond_to
+        tokio::spawn(async move {
+            info!(target: USER_TARGET, user_id = %user_id, "Starting background email send");
+
+            // Simulate slow email sending
+            tokio::time::sleep(Duration::from_millis(500)).await;
+
+            // Simulate email service call
+            let success = true; // In real code, this would be an actual email API call
+
+            let result = if success {
+                info!(target: USER_TARGET, user_id = %user_id, "Welcome email sent successfully");
+                Ok(())
+            } else {
+                error!(target: USER_TARGET, user_id = %user_id, "Failed to send welcome email");
+                Err(UserError::DatabaseError("Email service failed".to_string()))
+            };
+
+            // Task responds when work is actually done
+            let _ = respond_to.send(result);
+        });
+    }
+
+    /// **Alternative Background Pattern** - Return job ID immediately
+    ///
+    /// Shows another way: return a job ID immediately, do work in background.
+    /// Nothing here records the job's outcome; submit the work to a
+    /// [`JobService`] when the caller needs to check its status later.
+    #[instrument(target = "actor::user", fields(user_id = %user_id), skip(self, respond_to))]
+    pub async fn handle_generate_report_background(
+        &self,
+        user_id: String,
+        respond_to: ServiceResponse<String, UserError>,
+    ) {
+        debug!(target: USER_TARGET, "Processing generate_report request");
+
+        // Generate a job ID and return it immediately
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let job_id = format!("job_{}_{}", user_id, timestamp);
+
+        info!(target: USER_TARGET, job_id = %job_id, "Report generation started");
+        let _ = respond_to.send(Ok(job_id.clone()));
+
+        // Spawn background task for the actual report generation
+        let user_data = self.users.get(&user_id).cloned();
+
+        tokio::spawn(async move {
+            info!(target: USER_TARGET, job_id = %job_id, "Starting background report generation");
+
+            // Simulate slow report generation
+            tokio::time::sleep(Duration::from_millis(2000)).await;
+
+            match user_data {
+                Some(user) => {
+                    info!(
+                        target: USER_TARGET,
+                        job_id = %job_id,
+                        user_name = %user.name,
+                        "Report generated successfully"
+                    );
+                    // In real code, you would save the report somewhere
+                    // and maybe notify the user that it's ready
+                }
+                None => {
+                    error!(
+                        target: USER_TARGET,
+                        job_id = %job_id,
+                        "Cannot generate report: user not found"
+                    );
+                }
+            }
+        });
+    }
+}
+
+/// Answer `respond_to` with the result of CPU-heavy `work`, run on tokio's
+/// blocking pool so the actor goes straight back to its mailbox.
+///
+/// `work` must own what it reads (clone or snapshot it first), which also
+/// pins the answer to the state as of this message. A panic in `work` drops
+/// the response, which then answers with the service's `HandlerPanicked`
+/// error, as for any handler.
+fn respond_blocking<T, E, F>(respond_to: ServiceResponse<T, E>, work: F)
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+{
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || {
+        let _entered = span.enter();
+        let _ = respond_to.send(work());
+    });
+}
+
+/// Sections of the demo report, each standing in for a slow query
+const REPORT_SECTIONS: [&str; 4] = ["profile", "orders", "activity", "summary"];
+
+impl UserService {
+    /// **Blocking Handler** - CPU-bound work offloaded with [`respond_blocking`]
+    ///
+    /// Hashing the whole store would hold up every message queued behind it,
+    /// and would stall the runtime thread too. The handler only copies the
+    /// users; the hashing runs on the blocking pool and replies when done.
+    #[instrument(target = "actor::user", fields(users = self.users.len()), skip_all)]
+    fn handle_checksum(&self, respond_to: ServiceResponse<u64, UserError>) {
+        let mut users: Vec<User> = self.users.values().cloned().collect();
+        respond_blocking(respond_to, move || {
+            use std::hash::{Hash, Hasher};
+            users.sort_unstable_by(|a, b| a.id.cmp(&b.id));
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            for user in &users {
+                (&user.id, &user.name, &user.email).hash(&mut hasher);
+            }
+            Ok(hasher.finish())
+        });
+    }
+
+    /// **Cancellable Handler** - Long-running work that stops when the caller leaves
+    ///
+    /// The report is built on its own task with a [`CancellationToken`] that it
+    /// checks between sections. If the caller drops the call (a [`CallHandle`]
+    /// is cancelled, a client timeout fires, an HTTP request is aborted), the
+    /// closed reply channel cancels the token and the work stops at the next
+    /// check instead of running to completion for nobody.
+    #[instrument(target = "actor::user", fields(user_id = %user_id), skip(self, respond_to))]
+    fn handle_generate_report(
+        &self,
+        user_id: String,
+        mut respond_to: ServiceResponse<String, UserError>,
+    ) {
+        let Some(user) = self.users.get(&user_id).cloned() else {
+            send_error!(respond_to, UserError::NotFound(user_id));
+        };
+
+        tokio::spawn(
+            async move {
+                let token = CancellationToken::new();
+                let report = build_report(user, token.clone(), None);
+                tokio::pin!(report);
+                let result = tokio::select! {
+                    result = &mut report => result,
+                    _ = respond_to.closed() => {
+                        token.cancel();
+                        report.await
+                    }
+                };
+                if let Err(UserError::Cancelled) = result {
+                    info!(
+                        target: USER_TARGET,
+                        code = %EventCode::RequestCancelled,
+                        "Caller went away, report abandoned"
+                    );
+                }
+                let _ = respond_to.send(result);
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// **Streaming Handler** - Progress updates, then the result, on one channel
+    ///
+    /// Instead of "return a job ID and hope", the caller watches the work
+    /// advance and gets the outcome as the final [`Progress::Done`] message.
+    /// Dropping the receiver cancels the work, as a dropped call does for
+    /// [`handle_generate_report`](Self::handle_generate_report).
+    #[instrument(target = "actor::user", fields(user_id = %user_id), skip(self, updates))]
+    fn handle_generate_report_streaming(
+        &self,
+        user_id: String,
+        updates: mpsc::Sender<Progress<String>>,
+    ) {
+        let Some(user) = self.users.get(&user_id).cloned() else {
+            let error = UserError::NotFound(user_id).to_string();
+            let _ = updates.try_send(Progress::Done(Err(error)));
+            return;
+        };
+
+        tokio::spawn(
+            async move {
+                let token = CancellationToken::new();
+                let report = build_report(user, token.clone(), Some(updates.clone()));
+                tokio::pin!(report);
+                let result = tokio::select! {
+                    result = &mut report => result,
+                    _ = updates.closed() => {
+                        token.cancel();
+                        report.await
+                    }
+                };
+                if let Err(UserError::Cancelled) = result {
+                    info!(
+                        target: USER_TARGET,
+                        code = %EventCode::RequestCancelled,
+                        "Progress receiver dropped, report abandoned"
+                    );
+                }
+                let _ = updates
+                    .send(Progress::Done(result.map_err(|e| e.to_string())))
+                    .await;
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Build the report section by section, announcing each one on `progress` if given
+async fn build_report(
+    user: User,
+    token: CancellationToken,
+    progress: Option<mpsc::Sender<Progress<String>>>,
+) -> Result<String, UserError> {
+    let mut report = format!("Report for {} <{}>", user.name, user.email);
+    for (done, section) in REPORT_SECTIONS.into_iter().enumerate() {
+        if token.is_cancelled() {
+            return Err(UserError::Cancelled);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        report.push_str(&format!("\n- {}: ok", section));
+        debug!(target: USER_TARGET, section, "Report section built");
+        if let Some(progress) = &progress {
+            let step = Progress::Step {
+                done: done + 1,
+                total: REPORT_SECTIONS.len(),
+                stage: section.to_string(),
+            };
+            let _ = progress.send(step).await;
+        }
+    }
+    Ok(report)
+}
+
+/// Example of concurrent monitoring for performance and blocking detection
+///
+/// **Pattern:** Register the periodic check as a recurring job on the
+/// [`SchedulerService`] rather than running a free-floating loop, so it stops
+/// with the scheduler or through the returned handle.
+/// This can be used for automated alerting or circuit breaker patterns.
+///
+/// **Blocking Detection:** Uses timeouts to detect when requests hang on the server:
+/// - Normal response: < 100ms (debug log)
+/// - Slow response: 100-500ms (warning - potential overload)
+/// - Timeout: > 500ms (error - likely blocked/hanging)
+pub async fn performance_monitor(
+    scheduler: &SchedulerClient,
+    user_client: UserClient,
+    interval: Duration,
+) -> Result<TimerHandle, String> {
+    scheduler
+        .recurring(Recurrence::Every(interval), move || {
+            let user_client = user_client.clone();
+            async move { check_health(&user_client).await }
+        })
+        .await
+}
+
+async fn check_health(user_client: &UserClient) {
+    let start = std::time::Instant::now();
+    let timeout = Duration::from_millis(500);
+
+    match tokio::time::timeout(timeout, user_client.exists("health_check".to_string())).await {
+        Ok(Ok(_)) => log_response_time(start.elapsed()),
+        Ok(Err(e)) => {
+            error!(error = %e, duration_ms = start.elapsed().as_millis(), "Health check failed")
+        }
+        Err(_) => error!(
+            timeout_ms = timeout.as_millis(),
+            "Health check timed out - server may be blocked/overloaded"
+        ),
+    }
+}
+
+fn log_response_time(duration: Duration) {
+    let duration_ms = duration.as_millis();
+    if duration > Duration::from_millis(100) {
+        warn!(
+            duration_ms,
+            "Health check slow but completed - potential server overload"
+        );
+    } else {
+        debug!(duration_ms, "Health check completed normally");
+    }
+}
+
+// =============================================================================
+// INGREDIENT 11: DOMAIN EVENT BUS
+// =============================================================================
+
+/// Lifecycle events published by the services after a successful mutation
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DomainEvent {
+    UserCreated {
+        user_id: String,
+    },
+    UserUpdated {
+        user_id: String,
+    },
+    StockReserved {
+        product_id: String,
+        quantity: u32,
+    },
+    /// Reserved units went back into stock, e.g. when an order failed
+    StockReleased {
+        product_id: String,
+        quantity: u32,
+    },
+    /// A stock hold ran out before it was confirmed; its units are back in stock
+    HoldExpired {
+        product_id: String,
+        hold: u64,
+        quantity: u32,
+    },
+    OrderCreated {
+        order_id: String,
+        user_id: String,
+        product_id: String,
+        quantity: u32,
+    },
+}
+
+impl DomainEvent {
+    /// The product an event is about, if it names one
+    fn product_id(&self) -> Option<&str> {
+        match self {
+            DomainEvent::StockReserved { product_id, .. }
+            | DomainEvent::StockReleased { product_id, .. }
+            | DomainEvent::HoldExpired { product_id, .. }
+            | DomainEvent::OrderCreated { product_id, .. } => Some(product_id),
+            DomainEvent::UserCreated { .. } | DomainEvent::UserUpdated { .. } => None,
+        }
+    }
+}
+
+/// Schema version stamped on every [`DomainEvent`] encoded by [`EventCodec`].
+/// Bump it whenever a variant's shape changes, and register an upcaster for
+/// the old version.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// Rewrites a serialized event from one schema version to the next
+#[cfg(feature = "serde")]
+pub type Upcaster =
+    Box<dyn Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync>;
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct VersionedEvent {
+    version: u32,
+    event: serde_json::Value,
+}
+
+/// Serializes events for long-lived storage and reads old ones back.
+///
+/// **Pattern:** Events are written with the schema version they were created
+/// under. On read, registered upcasters migrate the raw JSON one version at a
+/// time until it matches today's [`DomainEvent`], so the enum can evolve
+/// without rewriting stored logs.
+///
+/// ```rust,ignore
+/// // Version 1 renamed `id` to `user_id`
+/// let codec = EventCodec::new().with_upcaster(0, |mut event| {
+///     let created = &mut event["UserCreated"];
+///     created["user_id"] = created["id"].take();
+///     Ok(event)
+/// });
+/// ```
+#[cfg(feature = "serde")]
+#[derive(Default)]
+pub struct EventCodec {
+    upcasters: BTreeMap<u32, Upcaster>,
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Debug for EventCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventCodec")
+            .field("upcasters", &self.upcasters.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl EventCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the migration from `from_version` to `from_version + 1`
+    pub fn with_upcaster<F>(mut self, from_version: u32, upcaster: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+    {
+        self.upcasters.insert(from_version, Box::new(upcaster));
+        self
+    }
+
+    pub fn encode(&self, event: &DomainEvent) -> Result<String, String> {
+        let versioned = VersionedEvent {
+            version: EVENT_SCHEMA_VERSION,
+            event: serde_json::to_value(event).map_err(|e| e.to_string())?,
+        };
+        serde_json::to_string(&versioned).map_err(|e| e.to_string())
+    }
+
+    /// Decode an event written under any version this codec can upcast from
+    pub fn decode(&self, json: &str) -> Result<DomainEvent, String> {
+        let VersionedEvent { version, event } =
+            serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if version > EVENT_SCHEMA_VERSION {
+            return Err(format!(
+                "Event version {} is newer than supported version {}",
+                version, EVENT_SCHEMA_VERSION
+            ));
+        }
+        let event = upcast(
+            &self.upcasters,
+            "event",
+            version,
+            EVENT_SCHEMA_VERSION,
+            event,
+        )?;
+        serde_json::from_value(event).map_err(|e| e.to_string())
+    }
+}
+
+/// Run `value` through the upcasters from `version` up to `current`
+#[cfg(feature = "serde")]
+fn upcast(
+    upcasters: &BTreeMap<u32, Upcaster>,
+    kind: &str,
+    version: u32,
+    current: u32,
+    mut value: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    for from in version..current {
+        let upcaster = upcasters
+            .get(&from)
+            .ok_or_else(|| format!("No upcaster from {} version {}", kind, from))?;
+        value = upcaster(value)?;
+    }
+    Ok(value)
+}
+
+/// Event bus error types
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventBusError {
+    Closed,
+}
+
+impl std::fmt::Display for EventBusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventBusError::Closed => write!(f, "Event bus closed"),
+        }
+    }
+}
+
+impl std::error::Error for EventBusError {}
+
+#[derive(Debug)]
+pub enum EventBusRequest {
+    Publish {
+        event: DomainEvent,
+    },
+    Subscribe {
+        respond_to: ServiceResponse<broadcast::Receiver<DomainEvent>, EventBusError>,
+    },
+    Shutdown,
+}
+
+/// ## Ingredient 11: Domain Event Bus
+///
+/// **Pattern:** Services announce *what happened* instead of calling every
+/// interested party directly. Publishers fire and forget; subscribers get a
+/// `broadcast` receiver and react at their own pace.
+///
+/// **Benefits:**
+/// - **Loose coupling** - OrderService doesn't know who cares about `OrderCreated`
+/// - **Easy extension** - New reactions are new subscribers, not new dependencies
+/// - **Lag tolerance** - Slow subscribers see `RecvError::Lagged` instead of
+///   blocking publishers
+pub struct EventBusService {
+    receiver: mpsc::Receiver<EventBusRequest>,
+    events: broadcast::Sender<DomainEvent>,
+}
+
+impl EventBusService {
+    pub fn new(buffer_size: usize, event_capacity: usize) -> (Self, EventBusClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let (events, _) = broadcast::channel(event_capacity);
+        let service = Self { receiver, events };
+        let client = EventBusClient::new(sender);
+        (service, client)
+    }
+
+    #[instrument(target = "actor::event_bus", name = "event_bus", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: EVENT_BUS_TARGET, "EventBus starting");
+
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                EventBusRequest::Publish { event } => {
+                    self.handle_publish(event);
+                }
+                EventBusRequest::Subscribe { respond_to } => {
+                    let _ = respond_to.send(Ok(self.events.subscribe()));
+                }
+                EventBusRequest::Shutdown => {
+                    info!(target: EVENT_BUS_TARGET, "EventBus shutting down");
+                    break;
+                }
+            }
+        }
+
+        info!(target: EVENT_BUS_TARGET, "EventBus stopped");
+    }
+
+    #[instrument(target = "actor::event_bus", skip(self))]
+    fn handle_publish(&self, event: DomainEvent) {
+        // An error only means nobody is subscribed right now
+        match self.events.send(event) {
+            Ok(subscribers) => debug!(target: EVENT_BUS_TARGET, subscribers, "Event published"),
+            Err(_) => debug!(target: EVENT_BUS_TARGET, "Event published with no subscribers"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventBusClient {
+    sender: mpsc::Sender<EventBusRequest>,
+    policy: ClientPolicy,
+}
+
+impl EventBusClient {
+    pub fn new(sender: mpsc::Sender<EventBusRequest>) -> Self {
+        Self {
+            sender,
+            policy: ClientPolicy::default(),
+        }
+    }
+
+    /// Fire-and-forget publish. A closed bus is logged, never surfaced to the
+    /// publisher, so a mutation never fails because of its notification.
+    #[instrument(skip(self))]
+    pub async fn publish(&self, event: DomainEvent) {
+        debug!("Publishing event");
+        if self.try_publish(event).await.is_err() {
+            warn!("Event bus unavailable, event dropped");
+        }
+    }
+
+    /// Publish, handing the event back if the bus is gone so the caller can keep it
+    pub async fn try_publish(&self, event: DomainEvent) -> Result<(), DomainEvent> {
+        match self.sender.reserve().await {
+            Ok(permit) => {
+                permit.send(EventBusRequest::Publish { event });
+                Ok(())
+            }
+            Err(_) => Err(event),
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        self.sender
+            .send(EventBusRequest::Shutdown)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+client_method!(EventBusClient => fn subscribe() -> broadcast::Receiver<DomainEvent> as EventBusRequest::Subscribe);
+
+// =============================================================================
+// INGREDIENT 12: STATE SNAPSHOTS
+// =============================================================================
+
+/// ## Ingredient 12: Snapshot and Restore
+///
+/// **Pattern:** A `Snapshot` message copies an actor's state out through the
+/// normal mailbox, so it is consistent with every request processed before it.
+/// Restoring happens *before* the actor is spawned, so no request can observe a
+/// half-loaded store.
+///
+/// With the `serde` feature, [`SystemSnapshot`] round-trips through a JSON file,
+/// which keeps local test data alive across `cargo run`s.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemSnapshot {
+    pub users: UserSnapshot,
+    pub products: ProductSnapshot,
+    pub orders: OrderSnapshot,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UserSnapshot {
+    pub users: HashMap<String, User>,
+    pub next_id: u64,
+}
+
+impl Default for UserSnapshot {
+    fn default() -> Self {
+        Self {
+            users: HashMap::new(),
+            next_id: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProductSnapshot {
+    pub products: HashMap<String, Product>,
+    pub stock: HashMap<String, u32>,
+    /// Units reserved and not released yet, per product
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reserved: HashMap<String, u32>,
+    /// Events of captured mutations that had not reached the bus yet
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub outbox: Outbox,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderSnapshot {
+    pub orders: HashMap<String, Order>,
+}
+
+/// Schema version stamped on snapshot files written by [`SystemSnapshot::save`].
+/// Files from before versioning have no stamp and read as version 0.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+
+/// Reads snapshot files written under older schemas, the same way
+/// [`EventCodec`] reads old events: registered upcasters migrate the raw JSON
+/// one version at a time before it is deserialized.
+#[cfg(feature = "serde")]
+pub struct SnapshotCodec {
+    upcasters: BTreeMap<u32, Upcaster>,
+}
+
+#[cfg(feature = "serde")]
+impl Default for SnapshotCodec {
+    fn default() -> Self {
+        let codec = Self {
+            upcasters: BTreeMap::new(),
+        };
+        // Version 1 only added the envelope; the snapshot itself is unchanged
+        codec.with_upcaster(0, Ok)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl std::fmt::Debug for SnapshotCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotCodec")
+            .field("upcasters", &self.upcasters.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SnapshotCodec {
+    /// A codec that can read every snapshot version shipped so far
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the migration from `from_version` to `from_version + 1`
+    pub fn with_upcaster<F>(mut self, from_version: u32, upcaster: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+    {
+        self.upcasters.insert(from_version, Box::new(upcaster));
+        self
+    }
+
+    pub fn encode(&self, snapshot: &SystemSnapshot) -> Result<String, String> {
+        let versioned = serde_json::json!({
+            "version": SNAPSHOT_SCHEMA_VERSION,
+            "snapshot": snapshot,
+        });
+        serde_json::to_string_pretty(&versioned).map_err(|e| e.to_string())
+    }
+
+    /// Decode a snapshot written under any version this codec can upcast from
+    pub fn decode(&self, json: &str) -> Result<SystemSnapshot, String> {
+        let mut value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let (version, snapshot) = match value.get("version").and_then(|v| v.as_u64()) {
+            Some(version) => {
+                let version = u32::try_from(version).map_err(|e| e.to_string())?;
+                (version, value["snapshot"].take())
+            }
+            None => (0, value),
+        };
+        if version > SNAPSHOT_SCHEMA_VERSION {
+            return Err(format!(
+                "Snapshot version {} is newer than supported version {}",
+                version, SNAPSHOT_SCHEMA_VERSION
+            ));
+        }
+        let snapshot = upcast(
+            &self.upcasters,
+            "snapshot",
+            version,
+            SNAPSHOT_SCHEMA_VERSION,
+            snapshot,
+        )?;
+        serde_json::from_value(snapshot).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl SystemSnapshot {
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), String> {
+        let json = SnapshotCodec::new().encode(self)?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        Self::load_with(path, &SnapshotCodec::new())
+    }
+
+    /// Load a file that may need migrations beyond the built-in ones
+    pub fn load_with(
+        path: impl AsRef<std::path::Path>,
+        codec: &SnapshotCodec,
+    ) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        codec.decode(&json)
+    }
+}
+
+/// JSON export/import on each client, for fixtures and API layers
+macro_rules! json_export_import {
+    ($client:ty => $snapshot:ty) => {
+        #[cfg(feature = "serde")]
+        impl $client {
+            /// Export the service's full state as JSON
+            pub async fn export_json(&self) -> Result<String, String> {
+                let snapshot = self.snapshot().await?;
+                serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())
+            }
+
+            /// Merge JSON produced by `export_json` into the service, returning the item count
+            pub async fn import_json(&self, json: &str) -> Result<usize, String> {
+                let snapshot: $snapshot = serde_json::from_str(json).map_err(|e| e.to_string())?;
+                self.import(snapshot).await
+            }
+        }
+    };
+}
+
+json_export_import!(UserClient => UserSnapshot);
+json_export_import!(ProductClient => ProductSnapshot);
+json_export_import!(OrderClient => OrderSnapshot);
+
+/// One field that differs between two versions of an entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+/// How one entity differs between two snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityDiff {
+    Added {
+        kind: &'static str,
+        id: String,
+    },
+    Removed {
+        kind: &'static str,
+        id: String,
+    },
+    Changed {
+        kind: &'static str,
+        id: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// Result of [`SystemSnapshot::diff`]: users, products, stock, then orders, each by ID.
+///
+/// Displays as a line-per-entity report: `+` added, `-` removed, `~` changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub entities: Vec<EntityDiff>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+impl std::fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entity in &self.entities {
+            match entity {
+                EntityDiff::Added { kind, id } => writeln!(f, "+ {} {}", kind, id)?,
+                EntityDiff::Removed { kind, id } => writeln!(f, "- {} {}", kind, id)?,
+                EntityDiff::Changed { kind, id, fields } => {
+                    write!(f, "~ {} {}:", kind, id)?;
+                    for change in fields {
+                        write!(
+                            f,
+                            " {} {} -> {};",
+                            change.field, change.before, change.after
+                        )?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SystemSnapshot {
+    /// What changed going from `self` to `other`, down to individual fields.
+    ///
+    /// Useful when two copies of the same state should agree but don't, e.g. a
+    /// saved snapshot against a live system, or before and after a replay.
+    pub fn diff(&self, other: &SystemSnapshot) -> SnapshotDiff {
+        let mut entities = Vec::new();
+        diff_entities(
+            &mut entities,
+            "user",
+            &self.users.users,
+            &other.users.users,
+            |a, b| {
+                let mut fields = Vec::new();
+                field_change(&mut fields, "name", &a.name, &b.name);
+                field_change(&mut fields, "email", &a.email, &b.email);
+                fields
+            },
+        );
+        diff_entities(
+            &mut entities,
+            "product",
+            &self.products.products,
+            &other.products.products,
+            |a, b| {
+                let mut fields = Vec::new();
+                field_change(&mut fields, "name", &a.name, &b.name);
+                field_change(&mut fields, "price", &a.price, &b.price);
+                fields
+            },
+        );
+        diff_entities(
+            &mut entities,
+            "stock",
+            &self.products.stock,
+            &other.products.stock,
+            |a, b| {
+                let mut fields = Vec::new();
+                field_change(&mut fields, "level", a, b);
+                fields
+            },
+        );
+        diff_entities(
+            &mut entities,
+            "order",
+            &self.orders.orders,
+            &other.orders.orders,
+            |a, b| {
+                let mut fields = Vec::new();
+                field_change(&mut fields, "user_id", &a.user_id, &b.user_id);
+                field_change(&mut fields, "product_id", &a.product_id, &b.product_id);
+                field_change(&mut fields, "quantity", &a.quantity, &b.quantity);
+                field_change(&mut fields, "total", &a.total, &b.total);
+                fields
+            },
+        );
+        SnapshotDiff { entities }
+    }
+}
+
+fn diff_entities<T>(
+    out: &mut Vec<EntityDiff>,
+    kind: &'static str,
+    before: &HashMap<String, T>,
+    after: &HashMap<String, T>,
+    fields: impl Fn(&T, &T) -> Vec<FieldChange>,
+) {
+    let mut ids: Vec<&String> = before.keys().chain(after.keys()).collect();
+    ids.sort();
+    ids.dedup();
+    for id in ids {
+        let id = id.clone();
+        match (before.get(&id), after.get(&id)) {
+            (None, Some(_)) => out.push(EntityDiff::Added { kind, id }),
+            (Some(_), None) => out.push(EntityDiff::Removed { kind, id }),
+            (Some(a), Some(b)) => {
+                let fields = fields(a, b);
+                if !fields.is_empty() {
+                    out.push(EntityDiff::Changed { kind, id, fields });
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+fn field_change<T: PartialEq + std::fmt::Debug>(
+    fields: &mut Vec<FieldChange>,
+    field: &'static str,
+    before: &T,
+    after: &T,
+) {
+    if before != after {
+        fields.push(FieldChange {
+            field,
+            before: format!("{:?}", before),
+            after: format!("{:?}", after),
+        });
+    }
+}
+
+// =============================================================================
+// INGREDIENT 13: DYNAMIC CLIENTS
+// =============================================================================
+
+/// A boxed, sendable future, for async methods on trait objects
+pub type BoxFuture<'a, T> = std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send + 'a>>;
+
+/// ## Ingredient 13: Type-Erased Clients
+///
+/// **Pattern:** Generic tooling (CLI, admin UI, debug inspector) can't name
+/// `UserClient` or `ProductClient` at compile time. `DynClient` is an
+/// object-safe facade that takes the method name plus JSON arguments keyed by
+/// parameter name, and returns JSON.
+///
+/// ```rust
+/// let users = system.dyn_client("users").unwrap();
+/// let id = users.call("create_user", json!({"user": {"name": "Alice", "email": "a@x.com"}})).await?;
+/// let user = users.call("get_user", json!({"id": id})).await?;
+/// ```
+///
+/// The typed clients stay the primary API; this is an adapter on top of them.
+#[cfg(feature = "serde")]
+pub trait DynClient: Send + Sync {
+    /// Methods accepted by [`DynClient::call`]
+    fn methods(&self) -> &'static [&'static str];
+
+    fn call<'a>(
+        &'a self,
+        method: &'a str,
+        args: serde_json::Value,
+    ) -> BoxFuture<'a, Result<serde_json::Value, String>>;
+}
+
+/// Pull one named argument out of a JSON object
+#[cfg(feature = "serde")]
+fn dyn_arg<T: serde::de::DeserializeOwned>(
+    args: &serde_json::Value,
+    name: &str,
+) -> Result<T, String> {
+    let value = args
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("Missing argument: {}", name))?;
+    serde_json::from_value(value).map_err(|e| format!("Invalid argument {}: {}", name, e))
+}
+
+#[cfg(feature = "serde")]
+fn dyn_result<T: serde::Serialize>(result: Result<T, String>) -> Result<serde_json::Value, String> {
+    serde_json::to_value(result?).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "serde")]
+impl DynClient for UserClient {
+    fn methods(&self) -> &'static [&'static str] {
+        &[
+            "get_user",
+            "find_users_by_email",
+            "count",
+            "exists",
+            "create_user",
+            "create_user_with_id",
+            "update_user",
+            "list_users",
+        ]
+    }
+
+    fn call<'a>(
+        &'a self,
+        method: &'a str,
+        args: serde_json::Value,
+    ) -> BoxFuture<'a, Result<serde_json::Value, String>> {
+        Box::pin(async move {
+            match method {
+                "get_user" => dyn_result(self.get_user(dyn_arg(&args, "id")?).await),
+                "find_users_by_email" => {
+                    dyn_result(self.find_users_by_email(dyn_arg(&args, "email")?).await)
+                }
+                "count" => dyn_result(self.count().await),
+                "exists" => dyn_result(self.exists(dyn_arg(&args, "id")?).await),
+                "create_user" => dyn_result(self.create_user(dyn_arg(&args, "user")?).await),
+                "create_user_with_id" => dyn_result(
+                    self.create_user_with_id(dyn_arg(&args, "id")?, dyn_arg(&args, "user")?)
+                        .await,
+                ),
+                "update_user" => dyn_result(
+                    self.update_user(dyn_arg(&args, "id")?, dyn_arg(&args, "user")?)
+                        .await,
+                ),
+                "list_users" => dyn_result(self.list_users().await),
+                other => Err(format!("Unknown method: {}", other)),
+            }
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl DynClient for ProductClient {
+    fn methods(&self) -> &'static [&'static str] {
+        &[
+            "get_product",
+            "count",
+            "exists",
+            "check_stock",
+            "reserve_stock",
+        ]
+    }
+
+    fn call<'a>(
+        &'a self,
+        method: &'a str,
+        args: serde_json::Value,
+    ) -> BoxFuture<'a, Result<serde_json::Value, String>> {
+        Box::pin(async move {
+            match method {
+                "get_product" => dyn_result(self.get_product(dyn_arg(&args, "id")?).await),
+                "count" => dyn_result(self.count().await),
+                "exists" => dyn_result(self.exists(dyn_arg(&args, "id")?).await),
+                "check_stock" => dyn_result(self.check_stock(dyn_arg(&args, "id")?).await),
+                "reserve_stock" => dyn_result(
+                    self.reserve_stock(dyn_arg(&args, "id")?, dyn_arg(&args, "quantity")?)
+                        .await,
+                ),
+                other => Err(format!("Unknown method: {}", other)),
+            }
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl DynClient for OrderClient {
+    fn methods(&self) -> &'static [&'static str] {
+        &["create_order", "get_order", "count", "exists"]
+    }
+
+    fn call<'a>(
+        &'a self,
+        method: &'a str,
+        args: serde_json::Value,
+    ) -> BoxFuture<'a, Result<serde_json::Value, String>> {
+        Box::pin(async move {
+            match method {
+                "create_order" => dyn_result(self.create_order(dyn_arg(&args, "order")?).await),
+                "get_order" => dyn_result(self.get_order(dyn_arg(&args, "id")?).await),
+                "count" => dyn_result(self.count().await),
+                "exists" => dyn_result(self.exists(dyn_arg(&args, "id")?).await),
+                other => Err(format!("Unknown method: {}", other)),
+            }
+        })
+    }
+}
+
+// =============================================================================
+// INGREDIENT 14: SUPERVISION
+// =============================================================================
+
+/// Creates and spawns a fresh actor, returning its mailbox and task handle
+pub type ActorFactory<R> =
+    Box<dyn FnMut() -> (mpsc::Sender<R>, tokio::task::JoinHandle<()>) + Send>;
+
+/// Receives an actor's final state as it stops (see `UserService::on_stop`)
+pub type StopHook<S> = Box<dyn FnOnce(S) + Send + Sync>;
+
+/// Receives each entity a bounded store evicts (see `UserService::on_evict`)
+pub type EvictHook<T> = Box<dyn FnMut(String, T) + Send + Sync>;
+
+/// ## Ingredient 14: Supervisor
+///
+/// **Pattern:** Clients talk to the supervisor's mailbox, never to the actor
+/// directly. The supervisor relays each request to the current actor
+/// incarnation and watches its `JoinHandle`. When the actor panics, the
+/// supervisor builds a new one from the factory and carries on, so every
+/// existing client clone keeps working.
+///
+/// **What Survives a Restart:**
+/// - **Clients** - They hold the supervisor's sender, which never changes
+/// - **Queued requests** - Requests still waiting in the supervisor mailbox
+///
+/// **What Doesn't:**
+/// - **The actor's state** - The factory decides what a new incarnation starts with
+/// - **In-flight requests** - Callers see a dropped response (`channel closed`)
+///
+/// A normal exit (e.g. after `Shutdown`) ends supervision; what happens after a
+/// panic is decided by the [`RestartPolicy`].
+///
+/// **Passivation:** With [`Supervisor::with_idle_timeout`], an actor that sees
+/// no traffic for that long is stopped cleanly and the next request spawns a
+/// fresh one from the factory. Pair it with the services' `on_stop` hook and
+/// `restore` so the state is persisted on the way down and picked up again.
+pub struct Supervisor<R> {
+    name: &'static str,
+    receiver: mpsc::Receiver<R>,
+    factory: ActorFactory<R>,
+    policy: RestartPolicy,
+    restarts: u32,
+    dead_letters: Option<DeadLetterClient<R>>,
+    idle_timeout: Option<Duration>,
+}
+
+/// Future adapter that turns a panic while polling into an `Err`.
+///
+/// Service run loops wrap every handler in this, so one bad message costs
+/// only its own request (the caller gets a `HandlerPanicked` error) rather than the
+/// whole actor and its store. Panics outside a handler still end the task and
+/// are left to the [`Supervisor`].
+struct CatchUnwind<'a, F>(std::pin::Pin<&'a mut F>);
+
+impl<F: std::future::Future> std::future::Future for CatchUnwind<'_, F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let inner = self.0.as_mut();
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| inner.poll(cx))) {
+            Ok(std::task::Poll::Pending) => std::task::Poll::Pending,
+            Ok(std::task::Poll::Ready(output)) => std::task::Poll::Ready(Ok(output)),
+            Err(panic) => std::task::Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+fn catch_unwind<F: std::future::Future>(future: std::pin::Pin<&mut F>) -> CatchUnwind<'_, F> {
+    CatchUnwind(future)
+}
+
+/// Best-effort text of a panic payload, for logging
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload")
+}
+
+/// What a [`Supervisor`] does when its actor panics.
+///
+/// A clean exit is never restarted: that is how `Shutdown` works.
+#[derive(Debug, Clone)]
+pub enum RestartPolicy {
+    /// Leave the actor dead; clients get `channel closed` from then on
+    Never,
+    /// Restart immediately after every panic
+    Always,
+    /// Restart up to `max_restarts` times, doubling the delay each time
+    OnFailure {
+        max_restarts: u32,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::OnFailure {
+            max_restarts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Per-actor restart policies for [`OrderSystem`]
+#[derive(Debug, Clone, Default)]
+pub struct RestartPolicies {
+    pub users: RestartPolicy,
+    pub products: RestartPolicy,
+    pub orders: RestartPolicy,
+}
+
+impl<R: Send + 'static> Supervisor<R> {
+    pub fn new<F>(name: &'static str, buffer_size: usize, factory: F) -> (Self, mpsc::Sender<R>)
+    where
+        F: FnMut() -> (mpsc::Sender<R>, tokio::task::JoinHandle<()>) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let supervisor = Self {
+            name,
+            receiver,
+            factory: Box::new(factory),
+            policy: RestartPolicy::Always,
+            restarts: 0,
+            dead_letters: None,
+            idle_timeout: None,
+        };
+        (supervisor, sender)
+    }
+
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Park undeliverable requests here instead of dropping them
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetterClient<R>) -> Self {
+        self.dead_letters = Some(dead_letters);
+        self
+    }
+
+    /// Stop the actor after `timeout` without requests; the next one respawns it
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    #[instrument(
+        target = "actor::supervisor",
+        name = "supervisor",
+        fields(actor = self.name),
+        skip(self),
+    )]
+    pub async fn run(mut self) {
+        let (mut actor, mut handle) = (self.factory)();
+        info!(target: SUPERVISOR_TARGET, "Supervisor started");
+
+        let stopped = loop {
+            tokio::select! {
+                msg = self.receiver.recv() => {
+                    let Some(msg) = msg else {
+                        // Every client is gone; let the actor drain and stop
+                        drop(actor);
+                        let _ = handle.await;
+                        break None;
+                    };
+
+                    if let Err(mpsc::error::SendError(msg)) = actor.send(msg).await {
+                        // The actor is gone; find out why before deciding about msg
+                        let result = (&mut handle).await;
+                        let reason = DeadLetterReason::for_exit(&result);
+                        match self.on_exit(result).await {
+                            Some((new_actor, new_handle)) => {
+                                actor = new_actor;
+                                handle = new_handle;
+                                let _ = actor.send(msg).await;
+                            }
+                            None => {
+                                self.dead_letter(msg, reason).await;
+                                break Some(reason);
+                            }
+                        }
+                    }
+                }
+                result = &mut handle => {
+                    let reason = DeadLetterReason::for_exit(&result);
+                    match self.on_exit(result).await {
+                        Some((new_actor, new_handle)) => {
+                            actor = new_actor;
+                            handle = new_handle;
+                        }
+                        None => break Some(reason),
+                    }
+                }
+                _ = tokio::time::sleep(self.idle_timeout.unwrap_or(Duration::MAX)),
+                    if self.idle_timeout.is_some() =>
+                {
+                    // Close the actor's mailbox so it stops cleanly, then wait for demand
+                    drop(std::mem::replace(&mut actor, mpsc::channel(1).0));
+                    if let Err(e) = (&mut handle).await {
+                        warn!(
+                            target: SUPERVISOR_TARGET,
+                            error = %e,
+                            "Actor failed while passivating"
+                        );
+                    }
+                    info!(target: SUPERVISOR_TARGET, "Actor passivated");
+
+                    let Some(msg) = self.receiver.recv().await else {
+                        break None;
+                    };
+                    debug!(target: SUPERVISOR_TARGET, "Reactivating actor");
+                    (actor, handle) = (self.factory)();
+                    if let Err(mpsc::error::SendError(msg)) = actor.send(msg).await {
+                        self.dead_letter(msg, DeadLetterReason::ActorStopped).await;
+                    }
+                }
+            }
+        };
+
+        if let (Some(reason), Some(_)) = (stopped, &self.dead_letters) {
+            // Nothing will serve what's still queued; park it rather than drop it
+            self.receiver.close();
+            while let Some(msg) = self.receiver.recv().await {
+                self.dead_letter(msg, reason).await;
+            }
+        }
+
+        info!(target: SUPERVISOR_TARGET, restarts = self.restarts, "Supervisor stopped");
+    }
+
+    // `&mut self` keeps the future `Send` without requiring the factory to be `Sync`
+    async fn dead_letter(&mut self, message: R, reason: DeadLetterReason) {
+        if let Some(dead_letters) = &self.dead_letters {
+            dead_letters
+                .record(DeadLetter {
+                    actor: self.name,
+                    reason,
+                    message,
+                })
+                .await;
+        }
+    }
+
+    /// Restart after a panic if the policy allows, give up after a clean exit or cancellation
+    async fn on_exit(
+        &mut self,
+        result: Result<(), tokio::task::JoinError>,
+    ) -> Option<(mpsc::Sender<R>, tokio::task::JoinHandle<()>)> {
+        match result {
+            Err(e) if e.is_panic() => {
+                let delay = match &self.policy {
+                    RestartPolicy::Never => {
+                        error!(
+                            target: SUPERVISOR_TARGET,
+                            code = %EventCode::RestartsExhausted,
+                            "Actor panicked, restart policy is Never - giving up"
+                        );
+                        return None;
+                    }
+                    RestartPolicy::Always => Duration::ZERO,
+                    RestartPolicy::OnFailure {
+                        max_restarts,
+                        initial_backoff,
+                        max_backoff,
+                    } => {
+                        if self.restarts >= *max_restarts {
+                            error!(
+                                target: SUPERVISOR_TARGET,
+                                code = %EventCode::RestartsExhausted,
+                                restarts = self.restarts,
+                                "Actor panicked, restart limit reached - giving up"
+                            );
+                            return None;
+                        }
+                        initial_backoff
+                            .saturating_mul(2u32.saturating_pow(self.restarts))
+                            .min(*max_backoff)
+                    }
+                };
+
+                self.restarts += 1;
+                error!(
+                    target: SUPERVISOR_TARGET,
+                    code = %EventCode::ActorRestarted,
+                    restarts = self.restarts,
+                    delay_ms = delay.as_millis(),
+                    "Actor panicked, restarting"
+                );
+                tokio::time::sleep(delay).await;
+                info!(target: SUPERVISOR_TARGET, restarts = self.restarts, "Actor restarted");
+                Some((self.factory)())
+            }
+            Ok(()) => {
+                debug!(target: SUPERVISOR_TARGET, "Actor stopped");
+                None
+            }
+            Err(e) => {
+                warn!(target: SUPERVISOR_TARGET, error = %e, "Actor cancelled");
+                None
+            }
+        }
+    }
+}
+
+// =============================================================================
+// INGREDIENT 15: DEAD LETTERS
+// =============================================================================
+
+/// ## Ingredient 15: Dead Letter Queue
+///
+/// **Pattern:** A [`Supervisor`] whose actor is gone for good hands every
+/// request it can no longer deliver to a dead letter actor instead of dropping
+/// it. The request is kept whole, `respond_to` included, so a caller that is
+/// still waiting gets its answer if the letter is replayed later.
+///
+/// **Operations:**
+/// - **List** - Inspect what's parked, with the reason and a debug rendering
+/// - **Replay** - Re-send every letter to a (new) actor mailbox
+/// - **Purge** - Drop everything; waiting callers see `channel closed`
+///
+/// Requests that reached a handler are not dead letters: their errors, and
+/// panics, already go back to the caller. Waiting callers should use a client
+/// timeout, since a parked request is answered only on replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// The actor had shut down cleanly
+    ActorStopped,
+    /// The actor panicked and its restart policy gave up
+    RestartsExhausted,
+}
+
+impl DeadLetterReason {
+    fn for_exit(result: &Result<(), tokio::task::JoinError>) -> Self {
+        match result {
+            Err(e) if e.is_panic() => DeadLetterReason::RestartsExhausted,
+            _ => DeadLetterReason::ActorStopped,
+        }
+    }
+}
+
+/// An undeliverable request and where it was headed
+#[derive(Debug)]
+pub struct DeadLetter<R> {
+    pub actor: &'static str,
+    pub reason: DeadLetterReason,
+    pub message: R,
+}
+
+/// What [`DeadLetterClient::list`] reports for each parked request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetterInfo {
+    pub actor: &'static str,
+    pub reason: DeadLetterReason,
+    pub message: String,
+}
+
+/// Dead letter error types
+#[derive(Debug, Clone)]
+pub enum DeadLetterError {
+    /// The replay target's mailbox is closed; unsent letters stay queued
+    TargetClosed { replayed: usize },
+}
+
+impl std::fmt::Display for DeadLetterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadLetterError::TargetClosed { replayed } => {
+                write!(f, "Replay target closed after {} letters", replayed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeadLetterError {}
+
+#[derive(Debug)]
+pub enum DeadLetterRequest<R> {
+    Record {
+        letter: DeadLetter<R>,
+    },
+    List {
+        respond_to: ServiceResponse<Vec<DeadLetterInfo>, DeadLetterError>,
+    },
+    Replay {
+        target: mpsc::Sender<R>,
+        respond_to: ServiceResponse<usize, DeadLetterError>,
+    },
+    Purge {
+        respond_to: ServiceResponse<usize, DeadLetterError>,
+    },
+    Shutdown,
+}
+
+pub struct DeadLetterService<R> {
+    receiver: mpsc::Receiver<DeadLetterRequest<R>>,
+    letters: VecDeque<DeadLetter<R>>,
+    capacity: usize,
+}
+
+impl<R: std::fmt::Debug + Send + 'static> DeadLetterService<R> {
+    /// `capacity` bounds memory; past it the oldest letter is discarded
+    pub fn new(buffer_size: usize, capacity: usize) -> (Self, DeadLetterClient<R>) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            letters: VecDeque::new(),
+            capacity: capacity.max(1),
+        };
+        (service, DeadLetterClient { sender })
+    }
+
+    #[instrument(target = "actor::dead_letters", name = "dead_letters", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: DEAD_LETTERS_TARGET, "DeadLetterService starting");
+
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                DeadLetterRequest::Record { letter } => {
+                    self.handle_record(letter);
+                }
+                DeadLetterRequest::List { respond_to } => {
+                    let _ = respond_to.send(Ok(self.list()));
+                }
+                DeadLetterRequest::Replay { target, respond_to } => {
+                    self.handle_replay(target, respond_to).await;
+                }
+                DeadLetterRequest::Purge { respond_to } => {
+                    let purged = self.letters.len();
+                    self.letters.clear();
+                    info!(target: DEAD_LETTERS_TARGET, purged, "Dead letters purged");
+                    let _ = respond_to.send(Ok(purged));
+                }
+                DeadLetterRequest::Shutdown => {
+                    info!(target: DEAD_LETTERS_TARGET, "DeadLetterService shutting down");
+                    break;
+                }
+            }
+        }
+
+        info!(target: DEAD_LETTERS_TARGET, "DeadLetterService stopped");
+    }
+
+    #[instrument(
+        target = "actor::dead_letters",
+        fields(actor = letter.actor, reason = ?letter.reason),
+        skip(self, letter),
+    )]
+    fn handle_record(&mut self, letter: DeadLetter<R>) {
+        if self.letters.len() >= self.capacity {
+            warn!(target: DEAD_LETTERS_TARGET, "Dead letter queue full, discarding oldest letter");
+            self.letters.pop_front();
+        }
+        warn!(
+            target: DEAD_LETTERS_TARGET,
+            code = %EventCode::DeadLetterRecorded,
+            "Dead letter recorded"
+        );
+        self.letters.push_back(letter);
+    }
+
+    fn list(&self) -> Vec<DeadLetterInfo> {
+        self.letters
+            .iter()
+            .map(|letter| DeadLetterInfo {
+                actor: letter.actor,
+                reason: letter.reason,
+                message: format!("{:?}", letter.message),
+            })
+            .collect()
+    }
+
+    #[instrument(target = "actor::dead_letters", skip(self, target, respond_to))]
+    async fn handle_replay(
+        &mut self,
+        target: mpsc::Sender<R>,
+        respond_to: ServiceResponse<usize, DeadLetterError>,
+    ) {
+        let mut replayed = 0;
+        while let Some(letter) = self.letters.pop_front() {
+            if let Err(mpsc::error::SendError(message)) = target.send(letter.message).await {
+                // Put it back untouched so a later replay can try again
+                self.letters.push_front(DeadLetter { message, ..letter });
+                send_error!(respond_to, DeadLetterError::TargetClosed { replayed });
+            }
+            replayed += 1;
+        }
+
+        info!(target: DEAD_LETTERS_TARGET, replayed, "Dead letters replayed");
+        let _ = respond_to.send(Ok(replayed));
+    }
+}
+
+pub struct DeadLetterClient<R> {
+    sender: mpsc::Sender<DeadLetterRequest<R>>,
+}
+
+// Manual impl: cloning the client must not require `R: Clone`
+impl<R> Clone for DeadLetterClient<R> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<R: Send + 'static> DeadLetterClient<R> {
+    /// Fire-and-forget, like [`EventBusClient::publish`]
+    pub async fn record(&self, letter: DeadLetter<R>) {
+        if let Err(e) = self.sender.send(DeadLetterRequest::Record { letter }).await {
+            warn!(error = %e, "Dead letter queue unavailable, letter dropped");
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list(&self) -> Result<Vec<DeadLetterInfo>, String> {
+        let (respond_to, response) = ServiceResponse::channel();
+        self.request(DeadLetterRequest::List { respond_to }, response)
+            .await
+    }
+
+    /// Re-send every letter to `target`, oldest first
+    #[instrument(skip(self, target))]
+    pub async fn replay(&self, target: mpsc::Sender<R>) -> Result<usize, String> {
+        let (respond_to, response) = ServiceResponse::channel();
+        self.request(DeadLetterRequest::Replay { target, respond_to }, response)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn purge(&self) -> Result<usize, String> {
+        let (respond_to, response) = ServiceResponse::channel();
+        self.request(DeadLetterRequest::Purge { respond_to }, response)
+            .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        self.sender
+            .send(DeadLetterRequest::Shutdown)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn request<T>(
+        &self,
+        request: DeadLetterRequest<R>,
+        response: oneshot::Receiver<ServiceResult<T, DeadLetterError>>,
+    ) -> Result<T, String> {
+        self.sender.send(request).await.map_err(|e| e.to_string())?;
+        response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+}
+
+// =============================================================================
+// INGREDIENT 16: DEMO FAULT INJECTION
+// =============================================================================
+
+/// ## Ingredient 16: Fault Injection for Demos
+///
+/// **Pattern:** A service checks its [`FaultInjector`] before each request and
+/// may sleep or drop it. The injector is shared, so a running system can be
+/// made slow or flaky on the fly, which is what makes the timeout, retry,
+/// breaker and tracing output worth looking at in a demo.
+///
+/// A dropped request looks to the caller exactly like a crashed handler: the
+/// response channel closes. `Drain` and `Shutdown` are never delayed or dropped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultProfile {
+    /// Added before every request is handled
+    pub latency: Duration,
+    /// Fraction of requests dropped, from 0.0 to 1.0
+    pub error_rate: f64,
+}
+
+/// A [`FaultProfile`] per service of the [`OrderSystem`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DemoProfile {
+    pub users: FaultProfile,
+    pub products: FaultProfile,
+    pub orders: FaultProfile,
+}
+
+impl DemoProfile {
+    /// Slow sub-actors and a flaky product service
+    pub fn chaos() -> Self {
+        Self {
+            users: FaultProfile {
+                latency: Duration::from_millis(50),
+                error_rate: 0.0,
+            },
+            products: FaultProfile {
+                latency: Duration::from_millis(150),
+                error_rate: 0.2,
+            },
+            orders: FaultProfile {
+                latency: Duration::from_millis(20),
+                error_rate: 0.0,
+            },
+        }
+    }
+
+    /// Pick a profile by name, as given in `DEMO_PROFILE`
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "calm" => Some(Self::default()),
+            "chaos" => Some(Self::chaos()),
+            _ => None,
+        }
+    }
+}
+
+/// Shared, runtime-adjustable [`FaultProfile`] for one service
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjector(Arc<std::sync::Mutex<FaultProfile>>);
+
+impl FaultInjector {
+    pub fn new(profile: FaultProfile) -> Self {
+        Self(Arc::new(std::sync::Mutex::new(profile)))
+    }
+
+    pub fn set(&self, profile: FaultProfile) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = profile;
+    }
+
+    /// Apply the current profile; `false` means drop the request
+    async fn inject(&self) -> bool {
+        let profile = self.0.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if !profile.latency.is_zero() {
+            tokio::time::sleep(profile.latency).await;
+        }
+        if profile.error_rate > 0.0 && random_fraction() < profile.error_rate {
+            warn!("Injected fault: request dropped");
+            return false;
+        }
+        true
+    }
+}
+
+/// The injectors wired into each [`OrderSystem`] service
+#[derive(Debug, Clone, Default)]
+struct DemoFaults {
+    users: FaultInjector,
+    products: FaultInjector,
+    orders: FaultInjector,
+}
+
+// =============================================================================
+// INGREDIENT 17: ACTOR REGISTRY
+// =============================================================================
+
+/// A client the [`ActorSystem`] can hand out and use to stop its actor
+pub trait ActorClient: Clone + Send + Sync + 'static {
+    fn shutdown_actor(&self) -> BoxFuture<'_, Result<(), String>>;
+}
+
+macro_rules! actor_client {
+    ($($client:ty),*) => {
+        $(impl ActorClient for $client {
+            fn shutdown_actor(&self) -> BoxFuture<'_, Result<(), String>> {
+                Box::pin(self.shutdown())
+            }
+        })*
+    };
+}
+
+actor_client!(UserClient, ProductClient, OrderClient, EventBusClient);
+
+/// ## Ingredient 17: Generic Actor System
+///
+/// **Pattern:** [`OrderSystem`] wires a fixed set of services by hand. An
+/// `ActorSystem` is the open-ended version: any actor is registered with its
+/// client and task handle, clients are looked up by type, and shutdown is the
+/// same for everyone.
+///
+/// **Shutdown Order:** Reverse registration order. Register dependencies
+/// first (as `OrderSystem` starts them) and dependents are stopped before the
+/// actors they call.
+///
+/// ```rust
+/// let mut system = ActorSystem::new();
+/// let (users, user_client) = UserService::new(100);
+/// system.register("users", user_client, tokio::spawn(users.run()))?;
+///
+/// let user_client = system.client::<UserClient>().ok_or("users not registered")?;
+/// system.shutdown().await?;
+/// ```
+#[derive(Default)]
+pub struct ActorSystem {
+    actors: Vec<RegisteredActor>,
+    by_type: HashMap<std::any::TypeId, usize>,
+}
+
+struct RegisteredActor {
+    name: &'static str,
+    client: Box<dyn std::any::Any + Send + Sync>,
+    shutdown: Box<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl ActorSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take ownership of a running actor. One actor per client type; a
+    /// rejected actor is left running, and stops once its clients are dropped.
+    pub fn register<C: ActorClient>(
+        &mut self,
+        name: &'static str,
+        client: C,
+        handle: tokio::task::JoinHandle<()>,
+    ) -> Result<(), String> {
+        let type_id = std::any::TypeId::of::<C>();
+        if let Some(&existing) = self.by_type.get(&type_id) {
+            return Err(format!(
+                "{} is already registered as {}",
+                std::any::type_name::<C>(),
+                self.actors[existing].name
+            ));
+        }
+
+        info!(actor = name, "Actor registered");
+        let stopper = client.clone();
+        self.by_type.insert(type_id, self.actors.len());
+        self.actors.push(RegisteredActor {
+            name,
+            client: Box::new(client),
+            shutdown: Box::new(move || {
+                let client = stopper.clone();
+                Box::pin(async move { client.shutdown_actor().await })
+            }),
+            handle,
+        });
+        Ok(())
+    }
+
+    /// A clone of the registered client of type `C`
+    pub fn client<C: ActorClient>(&self) -> Option<C> {
+        let index = *self.by_type.get(&std::any::TypeId::of::<C>())?;
+        self.actors[index].client.downcast_ref::<C>().cloned()
+    }
+
+    /// Registered actor names, in registration order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.actors.iter().map(|actor| actor.name).collect()
+    }
+
+    /// Stop every actor, last registered first, then wait for all of them
+    #[instrument(name = "actor_system_shutdown", skip(self))]
+    pub async fn shutdown(self) -> Result<(), String> {
+        for actor in self.actors.iter().rev() {
+            if let Err(e) = (actor.shutdown)().await {
+                warn!(actor = actor.name, error = %e, "Actor shutdown failed");
+            }
+        }
+        for actor in self.actors {
+            if let Err(e) = actor.handle.await {
+                error!(actor = actor.name, error = ?e, "Actor task failed");
+            }
+        }
+        info!("Actor system shutdown complete");
+        Ok(())
+    }
+}
+
+/// Named registry error types
+#[derive(Debug, Clone)]
+pub enum RegistryError {
+    AlreadyRegistered(String),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::AlreadyRegistered(key) => write!(f, "Already registered: {}", key),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Produces a fresh clone of a registered client, type-erased
+type ClientCloner = Box<dyn Fn() -> Box<dyn std::any::Any + Send> + Send + Sync>;
+
+/// Entries are keyed by client type as well as name, so `"eu"` can name both
+/// a product store and a user store.
+type RegistryKey = (std::any::TypeId, String);
+
+pub enum RegistryRequest {
+    Register {
+        key: RegistryKey,
+        cloner: ClientCloner,
+        respond_to: ServiceResponse<(), RegistryError>,
+    },
+    Lookup {
+        key: RegistryKey,
+        respond_to: ServiceResponse<Option<Box<dyn std::any::Any + Send>>, RegistryError>,
+    },
+    Unregister {
+        key: RegistryKey,
+        respond_to: ServiceResponse<bool, RegistryError>,
+    },
+    Shutdown,
+}
+
+/// ## Named Registry
+///
+/// **Pattern:** Where [`ActorSystem`] holds one actor per client type, the
+/// registry actor maps string keys to clients, so several actors of the same
+/// type can coexist (per-region product stores, say) and be found at runtime
+/// by whoever needs them.
+///
+/// ```rust
+/// registry.register("inventory-eu", eu_products).await?;
+/// let products = registry.lookup::<ProductClient>("inventory-eu").await?;
+/// ```
+pub struct RegistryService {
+    receiver: mpsc::Receiver<RegistryRequest>,
+    entries: HashMap<RegistryKey, ClientCloner>,
+}
+
+impl RegistryService {
+    pub fn new(buffer_size: usize) -> (Self, RegistryClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            entries: HashMap::new(),
+        };
+        (service, RegistryClient { sender })
+    }
+
+    #[instrument(target = "actor::registry", name = "registry", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: REGISTRY_TARGET, "Registry starting");
+
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                RegistryRequest::Register {
+                    key,
+                    cloner,
+                    respond_to,
+                } => {
+                    let result = match self.entries.entry(key) {
+                        std::collections::hash_map::Entry::Occupied(entry) => {
+                            Err(RegistryError::AlreadyRegistered(entry.key().1.clone()))
+                        }
+                        std::collections::hash_map::Entry::Vacant(entry) => {
+                            info!(
+                                target: REGISTRY_TARGET,
+                                key = %entry.key().1,
+                                "Client registered",
+                            );
+                            entry.insert(cloner);
+                            Ok(())
+                        }
+                    };
+                    let _ = respond_to.send(result);
+                }
+                RegistryRequest::Lookup { key, respond_to } => {
+                    let _ = respond_to.send(Ok(self.entries.get(&key).map(|cloner| cloner())));
+                }
+                RegistryRequest::Unregister { key, respond_to } => {
+                    let removed = self.entries.remove(&key).is_some();
+                    if removed {
+                        info!(target: REGISTRY_TARGET, key = %key.1, "Client unregistered");
+                    }
+                    let _ = respond_to.send(Ok(removed));
+                }
+                RegistryRequest::Shutdown => {
+                    info!(target: REGISTRY_TARGET, "Registry shutting down");
+                    break;
+                }
+            }
+        }
+
+        info!(target: REGISTRY_TARGET, "Registry stopped");
+    }
+}
+
+#[derive(Clone)]
+pub struct RegistryClient {
+    sender: mpsc::Sender<RegistryRequest>,
+}
+
+impl RegistryClient {
+    /// Register `client` under `name`; fails if that name is taken for this type
+    #[instrument(skip(self, client))]
+    pub async fn register<C>(&self, name: &str, client: C) -> Result<(), String>
+    where
+        C: Clone + Send + Sync + 'static,
+    {
+        let cloner: ClientCloner = Box::new(move || Box::new(client.clone()));
+        let (respond_to, response) = ServiceResponse::channel();
+        self.request(
+            RegistryRequest::Register {
+                key: Self::key::<C>(name),
+                cloner,
+                respond_to,
+            },
+            response,
+        )
+        .await
+    }
+
+    /// A clone of the client of type `C` registered under `name`
+    #[instrument(skip(self))]
+    pub async fn lookup<C>(&self, name: &str) -> Result<Option<C>, String>
+    where
+        C: Clone + Send + Sync + 'static,
+    {
+        let (respond_to, response) = ServiceResponse::channel();
+        let found = self
+            .request(
+                RegistryRequest::Lookup {
+                    key: Self::key::<C>(name),
+                    respond_to,
+                },
+                response,
+            )
+            .await?;
+        // The key includes C's TypeId, so the downcast cannot fail
+        Ok(found.and_then(|client| client.downcast::<C>().ok().map(|client| *client)))
+    }
+
+    /// Remove an entry; `false` if there was none
+    #[instrument(skip(self))]
+    pub async fn unregister<C: 'static>(&self, name: &str) -> Result<bool, String> {
+        let (respond_to, response) = ServiceResponse::channel();
+        self.request(
+            RegistryRequest::Unregister {
+                key: Self::key::<C>(name),
+                respond_to,
+            },
+            response,
+        )
+        .await
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        self.sender
+            .send(RegistryRequest::Shutdown)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn key<C: 'static>(name: &str) -> RegistryKey {
+        (std::any::TypeId::of::<C>(), name.to_string())
+    }
+
+    async fn request<T>(
+        &self,
+        request: RegistryRequest,
+        response: oneshot::Receiver<ServiceResult<T, RegistryError>>,
+    ) -> Result<T, String> {
+        self.sender
+            .send(request)
+            .await
+            .map_err(|_| "Registry closed".to_string())?;
+        response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
+}
+
+// =============================================================================
+// INGREDIENT 18: SHARDING
+// =============================================================================
+
+/// ## Ingredient 18: Sharded Product Service
+///
+/// **Pattern:** One actor per entity type serializes every request for that
+/// type. `ShardedProductService` runs N independent [`ProductService`] shards
+/// behind a router that owns the public mailbox. Requests for one product are
+/// routed by hashing its ID, so they stay ordered relative to each other while
+/// different products are served in parallel.
+///
+/// Callers get an ordinary [`ProductClient`]: sharding is invisible to them and
+/// to the [`OrderService`]. Whole-store requests (`Snapshot`, `Import`, `Count`, `Drain`)
+/// fan out to every shard and the answers are merged, as do batch requests
+/// (`GetProducts`, `UpdateProducts`), each shard getting only the IDs it owns; `Shutdown` stops the
+/// router, which then lets every shard drain and stop.
+pub struct ShardedProductService {
+    receiver: mpsc::Receiver<ProductRequest>,
+    shards: Vec<(ProductService, mpsc::Sender<ProductRequest>)>,
+    shutdown_acks: Vec<ServiceResponse<(), ProductError>>,
+}
+
+impl ShardedProductService {
+    pub fn new(shards: usize, buffer_size: usize) -> (Self, ProductClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let shards = (0..shards.max(1))
+            .map(|_| {
+                let (shard, client) = ProductService::new(buffer_size);
+                (shard, client.sender)
+            })
+            .collect();
+        let service = Self {
+            receiver,
+            shards,
+            shutdown_acks: Vec::new(),
+        };
+        (service, ProductClient::new(sender))
+    }
+
+    /// Publish lifecycle events from every shard to the given bus
+    pub fn with_event_bus(mut self, events: EventBusClient) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_event_bus(events.clone()), sender))
+            .collect();
+        self
+    }
+
+    /// Record every shard's mutations in the given audit log
+    pub fn with_audit(mut self, audit: AuditClient) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_audit(audit.clone()), sender))
+            .collect();
+        self
+    }
+
+    /// Inject artificial latency and failures into every shard, for demos
+    pub fn with_faults(mut self, faults: FaultInjector) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_faults(faults.clone()), sender))
+            .collect();
+        self
+    }
+
+    /// Warn about any handler on any shard that takes longer than `threshold`
+    pub fn with_slow_handler(mut self, threshold: Duration) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_slow_handler(threshold), sender))
+            .collect();
+        self
+    }
+
+    /// Set every shard's batch size; see [`ActorConfig::batch_size`]
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_batch_size(size), sender))
+            .collect();
+        self
+    }
+
+    /// Shed load on each shard by its own queue depth
+    pub fn with_load_shedding(mut self, threshold: usize) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_load_shedding(threshold), sender))
+            .collect();
+        self
+    }
+
+    /// Read time from `clock` on every shard
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_clock(clock.clone()), sender))
+            .collect();
+        self
+    }
+
+    /// Bound every shard's outbox; see [`ProductService::with_outbox_capacity`]
+    pub fn with_outbox_capacity(mut self, capacity: usize) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_outbox_capacity(capacity), sender))
+            .collect();
+        self
+    }
+
+    /// How often every shard runs its periodic housekeeping
+    pub fn with_tick_interval(mut self, interval: Duration) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|(shard, sender)| (shard.with_tick_interval(interval), sender))
+            .collect();
+        self
+    }
+
+    /// Turn on the read-optimized mode on every shard; see [`ProductReadView`]
+    pub fn read_view(&mut self) -> ProductReadView {
+        let shards = self
+            .shards
+            .iter_mut()
+            .flat_map(|(shard, _)| shard.read_view().shards)
+            .collect();
+        ProductReadView { shards }
+    }
+
+    /// Load previously captured state, each product into the shard that owns it
+    pub fn restore(mut self, snapshot: ProductSnapshot) -> Self {
+        let parts = split_product_snapshot(snapshot, self.shards.len());
+        self.shards = self
+            .shards
+            .into_iter()
+            .zip(parts)
+            .map(|((shard, sender), part)| (shard.restore(part), sender))
+            .collect();
+        self
+    }
+
+    #[instrument(
+        target = "actor::product",
+        name = "sharded_product_service",
+        skip(self)
+    )]
+    pub async fn run(mut self) {
+        let (shards, handles): (Vec<_>, Vec<_>) = std::mem::take(&mut self.shards)
+            .into_iter()
+            .map(|(shard, sender)| (sender, tokio::spawn(shard.run())))
+            .unzip();
+        info!(target: PRODUCT_TARGET, shards = shards.len(), "ShardedProductService starting");
+
+        while let Some(msg) = self.receiver.recv().await {
+            self.route(&shards, msg).await;
+        }
+
+        // The shards stop once every sender, including in-flight fan-outs, is gone
+        drop(shards);
+        for handle in handles {
+            let _ = handle.await;
+        }
+        for ack in self.shutdown_acks.drain(..) {
+            let _ = ack.send(Ok(()));
+        }
+        info!(target: PRODUCT_TARGET, "ShardedProductService stopped");
+    }
+
+    /// Forward one request to its shard, or fan it out to all of them
+    async fn route(&mut self, shards: &[mpsc::Sender<ProductRequest>], msg: ProductRequest) {
+        match msg {
+            ProductRequest::Snapshot { respond_to } => {
+                let responses = fan_out(shards, |_, reply| ProductRequest::Snapshot {
+                    respond_to: reply,
+                })
+                .await;
+                tokio::spawn(async move {
+                    let merged = gather(responses).await.map(|parts| {
+                        parts
+                            .into_iter()
+                            .fold(ProductSnapshot::default(), |mut all, part| {
+                                all.products.extend(part.products);
+                                all.stock.extend(part.stock);
+                                all.reserved.extend(part.reserved);
+                                all.outbox.append(part.outbox);
+                                all
+                            })
+                    });
+                    let _ = respond_to.send(merged);
+                });
+            }
+            ProductRequest::Import {
+                snapshot,
+                respond_to,
+            } => {
+                let mut parts = split_product_snapshot(snapshot, shards.len());
+                let responses = fan_out(shards, |i, reply| ProductRequest::Import {
+                    snapshot: std::mem::take(&mut parts[i]),
+                    respond_to: reply,
+                })
+                .await;
+                tokio::spawn(async move {
+                    let _ = respond_to.send(gather(responses).await.map(|n| n.iter().sum()));
+                });
+            }
+            ProductRequest::GetProducts { ids, respond_to } => {
+                let mut parts = vec![Vec::new(); shards.len()];
+                for id in ids {
+                    parts[shard_for(&id, shards.len())].push(id);
+                }
+                let responses = fan_out(shards, |i, reply| ProductRequest::GetProducts {
+                    ids: std::mem::take(&mut parts[i]),
+                    respond_to: reply,
+                })
+                .await;
+                tokio::spawn(async move {
+                    let merged = gather(responses)
+                        .await
+                        .map(|parts| parts.into_iter().flatten().collect());
+                    let _ = respond_to.send(merged);
+                });
+            }
+            ProductRequest::UpdateProducts {
+                patches,
+                respond_to,
+            } => {
+                // Remember each patch's position so the results come back in order
+                let mut parts = vec![Vec::new(); shards.len()];
+                let mut positions = vec![Vec::new(); shards.len()];
+                for (i, (id, patch)) in patches.into_iter().enumerate() {
+                    let shard = shard_for(&id, shards.len());
+                    parts[shard].push((id, patch));
+                    positions[shard].push(i);
+                }
+                let count = positions.iter().map(Vec::len).sum();
+                let responses = fan_out(shards, |i, reply| ProductRequest::UpdateProducts {
+                    patches: std::mem::take(&mut parts[i]),
+                    respond_to: reply,
+                })
+                .await;
+                tokio::spawn(async move {
+                    let merged = gather(responses).await.map(|parts| {
+                        let mut results: Vec<_> = (0..count).map(|_| Ok(())).collect();
+                        for (part, positions) in parts.into_iter().zip(positions) {
+                            for (result, i) in part.into_iter().zip(positions) {
+                                results[i] = result;
+                            }
+                        }
+                        results
+                    });
+                    let _ = respond_to.send(merged);
+                });
+            }
+            ProductRequest::Count { respond_to } => {
+                let responses = fan_out(shards, |_, reply| ProductRequest::Count {
+                    respond_to: reply,
+                })
+                .await;
+                tokio::spawn(async move {
+                    let _ = respond_to.send(gather(responses).await.map(|n| n.iter().sum()));
+                });
+            }
+            ProductRequest::Drain { respond_to } => {
+                let responses = fan_out(shards, |_, reply| ProductRequest::Drain {
+                    respond_to: reply,
+                })
+                .await;
+                tokio::spawn(async move {
+                    let _ = respond_to.send(gather(responses).await.map(|_| ()));
+                });
+            }
+            ProductRequest::GetStats { respond_to } => {
+                // The router's own mailbox is the one callers queue in
+                let stats = MailboxStats::sample(&self.receiver, 0, respond_to.sent_at());
+                stats.log(PRODUCT_TARGET);
+                let _ = respond_to.send(Ok(stats));
+            }
+            ProductRequest::Ping { respond_to } => {
+                let _ = respond_to.send(Ok(()));
+            }
+            ProductRequest::Shutdown { respond_to } => {
+                info!(
+                    target: PRODUCT_TARGET,
+                    "ShardedProductService shutting down, draining queued requests"
+                );
+                self.receiver.close();
+                self.shutdown_acks.push(respond_to);
+            }
+            keyed => {
+                let shard = keyed
+                    .product_id()
+                    .map_or(0, |id| shard_for(id, shards.len()));
+                // A failed send drops the request, so the caller sees `channel closed`
+                let _ = shards[shard].send(keyed).await;
+            }
+        }
+    }
+}
+
+/// The shard that owns `id`. `DefaultHasher::new()` is unseeded, so the
+/// mapping is stable across restarts for a given shard count.
+fn shard_for(id: &str, shards: usize) -> usize {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    id.hash(&mut hasher);
+    (hasher.finish() % shards as u64) as usize
+}
+
+fn split_product_snapshot(snapshot: ProductSnapshot, shards: usize) -> Vec<ProductSnapshot> {
+    let mut parts = vec![ProductSnapshot::default(); shards];
+    for (id, product) in snapshot.products {
+        parts[shard_for(&id, shards)].products.insert(id, product);
+    }
+    for (id, stock) in snapshot.stock {
+        parts[shard_for(&id, shards)].stock.insert(id, stock);
+    }
+    for (id, units) in snapshot.reserved {
+        parts[shard_for(&id, shards)].reserved.insert(id, units);
+    }
+    for event in snapshot.outbox.into_events() {
+        let shard = event.product_id().map_or(0, |id| shard_for(id, shards));
+        parts[shard].outbox.push(event);
+    }
+    parts
+}
+
+/// Send one request to every shard, returning the pending answers
+async fn fan_out<T>(
+    shards: &[mpsc::Sender<ProductRequest>],
+    mut request: impl FnMut(usize, ServiceResponse<T, ProductError>) -> ProductRequest,
+) -> Vec<oneshot::Receiver<ServiceResult<T, ProductError>>> {
+    let mut responses = Vec::with_capacity(shards.len());
+    for (i, shard) in shards.iter().enumerate() {
+        let (reply, response) = ServiceResponse::channel();
+        // A shard that is gone drops `reply`, which `gather` reports
+        let _ = shard.send(request(i, reply)).await;
+        responses.push(response);
+    }
+    responses
+}
+
+/// Wait for every shard's answer; any failure fails the whole request
+async fn gather<T>(
+    responses: Vec<oneshot::Receiver<ServiceResult<T, ProductError>>>,
+) -> ServiceResult<Vec<T>, ProductError> {
+    let mut results = Vec::with_capacity(responses.len());
+    for response in responses {
+        let result = response
+            .await
+            .map_err(|_| ProductError::DatabaseError("Shard stopped".to_string()))?;
+        results.push(result?);
+    }
+    Ok(results)
+}
+
+// =============================================================================
+// INGREDIENT 19: WORKER POOLS
+// =============================================================================
+
+/// The async function a [`WorkerPool`] runs for every request
+type PoolHandler<Req, Resp> = Arc<dyn Fn(Req) -> BoxFuture<'static, Resp> + Send + Sync>;
+
+#[derive(Debug)]
+pub enum WorkerPoolRequest<Req, Resp> {
+    /// Answered with the handler's response, or an error if it panicked
+    Run {
+        request: Req,
+        respond_to: oneshot::Sender<Result<Resp, String>>,
+    },
+    /// Finish the queued requests, stop every worker, then acknowledge
+    Shutdown { respond_to: oneshot::Sender<()> },
+}
+
+/// ## Ingredient 19: Worker Pool
+///
+/// **Pattern:** Not every action needs an owner. Work that touches no shared
+/// state (rendering a report, hashing a file) can run on any of N identical
+/// worker tasks. The pool's own mailbox hands requests out round-robin, and
+/// one [`WorkerPoolClient`] serves every caller, just like a service client.
+///
+/// A panicking handler loses only its own request; the worker carries on.
+///
+/// ```rust,ignore
+/// let (pool, reports) = WorkerPool::new(4, 100, |order: Order| async move {
+///     format!("{} x {} = {:.2}", order.quantity, order.product_id, order.total)
+/// });
+/// tokio::spawn(pool.run());
+/// let line = reports.call(order).await?;
+/// ```
+pub struct WorkerPool<Req, Resp> {
+    receiver: mpsc::Receiver<WorkerPoolRequest<Req, Resp>>,
+    workers: usize,
+    buffer_size: usize,
+    handler: PoolHandler<Req, Resp>,
+}
+
+impl<Req: Send + 'static, Resp: Send + 'static> WorkerPool<Req, Resp> {
+    pub fn new<F, Fut>(
+        workers: usize,
+        buffer_size: usize,
+        handler: F,
+    ) -> (Self, WorkerPoolClient<Req, Resp>)
+    where
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Resp> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let pool = Self {
+            receiver,
+            workers: workers.max(1),
+            buffer_size,
+            handler: Arc::new(move |request| Box::pin(handler(request))),
+        };
+        (pool, WorkerPoolClient { sender })
+    }
+
+    #[instrument(target = "actor::pool", name = "worker_pool", skip(self))]
+    pub async fn run(mut self) {
+        let (workers, handles): (Vec<_>, Vec<_>) = (0..self.workers)
+            .map(|worker| {
+                let (sender, receiver) = mpsc::channel(self.buffer_size);
+                let handle = tokio::spawn(
+                    Self::work(receiver, self.handler.clone())
+                        .instrument(tracing::debug_span!(target: POOL_TARGET, "worker", worker)),
+                );
+                (sender, handle)
+            })
+            .unzip();
+        info!(target: POOL_TARGET, workers = workers.len(), "WorkerPool starting");
+
+        let mut next = 0;
+        let mut shutdown_acks = Vec::new();
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                WorkerPoolRequest::Run {
+                    request,
+                    respond_to,
+                } => {
+                    // A dead worker drops the request, so the caller sees `channel closed`
+                    let _ = workers[next].send((request, respond_to)).await;
+                    next = (next + 1) % workers.len();
+                }
+                WorkerPoolRequest::Shutdown { respond_to } => {
+                    info!(
+                        target: POOL_TARGET,
+                        "WorkerPool shutting down, draining queued requests"
+                    );
+                    self.receiver.close();
+                    shutdown_acks.push(respond_to);
+                }
+            }
+        }
+
+        // Workers finish what they were handed once their mailboxes close
+        drop(workers);
+        for handle in handles {
+            let _ = handle.await;
+        }
+        for ack in shutdown_acks {
+            let _ = ack.send(());
+        }
+        info!(target: POOL_TARGET, "WorkerPool stopped");
+    }
+
+    async fn work(
+        mut receiver: mpsc::Receiver<(Req, oneshot::Sender<Result<Resp, String>>)>,
+        handler: PoolHandler<Req, Resp>,
+    ) {
+        while let Some((request, respond_to)) = receiver.recv().await {
+            match catch_unwind(std::pin::pin!(handler(request))).await {
+                Ok(response) => {
+                    let _ = respond_to.send(Ok(response));
+                }
+                Err(panic) => {
+                    let panic = panic_message(&panic);
+                    error!(
+                        target: POOL_TARGET,
+                        code = %EventCode::HandlerPanicked,
+                        panic,
+                        "Handler panicked, request failed"
+                    );
+                    let _ = respond_to.send(Err(format!("Handler panicked: {}", panic)));
+                }
+            }
+        }
+    }
+}
+
+pub struct WorkerPoolClient<Req, Resp> {
+    sender: mpsc::Sender<WorkerPoolRequest<Req, Resp>>,
+}
+
+// Manual impl: cloning the client must not require `Req: Clone`
+impl<Req, Resp> Clone for WorkerPoolClient<Req, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<Req: Send + 'static, Resp: Send + 'static> WorkerPoolClient<Req, Resp> {
+    /// Run `request` on the next worker in turn and wait for its answer
+    #[instrument(target = "actor::pool", skip(self, request))]
+    pub async fn call(&self, request: Req) -> Result<Resp, String> {
+        let (respond_to, response) = oneshot::channel();
+        self.sender
+            .send(WorkerPoolRequest::Run {
+                request,
+                respond_to,
+            })
+            .await
+            .map_err(|_| "Worker pool closed".to_string())?;
+        response.await.map_err(|e| e.to_string())?
+    }
+
+    #[instrument(target = "actor::pool", skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!(target: POOL_TARGET, "Sending shutdown request");
+        let (respond_to, response) = oneshot::channel();
+        self.sender
+            .send(WorkerPoolRequest::Shutdown { respond_to })
+            .await
+            .map_err(|_| "Worker pool closed".to_string())?;
+        response.await.map_err(|e| e.to_string())
+    }
+}
+
+// =============================================================================
+// INGREDIENT 20: PRIORITY MAILBOXES
+// =============================================================================
+
+/// The two ways into a [`PriorityMailbox`]. Build a client from either sender,
+/// e.g. `UserClient::new(lanes.high)`, to pick the priority of all its calls.
+#[derive(Debug)]
+pub struct PriorityLanes<R> {
+    pub normal: mpsc::Sender<R>,
+    pub high: mpsc::Sender<R>,
+}
+
+/// ## Ingredient 20: Priority Mailbox
+///
+/// **Pattern:** A channel is strictly FIFO, so a `Shutdown` sent behind ten
+/// thousand bulk writes waits for all of them. A `PriorityMailbox` sits in
+/// front of the actor like a [`Supervisor`] and owns the queue instead:
+/// requests on the high lane always go first, and urgent requests that come
+/// in on the normal lane (control messages such as `Shutdown` and `Drain`)
+/// skip the normal backlog. Everything else keeps its order.
+///
+/// Give the actor a mailbox of 1 so the backlog waits here, where it can be
+/// reordered, rather than in the actor's own channel. Owning the queue also
+/// makes [`PriorityMailbox::with_drop_oldest`] possible, which a plain channel
+/// sender cannot do.
+///
+/// ```rust,ignore
+/// let (service, direct) = UserService::new(1);
+/// let (mailbox, lanes) = PriorityMailbox::new(100, direct.sender, UserRequest::is_control);
+/// tokio::spawn(service.run());
+/// tokio::spawn(mailbox.run());
+/// let (bulk, admin) = (UserClient::new(lanes.normal), UserClient::new(lanes.high));
+/// ```
+pub struct PriorityMailbox<R> {
+    high: mpsc::Receiver<R>,
+    normal: mpsc::Receiver<R>,
+    backlog: VecDeque<R>,
+    capacity: usize,
+    actor: mpsc::Sender<R>,
+    urgent: fn(&R) -> bool,
+    drop_oldest: bool,
+}
+
+impl<R: Send + 'static> PriorityMailbox<R> {
+    /// `urgent` picks the normal-lane requests that skip the backlog
+    pub fn new(
+        buffer_size: usize,
+        actor: mpsc::Sender<R>,
+        urgent: fn(&R) -> bool,
+    ) -> (Self, PriorityLanes<R>) {
+        let (high_sender, high) = mpsc::channel(buffer_size);
+        let (normal_sender, normal) = mpsc::channel(buffer_size);
+        let mailbox = Self {
+            high,
+            normal,
+            backlog: VecDeque::new(),
+            capacity: buffer_size.max(1),
+            actor,
+            urgent,
+            drop_oldest: false,
+        };
+        let lanes = PriorityLanes {
+            normal: normal_sender,
+            high: high_sender,
+        };
+        (mailbox, lanes)
+    }
+
+    /// When the backlog is full, discard its oldest request instead of making
+    /// senders wait. The discarded caller sees `channel closed`.
+    pub fn with_drop_oldest(mut self) -> Self {
+        self.drop_oldest = true;
+        self
+    }
+
+    #[instrument(target = "actor::mailbox", name = "priority_mailbox", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: MAILBOX_TARGET, "PriorityMailbox started");
+
+        loop {
+            tokio::select! {
+                biased;
+                Some(msg) = self.high.recv() => {
+                    if self.actor.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                Some(msg) = self.normal.recv(),
+                    if self.drop_oldest || self.backlog.len() < self.capacity =>
+                {
+                    if (self.urgent)(&msg) {
+                        if self.actor.send(msg).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    if self.backlog.len() >= self.capacity && self.backlog.pop_front().is_some() {
+                        warn!(target: MAILBOX_TARGET, "Backlog full, oldest request dropped");
+                    }
+                    self.backlog.push_back(msg);
+                }
+                permit = self.actor.reserve(), if !self.backlog.is_empty() => {
+                    let Ok(permit) = permit else { break };
+                    if let Some(msg) = self.backlog.pop_front() {
+                        permit.send(msg);
+                    }
+                }
+                else => break,
+            }
+        }
+
+        if !self.backlog.is_empty() {
+            warn!(
+                target: MAILBOX_TARGET,
+                dropped = self.backlog.len(),
+                "Actor stopped, queued requests dropped"
+            );
+        }
+        info!(target: MAILBOX_TARGET, "PriorityMailbox stopped");
+    }
+}
+
+// =============================================================================
+// INGREDIENT 21: METRICS
+// =============================================================================
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+#[derive(Debug, Clone, Default)]
+struct RequestStats {
+    requests: u64,
+    errors: u64,
+    /// Cumulative, as Prometheus expects: requests no slower than each bound
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    seconds: f64,
+}
+
+impl RequestStats {
+    fn observe(&mut self, latency: Duration) {
+        self.requests += 1;
+        let seconds = latency.as_secs_f64();
+        self.seconds += seconds;
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Series {
+    help: &'static str,
+    /// Current value per rendered label set (`""` when unlabelled)
+    values: BTreeMap<String, i64>,
+}
+
+/// ## Ingredient 21: Request Metrics
+///
+/// **Pattern:** Metrics are taken at the two places every request passes
+/// through, so handlers need no code of their own:
+/// - **Generated client methods** record each call's variant, outcome and
+///   end-to-end latency (queueing, retries and handling included)
+/// - **Actor run loops** record how long the handler itself took, for every
+///   message however it was sent: hand-written client methods, timers and
+///   dynamic clients included, and sample the mailbox into the
+///   `actor_mailbox_depth`, `_capacity` and `_queue_time_ms` gauges
+///
+/// [`Metrics::render`] produces the Prometheus text format and
+/// [`serve_metrics`] exposes it for scraping:
+///
+/// ```text
+/// actor_requests_total{entity="User",request="GetUser"} 42
+/// actor_request_errors_total{entity="User",request="GetUser"} 1
+/// actor_request_duration_seconds_bucket{entity="User",request="GetUser",le="0.001"} 40
+/// actor_handler_duration_seconds_count{entity="User",request="ModifyUser"} 3
+/// actor_mailbox_depth{entity="User"} 7
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: std::sync::Mutex<BTreeMap<(&'static str, &'static str), RequestStats>>,
+    handlers: std::sync::Mutex<BTreeMap<(&'static str, &'static str), RequestStats>>,
+    counters: std::sync::Mutex<BTreeMap<&'static str, Series>>,
+    gauges: std::sync::Mutex<BTreeMap<&'static str, Series>>,
+}
+
+impl Metrics {
+    /// The process-wide registry that generated client methods record into
+    pub fn global() -> &'static Metrics {
+        static GLOBAL: std::sync::OnceLock<Metrics> = std::sync::OnceLock::new();
+        GLOBAL.get_or_init(Metrics::default)
+    }
+
+    /// Count one call of `variant` on the actor that takes `request` messages
+    pub fn record(
+        &self,
+        request: &'static str,
+        variant: &'static str,
+        latency: Duration,
+        ok: bool,
+    ) {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = requests.entry((request, variant)).or_default();
+        stats.observe(latency);
+        if !ok {
+            stats.errors += 1;
+        }
+    }
+
+    /// Time one handler run of `variant` inside the actor that takes `request` messages
+    pub fn record_handler(&self, request: &'static str, variant: &'static str, duration: Duration) {
+        let mut handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        handlers
+            .entry((request, variant))
+            .or_default()
+            .observe(duration);
+    }
+
+    /// Add `n` to the counter `name` for one label set, e.g. `entity="Product"`
+    pub fn add_counter(&self, name: &'static str, help: &'static str, labels: &str, n: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let counter = counters.entry(name).or_default();
+        counter.help = help;
+        *counter.values.entry(labels.to_string()).or_default() += n as i64;
+    }
+
+    /// Move the gauge `name` by `delta` for one label set, e.g. `product="p1"`
+    pub fn add_gauge(&self, name: &'static str, help: &'static str, labels: &str, delta: i64) {
+        let mut gauges = self.gauges.lock().unwrap_or_else(|e| e.into_inner());
+        let gauge = gauges.entry(name).or_default();
+        gauge.help = help;
+        *gauge.values.entry(labels.to_string()).or_default() += delta;
+    }
+
+    /// Set the gauge `name` to `value` for one label set, replacing the last value
+    pub fn set_gauge(&self, name: &'static str, help: &'static str, labels: &str, value: i64) {
+        let mut gauges = self.gauges.lock().unwrap_or_else(|e| e.into_inner());
+        let gauge = gauges.entry(name).or_default();
+        gauge.help = help;
+        gauge.values.insert(labels.to_string(), value);
+    }
+
+    /// Everything recorded so far, in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        use std::fmt::Write;
+
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        let labels = |(request, variant): &(&str, &str)| {
+            format!(
+                "entity=\"{}\",request=\"{}\"",
+                request.trim_end_matches("Request"),
+                variant
+            )
+        };
+        let mut out = String::new();
+
+        out.push_str("# HELP actor_requests_total Requests made through actor clients\n");
+        out.push_str("# TYPE actor_requests_total counter\n");
+        for (key, stats) in requests.iter() {
+            let _ = writeln!(
+                out,
+                "actor_requests_total{{{}}} {}",
+                labels(key),
+                stats.requests
+            );
+        }
+
+        out.push_str("# HELP actor_request_errors_total Requests that returned an error\n");
+        out.push_str("# TYPE actor_request_errors_total counter\n");
+        for (key, stats) in requests.iter() {
+            let _ = writeln!(
+                out,
+                "actor_request_errors_total{{{}}} {}",
+                labels(key),
+                stats.errors
+            );
+        }
+
+        let histogram =
+            |out: &mut String, name: &str, help: &str, all: &BTreeMap<_, RequestStats>| {
+                let _ = writeln!(out, "# HELP {} {}", name, help);
+                let _ = writeln!(out, "# TYPE {} histogram", name);
+                for (key, stats) in all.iter() {
+                    let labels = labels(key);
+                    for (count, bound) in stats.buckets.iter().zip(LATENCY_BUCKETS) {
+                        let _ = writeln!(
+                            out,
+                            "{}_bucket{{{},le=\"{}\"}} {}",
+                            name, labels, bound, count
+                        );
+                    }
+                    let _ = writeln!(
+                        out,
+                        "{}_bucket{{{},le=\"+Inf\"}} {}",
+                        name, labels, stats.requests
+                    );
+                    let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, stats.seconds);
+                    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, stats.requests);
+                }
+            };
+        histogram(
+            &mut out,
+            "actor_request_duration_seconds",
+            "End-to-end request latency",
+            &requests,
+        );
+        let handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        histogram(
+            &mut out,
+            "actor_handler_duration_seconds",
+            "Time actors spent handling each request",
+            &handlers,
+        );
+
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let gauges = self.gauges.lock().unwrap_or_else(|e| e.into_inner());
+        let all = [("counter", &*counters), ("gauge", &*gauges)];
+        for (name, kind, series) in all
+            .into_iter()
+            .flat_map(|(kind, named)| named.iter().map(move |(name, s)| (name, kind, s)))
+        {
+            let _ = writeln!(out, "# HELP {} {}", name, series.help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for (labels, value) in &series.values {
+                if labels.is_empty() {
+                    let _ = writeln!(out, "{} {}", name, value);
+                } else {
+                    let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Answer every connection on `listener` with the current metrics.
+///
+/// Deliberately minimal HTTP, enough for a Prometheus scraper or `curl`: the
+/// request itself is read and ignored.
+pub async fn serve_metrics(
+    listener: tokio::net::TcpListener,
+    metrics: &'static Metrics,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let addr = listener.local_addr()?;
+    info!(target: METRICS_TARGET, %addr, "Metrics exporter listening");
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let body = metrics.render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                debug!(target: METRICS_TARGET, %peer, error = %e, "Metrics scrape failed");
+            }
+        });
+    }
+}
+
+/// Business-level gauges kept up to date from domain events.
+///
+/// **Pattern:** A projection subscribes to the [event bus](EventBusService)
+/// and moves gauges by one event's worth at a time, so the numbers cost
+/// nothing to scrape and never require a snapshot of the actors. Gauges count
+/// from the moment the projection subscribes; events missed to lag are logged,
+/// not replayed.
+///
+/// ```text
+/// domain_users 3
+/// domain_orders{product="p1"} 2
+/// domain_units_reserved{product="p1"} 7
+/// ```
+pub struct DomainGauges {
+    events: broadcast::Receiver<DomainEvent>,
+    metrics: &'static Metrics,
+}
+
+impl DomainGauges {
+    pub fn new(events: broadcast::Receiver<DomainEvent>, metrics: &'static Metrics) -> Self {
+        Self { events, metrics }
+    }
+
+    /// Apply events until the bus shuts down
+    #[instrument(target = "actor::metrics", name = "domain_gauges", skip(self))]
+    pub async fn run(mut self) {
+        loop {
+            match self.events.recv().await {
+                Ok(event) => self.apply(&event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(target: METRICS_TARGET, missed, "Domain gauges lagged, events skipped");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    fn apply(&self, event: &DomainEvent) {
+        match event {
+            DomainEvent::UserCreated { .. } => {
+                self.metrics
+                    .add_gauge("domain_users", "Users created", "", 1);
+            }
+            DomainEvent::UserUpdated { .. } => {}
+            DomainEvent::StockReserved {
+                product_id,
+                quantity,
+            } => {
+                self.metrics.add_gauge(
+                    "domain_units_reserved",
+                    "Units of stock reserved per product",
+                    &format!("product=\"{}\"", product_id),
+                    i64::from(*quantity),
+                );
+            }
+            DomainEvent::StockReleased {
+                product_id,
+                quantity,
+            } => {
+                self.metrics.add_gauge(
+                    "domain_units_reserved",
+                    "Units of stock reserved per product",
+                    &format!("product=\"{}\"", product_id),
+                    -i64::from(*quantity),
+                );
+            }
+            DomainEvent::HoldExpired { product_id, .. } => {
+                self.metrics.add_gauge(
+                    "domain_holds_expired",
+                    "Stock holds that expired unconfirmed, per product",
+                    &format!("product=\"{}\"", product_id),
+                    1,
+                );
+            }
+            DomainEvent::OrderCreated { product_id, .. } => {
+                self.metrics.add_gauge(
+                    "domain_orders",
+                    "Orders placed per product",
+                    &format!("product=\"{}\"", product_id),
+                    1,
+                );
+            }
+        }
+    }
+}
+
+// =============================================================================
+// INGREDIENT 22: CONSISTENCY CHECKS
+// =============================================================================
+
+/// A cross-actor invariant that does not hold
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// An order names a user the user service doesn't know
+    OrderUserMissing { order_id: String, user_id: String },
+    /// An order names a product the product service doesn't know
+    OrderProductMissing {
+        order_id: String,
+        product_id: String,
+    },
+    /// Stock is tracked for a product that doesn't exist
+    OrphanedStock { product_id: String },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::OrderUserMissing { order_id, user_id } => {
+                write!(f, "Order {} references missing user {}", order_id, user_id)
+            }
+            Violation::OrderProductMissing {
+                order_id,
+                product_id,
+            } => write!(
+                f,
+                "Order {} references missing product {}",
+                order_id, product_id
+            ),
+            Violation::OrphanedStock { product_id } => {
+                write!(f, "Stock tracked for missing product {}", product_id)
+            }
+        }
+    }
+}
+
+/// ## Ingredient 22: Cross-Actor Consistency Checker
+///
+/// **Pattern:** Each actor guards its own invariants, but nothing guards the
+/// ones *between* actors, such as "every order's user exists". The checker is
+/// an outside observer: on every tick it snapshots the services through their
+/// public clients and reports each violation as a warning with
+/// [`EventCode::ConsistencyViolation`].
+///
+/// The snapshots are taken one after the other, not atomically. Orders are
+/// read first so that a user or product created in between can only make the
+/// check more lenient, never produce a false alarm.
+pub struct ConsistencyChecker {
+    users: UserClient,
+    products: ProductClient,
+    orders: OrderClient,
+    interval: Duration,
+}
+
+impl ConsistencyChecker {
+    pub fn new(
+        users: UserClient,
+        products: ProductClient,
+        orders: OrderClient,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            users,
+            products,
+            orders,
+            interval,
+        }
+    }
+
+    /// Check every invariant once
+    #[instrument(target = "actor::consistency", skip(self))]
+    pub async fn check(&self) -> Result<Vec<Violation>, String> {
+        let orders = self.orders.snapshot().await?.orders;
+        let users = self.users.snapshot().await?.users;
+        let products = self.products.snapshot().await?;
+
+        let mut order_ids: Vec<&String> = orders.keys().collect();
+        order_ids.sort();
+        let mut violations = Vec::new();
+        for order in order_ids.into_iter().map(|id| &orders[id]) {
+            if !users.contains_key(&order.user_id) {
+                violations.push(Violation::OrderUserMissing {
+                    order_id: order.id.clone(),
+                    user_id: order.user_id.clone(),
+                });
+            }
+            if !products.products.contains_key(&order.product_id) {
+                violations.push(Violation::OrderProductMissing {
+                    order_id: order.id.clone(),
+                    product_id: order.product_id.clone(),
+                });
+            }
+        }
+        let mut stocked: Vec<&String> = products.stock.keys().collect();
+        stocked.sort();
+        for product_id in stocked {
+            if !products.products.contains_key(product_id) {
+                violations.push(Violation::OrphanedStock {
+                    product_id: product_id.clone(),
+                });
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Check on every tick until one of the services stops answering
+    #[instrument(
+        target = "actor::consistency",
+        name = "consistency_checker",
+        skip(self)
+    )]
+    pub async fn run(self) {
+        info!(
+            target: CONSISTENCY_TARGET,
+            interval_ms = self.interval.as_millis() as u64,
+            "ConsistencyChecker starting"
+        );
+        let mut ticks = tokio::time::interval(self.interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticks.tick().await;
+            match self.check().await {
+                Ok(violations) if violations.is_empty() => {
+                    debug!(target: CONSISTENCY_TARGET, "All invariants hold");
+                }
+                Ok(violations) => {
+                    for violation in &violations {
+                        warn!(
+                            target: CONSISTENCY_TARGET,
+                            code = %EventCode::ConsistencyViolation,
+                            %violation,
+                            "Consistency violation"
+                        );
+                    }
+                }
+                Err(e) => {
+                    info!(target: CONSISTENCY_TARGET, error = %e, "Services unavailable, stopping");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// =============================================================================
+// INGREDIENT 23: HEALTH CHECKS
+// =============================================================================
+
+/// Anything [`HealthMonitor`] can probe, usually an actor's client
+pub trait HealthProbe: Send + Sync {
+    fn probe(&self) -> BoxFuture<'_, Result<(), String>>;
+}
+
+impl HealthProbe for UserClient {
+    fn probe(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.ping())
+    }
+}
+
+impl HealthProbe for ProductClient {
+    fn probe(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.ping())
+    }
+}
+
+impl HealthProbe for OrderClient {
+    fn probe(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(self.ping())
+    }
+}
+
+/// The latest probe results for one actor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActorHealth {
+    pub name: &'static str,
+    /// Round trip of the last successful ping
+    pub latency: Option<Duration>,
+    /// When the actor last answered a ping
+    pub last_seen: Option<tokio::time::Instant>,
+    /// Failed or timed-out pings since the last success
+    pub failures: u32,
+    pub last_error: Option<String>,
+}
+
+impl ActorHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.last_seen.is_some() && self.failures == 0
+    }
+}
+
+/// Aggregated health of every registered actor, in registration order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SystemHealth {
+    pub actors: Vec<ActorHealth>,
+}
+
+impl SystemHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.actors.iter().all(ActorHealth::is_healthy)
+    }
+
+    pub fn actor(&self, name: &str) -> Option<&ActorHealth> {
+        self.actors.iter().find(|actor| actor.name == name)
+    }
+}
+
+/// ## Ingredient 23: Health Monitoring
+///
+/// **Pattern:** Every actor answers a `Ping` control message from its normal
+/// mailbox, so a reply proves the run loop is alive *and* keeping up - a
+/// wedged handler or a flooded mailbox shows up as latency or a timeout. The
+/// monitor pings each registered actor on a fixed interval and publishes a
+/// [`SystemHealth`] on a watch channel, so readers always see the latest
+/// report without asking the monitor anything.
+///
+/// ```rust,ignore
+/// let (monitor, health) = system.health_monitor(Duration::from_secs(5));
+/// tokio::spawn(monitor.run());
+/// if !health.borrow().is_healthy() { /* alert */ }
+/// ```
+pub struct HealthMonitor {
+    probes: Vec<(&'static str, Box<dyn HealthProbe>)>,
+    interval: Duration,
+    timeout: Duration,
+    report: watch::Sender<SystemHealth>,
+}
+
+impl HealthMonitor {
+    /// A monitor pinging every `interval`, plus the receiver for its reports.
+    /// A ping unanswered after `timeout` counts as a failure.
+    pub fn new(interval: Duration, timeout: Duration) -> (Self, watch::Receiver<SystemHealth>) {
+        let (report, health) = watch::channel(SystemHealth::default());
+        let monitor = Self {
+            probes: Vec::new(),
+            interval,
+            timeout,
+            report,
+        };
+        (monitor, health)
+    }
+
+    /// Include an actor in every round, reported under `name`
+    pub fn register(mut self, name: &'static str, probe: impl HealthProbe + 'static) -> Self {
+        self.probes.push((name, Box::new(probe)));
+        self.report.send_modify(|health| {
+            health.actors.push(ActorHealth {
+                name,
+                latency: None,
+                last_seen: None,
+                failures: 0,
+                last_error: None,
+            });
+        });
+        self
+    }
+
+    /// Ping every registered actor once and publish the updated report
+    #[instrument(target = "actor::health", skip(self))]
+    pub async fn check(&self) {
+        for (index, (name, probe)) in self.probes.iter().enumerate() {
+            let started = tokio::time::Instant::now();
+            let result = match tokio::time::timeout(self.timeout, probe.probe()).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("No answer within {:?}", self.timeout)),
+            };
+            self.report.send_modify(|health| {
+                let actor = &mut health.actors[index];
+                match result {
+                    Ok(()) => {
+                        actor.latency = Some(started.elapsed());
+                        actor.last_seen = Some(tokio::time::Instant::now());
+                        actor.failures = 0;
+                        actor.last_error = None;
+                    }
+                    Err(e) => {
+                        warn!(
+                            target: HEALTH_TARGET,
+                            actor = name,
+                            error = %e,
+                            "Health check failed"
+                        );
+                        actor.failures += 1;
+                        actor.last_error = Some(e);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Check on every tick until every report receiver is dropped
+    #[instrument(target = "actor::health", name = "health_monitor", skip(self))]
+    pub async fn run(self) {
+        info!(
+            target: HEALTH_TARGET,
+            actors = self.probes.len(),
+            interval_ms = self.interval.as_millis() as u64,
+            "HealthMonitor starting"
+        );
+        let mut ticks = tokio::time::interval(self.interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = self.report.closed() => break,
+                _ = ticks.tick() => self.check().await,
+            }
+        }
+        info!(target: HEALTH_TARGET, "HealthMonitor stopped");
+    }
+}
+
+// =============================================================================
+// INGREDIENT 24: AUDIT LOG
+// =============================================================================
+
+/// What a mutation did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    /// A domain operation that isn't plain CRUD, e.g. `"ReserveStock"`
+    Action(&'static str),
+}
+
+/// One mutation, as kept by the [`AuditService`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    /// Entity type, e.g. `"User"`
+    pub entity: &'static str,
+    pub id: String,
+    pub action: AuditAction,
+    /// The request that caused the change
+    pub request_id: Option<RequestId>,
+    /// Unix seconds, stamped by the audit actor when the record arrives
+    pub recorded_at: u64,
+    /// Summary of the entity before the change, `None` for a create
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl AuditRecord {
+    /// A record attributed to the request currently being handled
+    pub fn new(
+        entity: &'static str,
+        id: impl Into<String>,
+        action: AuditAction,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Self {
+        Self {
+            entity,
+            id: id.into(),
+            action,
+            request_id: RequestId::current(),
+            recorded_at: 0,
+            before,
+            after,
+        }
+    }
+}
+
+/// Selects audit records; every criterion that is set must match
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    pub entity: Option<&'static str>,
+    pub id: Option<String>,
+    /// Inclusive lower bound on `recorded_at`
+    pub since: Option<u64>,
+    /// Exclusive upper bound on `recorded_at`
+    pub until: Option<u64>,
+}
+
+impl AuditQuery {
+    /// The history of one entity
+    pub fn entity(entity: &'static str, id: impl Into<String>) -> Self {
+        Self {
+            entity: Some(entity),
+            id: Some(id.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Only records with `since <= recorded_at < until`
+    pub fn between(mut self, since: u64, until: u64) -> Self {
+        self.since = Some(since);
+        self.until = Some(until);
+        self
+    }
+
+    fn matches(&self, record: &AuditRecord) -> bool {
+        // `Option::iter().all` holds for `None`, i.e. an unset criterion
+        self.entity.iter().all(|&entity| record.entity == entity)
+            && self.id.iter().all(|id| &record.id == id)
+            && self.since.iter().all(|&since| record.recorded_at >= since)
+            && self.until.iter().all(|&until| record.recorded_at < until)
+    }
+}
+
+/// Audit log error types
+#[derive(Debug, Clone)]
+pub enum AuditError {
+    Closed,
+}
+
+impl std::fmt::Display for AuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditError::Closed => write!(f, "Audit log closed"),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+#[derive(Debug)]
+pub enum AuditRequest {
+    Record {
+        record: AuditRecord,
+    },
+    /// Matching records, oldest first
+    Query {
+        query: AuditQuery,
+        respond_to: ServiceResponse<Vec<AuditRecord>, AuditError>,
+    },
+    Shutdown,
+}
+
+/// ## Ingredient 24: Audit Log
+///
+/// **Pattern:** Services that are given an [`AuditClient`] send a record for
+/// every successful mutation, fire-and-forget like event publishing. Unlike a
+/// [`DomainEvent`], a record says who asked (the [`RequestId`]), when, and what
+/// the entity looked like before and after, so it answers "how did this get
+/// into this state?" after the fact.
+///
+/// The log is append-only and in memory; records arrive in the order the
+/// services committed their changes, per service. Each entity type keeps its
+/// last `retention` records, so a busy entity cannot push out the history of a
+/// quiet one, and one that must be kept forever can say so.
+pub struct AuditService {
+    receiver: mpsc::Receiver<AuditRequest>,
+    /// Every kept record by arrival number, so queries see them oldest first
+    records: BTreeMap<u64, AuditRecord>,
+    next_record: u64,
+    /// Arrival numbers of each entity type's kept records, oldest first
+    by_entity: HashMap<&'static str, VecDeque<u64>>,
+    retention: usize,
+    entity_retention: HashMap<&'static str, usize>,
+    clock: SharedClock,
+}
+
+impl AuditService {
+    pub fn new(buffer_size: usize) -> (Self, AuditClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            records: BTreeMap::new(),
+            next_record: 0,
+            by_entity: HashMap::new(),
+            retention: ActorConfig::default().audit_retention,
+            entity_retention: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        };
+        (service, AuditClient::new(sender))
+    }
+
+    /// Keep at most this many records per entity type
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Keep at most this many records of `entity`, e.g. `usize::MAX` for orders
+    pub fn with_entity_retention(mut self, entity: &'static str, retention: usize) -> Self {
+        self.entity_retention.insert(entity, retention);
+        self
+    }
+
+    /// Store `record`, dropping its entity type's oldest records past retention
+    fn keep(&mut self, record: AuditRecord) {
+        let retention = self
+            .entity_retention
+            .get(record.entity)
+            .copied()
+            .unwrap_or(self.retention);
+        let kept = self.by_entity.entry(record.entity).or_default();
+        kept.push_back(self.next_record);
+        self.records.insert(self.next_record, record);
+        self.next_record += 1;
+        while kept.len() > retention {
+            if let Some(oldest) = kept.pop_front() {
+                self.records.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    #[instrument(target = "actor::audit", name = "audit_service", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: AUDIT_TARGET, "AuditService starting");
+
+        while let Some(msg) = self.receiver.recv().await {
+            match msg {
+                AuditRequest::Record { mut record } => {
+                    record.recorded_at = self.clock.unix_secs();
+                    debug!(
+                        target: AUDIT_TARGET,
+                        entity = record.entity,
+                        id = %record.id,
+                        action = ?record.action,
+                        "Mutation recorded"
+                    );
+                    self.keep(record);
+                }
+                AuditRequest::Query { query, respond_to } => {
+                    let records = self
+                        .records
+                        .values()
+                        .filter(|record| query.matches(record))
+                        .cloned()
+                        .collect();
+                    let _ = respond_to.send(Ok(records));
+                }
+                AuditRequest::Shutdown => {
+                    info!(target: AUDIT_TARGET, "AuditService shutting down");
+                    break;
+                }
+            }
+        }
+
+        info!(target: AUDIT_TARGET, records = self.records.len(), "AuditService stopped");
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditClient {
+    sender: mpsc::Sender<AuditRequest>,
+    policy: ClientPolicy,
+}
+
+impl AuditClient {
+    pub fn new(sender: mpsc::Sender<AuditRequest>) -> Self {
+        Self {
+            sender,
+            policy: ClientPolicy::default(),
+        }
+    }
+
+    /// Fire-and-forget, like [`EventBusClient::publish`]: a closed audit log
+    /// is logged, never surfaced to the mutation being audited
+    #[instrument(skip(self, record))]
+    pub async fn record(&self, record: AuditRecord) {
+        if let Err(e) = self.sender.send(AuditRequest::Record { record }).await {
+            warn!(error = %e, "Audit log unavailable, record dropped");
+        }
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        self.sender
+            .send(AuditRequest::Shutdown)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+client_method!(AuditClient => fn query(query: AuditQuery) -> Vec<AuditRecord> as AuditRequest::Query);
+
+// =============================================================================
+// INGREDIENT 25: DELAYED DELIVERY
+// =============================================================================
+
+/// Identifies one pending timer in a [`SchedulerService`]
+pub type TimerId = u64;
+
+/// What a timer does when it fires: usually "put this request in that mailbox"
+type Delivery = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// What a recurring job does each time it is due, typically a call on a client
+type RecurringJob = Box<dyn FnMut() -> BoxFuture<'static, ()> + Send>;
+
+/// When a recurring job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    /// Every `Duration`, starting one period after the job is scheduled.
+    /// The period must not be zero.
+    Every(Duration),
+    /// On every minute the expression matches, by wall-clock time
+    Cron(CronSchedule),
+}
+
+/// A five-field cron expression: minute, hour, day of month, month, day of week.
+///
+/// Fields take `*`, single values, ranges (`9-17`), lists (`1,15`) and steps
+/// (`*/15`, `0-30/10`). Day of week runs 0-6 from Sunday, and 7 is Sunday too.
+/// As in classic cron, restricting both day fields matches a day if either
+/// does. Times are UTC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    either_day: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "cron expression needs 5 fields, got {}",
+                fields.len()
+            ));
+        };
+        let weekdays = parse_cron_field(weekday, 0, 7)?;
+        Ok(Self {
+            minutes: parse_cron_field(minute, 0, 59)?,
+            hours: parse_cron_field(hour, 0, 23)?,
+            days: parse_cron_field(day, 1, 31)?,
+            months: parse_cron_field(month, 1, 12)?,
+            // Fold 7 onto 0 so Sunday has a single bit
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            either_day: day != "*" && weekday != "*",
+        })
+    }
+
+    /// The first matching minute strictly after `unix_secs`, or `None` if the
+    /// expression can never match (e.g. February 30th)
+    pub fn next_after(&self, unix_secs: u64) -> Option<u64> {
+        const DAY: u64 = 24 * 60 * 60;
+        let has = |set: u64, value: u64| set & (1 << value) != 0;
+        let mut t = (unix_secs / 60 + 1) * 60;
+        // Every satisfiable expression matches within 28 years (the leap-day cycle)
+        let give_up = t + 28 * 366 * DAY;
+
+        while t < give_up {
+            let days = t / DAY;
+            let (year, month, day) = civil_from_days(days);
+            // 1970-01-01 was a Thursday
+            let weekday = (days + 4) % 7;
+            if !has(self.months, month) {
+                t = if month == 12 {
+                    days_from_civil(year + 1, 1)
+                } else {
+                    days_from_civil(year, month + 1)
+                } * DAY;
+                continue;
+            }
+            let day_ok = if self.either_day {
+                has(self.days, day) || has(self.weekdays, weekday)
+            } else {
+                has(self.days, day) && has(self.weekdays, weekday)
+            };
+            if !day_ok {
+                t = (days + 1) * DAY;
+                continue;
+            }
+            if !has(self.hours, t / 3600 % 24) {
+                t = (t / 3600 + 1) * 3600;
+                continue;
+            }
+            if !has(self.minutes, t / 60 % 60) {
+                t += 60;
+                continue;
+            }
+            return Some(t);
+        }
+        None
+    }
+}
+
+/// One cron field as a bit set of the values it allows
+fn parse_cron_field(field: &str, min: u64, max: u64) -> Result<u64, String> {
+    let invalid = || format!("invalid cron field '{}' (allowed {}-{})", field, min, max);
+    let number = |s: &str| s.parse::<u64>().map_err(|_| invalid());
+    let mut set = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, number(step)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/10` means "from 5 to the end, every 10"
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+/// Year, month and day for a count of days since 1970-01-01 (Howard Hinnant's algorithm)
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days since 1970-01-01 for the first day of `month` in `year`
+fn days_from_civil(year: u64, month: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year % 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// A pending entry in the scheduler's queue
+enum Timer {
+    Once(Delivery),
+    Recurring {
+        recurrence: Recurrence,
+        job: RecurringJob,
+        /// The last run, so a slow job is skipped rather than run twice at once
+        running: Option<tokio::task::JoinHandle<()>>,
+    },
+}
+
+/// Scheduler error types
+#[derive(Debug, Clone)]
+pub enum SchedulerError {
+    Closed,
+    /// `Recurrence::Every` with a zero period would fire continuously
+    ZeroPeriod,
+}
+
+impl std::fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerError::Closed => write!(f, "Scheduler closed"),
+            SchedulerError::ZeroPeriod => write!(f, "Recurring period must be greater than zero"),
+        }
+    }
+}
+
+impl std::error::Error for SchedulerError {}
+
+pub enum SchedulerRequest {
+    Schedule {
+        delay: Duration,
+        delivery: Delivery,
+        respond_to: ServiceResponse<TimerId, SchedulerError>,
+    },
+    /// Run `job` on `recurrence` until cancelled; cancel with the returned ID
+    Recurring {
+        recurrence: Recurrence,
+        job: RecurringJob,
+        respond_to: ServiceResponse<TimerId, SchedulerError>,
+    },
+    /// `true` if the timer was still pending, `false` if it had fired or was unknown
+    Cancel {
+        timer: TimerId,
+        respond_to: ServiceResponse<bool, SchedulerError>,
+    },
+    /// Number of timers that have not fired yet
+    Pending {
+        respond_to: ServiceResponse<usize, SchedulerError>,
+    },
+    Shutdown,
+}
+
+impl SchedulerRequest {
+    fn variant(&self) -> &'static str {
+        match self {
+            SchedulerRequest::Schedule { .. } => "Schedule",
+            SchedulerRequest::Recurring { .. } => "Recurring",
+            SchedulerRequest::Cancel { .. } => "Cancel",
+            SchedulerRequest::Pending { .. } => "Pending",
+            SchedulerRequest::Shutdown => "Shutdown",
+        }
+    }
+}
+
+impl std::fmt::Debug for SchedulerRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchedulerRequest::Schedule { delay, .. } => {
+                f.debug_struct("Schedule").field("delay", delay).finish()
+            }
+            SchedulerRequest::Recurring { recurrence, .. } => f
+                .debug_struct("Recurring")
+                .field("recurrence", recurrence)
+                .finish(),
+            SchedulerRequest::Cancel { timer, .. } => {
+                f.debug_struct("Cancel").field("timer", timer).finish()
+            }
+            SchedulerRequest::Pending { .. } => f.write_str("Pending"),
+            SchedulerRequest::Shutdown => f.write_str("Shutdown"),
+        }
+    }
+}
+
+/// ## Ingredient 25: Delayed Delivery
+///
+/// **Pattern:** "Do this later" is a message to a timer actor rather than a
+/// `tokio::spawn(sleep(..))` per call site. The scheduler owns every pending
+/// timer, so each one can be cancelled by ID, counted, and dropped in one place
+/// at shutdown, and the target actor only ever sees an ordinary request once
+/// it is due.
+///
+/// ```rust
+/// let (respond_to, _) = ServiceResponse::channel();
+/// let timer = product_client
+///     .send_after(&scheduler, Duration::from_secs(15 * 60), ProductRequest::Ping { respond_to })
+///     .await?;
+/// timer.cancel().await?;
+/// ```
+///
+/// Delivery goes through the target's mailbox like any other request, and the
+/// reply is discarded. Timers live in memory only: a restarted scheduler starts
+/// empty.
+///
+/// **Recurring Jobs:** The same queue runs periodic work, on an interval or a
+/// [`CronSchedule`]. A job is a callback, usually a call on some client, and is
+/// cancelled like any timer; a run still in progress when the next one is due
+/// makes the scheduler skip that tick instead of stacking runs.
+pub struct SchedulerService {
+    receiver: mpsc::Receiver<SchedulerRequest>,
+    /// Pending deliveries in firing order; the ID breaks ties between equal deadlines
+    timers: BTreeMap<(tokio::time::Instant, TimerId), Timer>,
+    deadlines: HashMap<TimerId, tokio::time::Instant>,
+    next_timer: TimerId,
+    clock: SharedClock,
+}
+
+impl SchedulerService {
+    pub fn new(buffer_size: usize) -> (Self, SchedulerClient) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            timers: BTreeMap::new(),
+            deadlines: HashMap::new(),
+            next_timer: 1,
+            clock: Arc::new(SystemClock),
+        };
+        (service, SchedulerClient::new(sender))
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    #[instrument(target = "actor::scheduler", name = "scheduler_service", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: SCHEDULER_TARGET, "SchedulerService starting");
+
+        loop {
+            let next_due = self.timers.keys().next().map(|&(due, _)| due);
+            tokio::select! {
+                msg = self.receiver.recv() => match msg {
+                    Some(SchedulerRequest::Shutdown) | None => break,
+                    Some(msg) => {
+                        let variant = msg.variant();
+                        let started = tokio::time::Instant::now();
+                        self.handle_message(msg);
+                        let elapsed = started.elapsed();
+                        Metrics::global().record_handler("SchedulerRequest", variant, elapsed);
+                    }
+                },
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(|| self.clock.now())),
+                    if next_due.is_some() => self.fire_due(),
+            }
+        }
+
+        info!(
+            target: SCHEDULER_TARGET,
+            dropped = self.timers.len(),
+            "SchedulerService stopped"
+        );
+    }
+
+    fn handle_message(&mut self, msg: SchedulerRequest) {
+        match msg {
+            SchedulerRequest::Schedule {
+                delay,
+                delivery,
+                respond_to,
+            } => {
+                let timer = self.next_timer;
+                self.next_timer += 1;
+                self.enqueue(timer, delay, Timer::Once(delivery));
+                let _ = respond_to.send(Ok(timer));
+            }
+            SchedulerRequest::Recurring {
+                recurrence,
+                job,
+                respond_to,
+            } => {
+                if recurrence == Recurrence::Every(Duration::ZERO) {
+                    send_error!(respond_to, SchedulerError::ZeroPeriod);
+                }
+                let timer = self.next_timer;
+                self.next_timer += 1;
+                let job = Timer::Recurring {
+                    recurrence,
+                    job,
+                    running: None,
+                };
+                self.reschedule(timer, job);
+                let _ = respond_to.send(Ok(timer));
+            }
+            SchedulerRequest::Cancel { timer, respond_to } => {
+                let cancelled = match self.deadlines.remove(&timer) {
+                    Some(due) => self.timers.remove(&(due, timer)).is_some(),
+                    None => false,
+                };
+                debug!(target: SCHEDULER_TARGET, timer, cancelled, "Timer cancel requested");
+                let _ = respond_to.send(Ok(cancelled));
+            }
+            SchedulerRequest::Pending { respond_to } => {
+                let _ = respond_to.send(Ok(self.timers.len()));
+            }
+            SchedulerRequest::Shutdown => {}
+        }
+    }
+
+    fn enqueue(&mut self, timer: TimerId, delay: Duration, entry: Timer) {
+        let due = self.clock.now() + delay;
+        self.timers.insert((due, timer), entry);
+        self.deadlines.insert(timer, due);
+        debug!(
+            target: SCHEDULER_TARGET,
+            timer,
+            delay_ms = delay.as_millis(),
+            "Timer scheduled"
+        );
+    }
+
+    /// Queue a recurring job for its next run, or drop it if there is none
+    fn reschedule(&mut self, timer: TimerId, entry: Timer) {
+        let Timer::Recurring { recurrence, .. } = &entry else {
+            return;
+        };
+        let delay = match recurrence {
+            Recurrence::Every(period) => Some(*period),
+            Recurrence::Cron(schedule) => {
+                let wall = self
+                    .clock
+                    .wall_time()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                schedule
+                    .next_after(wall.as_secs())
+                    .map(|next| Duration::from_secs(next).saturating_sub(wall))
+            }
+        };
+        match delay {
+            // At least 1ms, so `fire_due` never finds the job due again in the same pass
+            Some(delay) => self.enqueue(timer, delay.max(Duration::from_millis(1)), entry),
+            None => {
+                warn!(target: SCHEDULER_TARGET, timer, "Cron schedule never matches, job dropped")
+            }
+        }
+    }
+
+    /// Run every timer whose deadline has passed. Each run gets its own task so
+    /// a full target mailbox or a slow job can't hold up the other timers.
+    fn fire_due(&mut self) {
+        let now = self.clock.now();
+        while let Some(entry) = self.timers.first_entry() {
+            let (due, timer) = *entry.key();
+            if due > now {
+                break;
+            }
+            self.deadlines.remove(&timer);
+            match entry.remove() {
+                Timer::Once(delivery) => {
+                    debug!(target: SCHEDULER_TARGET, timer, "Timer fired");
+                    tokio::spawn(delivery());
+                }
+                Timer::Recurring {
+                    recurrence,
+                    mut job,
+                    mut running,
+                } => {
+                    if running.as_ref().is_some_and(|run| !run.is_finished()) {
+                        warn!(
+                            target: SCHEDULER_TARGET,
+                            timer,
+                            "Previous run still busy, tick skipped"
+                        );
+                    } else {
+                        debug!(target: SCHEDULER_TARGET, timer, "Recurring job started");
+                        running = Some(tokio::spawn(job()));
+                    }
+                    let next = Timer::Recurring {
+                        recurrence,
+                        job,
+                        running,
+                    };
+                    self.reschedule(timer, next);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SchedulerClient {
+    sender: mpsc::Sender<SchedulerRequest>,
+    policy: ClientPolicy,
+}
+
+impl SchedulerClient {
+    pub fn new(sender: mpsc::Sender<SchedulerRequest>) -> Self {
+        Self {
+            sender,
+            policy: ClientPolicy::default(),
+        }
+    }
+
+    /// Put `request` in `mailbox` once `delay` has passed. Service clients wrap
+    /// this as `send_after`, which is usually the more convenient entry point.
+    #[instrument(skip(self, mailbox, request))]
+    pub async fn send_after<R: Send + 'static>(
+        &self,
+        delay: Duration,
+        mailbox: mpsc::Sender<R>,
+        request: R,
+    ) -> Result<TimerHandle, String> {
+        let delivery: Delivery = Box::new(move || {
+            Box::pin(async move {
+                if mailbox.send(request).await.is_err() {
+                    warn!(target: SCHEDULER_TARGET, "Timer target is gone, request dropped");
+                }
+            })
+        });
+        let (respond_to, response) = ServiceResponse::channel();
+        self.policy
+            .send(
+                &self.sender,
+                SchedulerRequest::Schedule {
+                    delay,
+                    delivery,
+                    respond_to,
+                },
+            )
+            .await?;
+        let id = response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        Ok(TimerHandle {
+            id,
+            scheduler: self.clone(),
+        })
+    }
+
+    /// Run `job` on `recurrence` until the returned handle is cancelled
+    #[instrument(skip(self, job))]
+    pub async fn recurring<F, Fut>(
+        &self,
+        recurrence: Recurrence,
+        mut job: F,
+    ) -> Result<TimerHandle, String>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let job: RecurringJob = Box::new(move || Box::pin(job()));
+        let (respond_to, response) = ServiceResponse::channel();
+        self.policy
+            .send(
+                &self.sender,
+                SchedulerRequest::Recurring {
+                    recurrence,
+                    job,
+                    respond_to,
+                },
+            )
+            .await?;
+        let id = response
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        Ok(TimerHandle {
+            id,
+            scheduler: self.clone(),
+        })
+    }
+
+    #[instrument(skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!("Sending shutdown request");
+        self.sender
+            .send(SchedulerRequest::Shutdown)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+client_method!(SchedulerClient => fn cancel(timer: TimerId) -> bool as SchedulerRequest::Cancel);
+client_method!(SchedulerClient => fn pending() -> usize as SchedulerRequest::Pending);
+
+/// A scheduled delivery or recurring job. Dropping the handle does not cancel it.
+#[derive(Clone)]
+pub struct TimerHandle {
+    id: TimerId,
+    scheduler: SchedulerClient,
+}
+
+impl TimerHandle {
+    pub fn id(&self) -> TimerId {
+        self.id
+    }
+
+    /// Stop the delivery or job; `false` if a one-off delivery already fired
+    pub async fn cancel(&self) -> Result<bool, String> {
+        self.scheduler.cancel(self.id).await
+    }
+}
+
+impl std::fmt::Debug for TimerHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimerHandle").field("id", &self.id).finish()
+    }
+}
+
+// =============================================================================
+// INGREDIENT 26: JOB QUEUE
+// =============================================================================
+
+/// Identifies one job in a [`JobService`]
+pub type JobId = u64;
+
+/// Where a job is in its lifecycle
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobStatus<T> {
+    /// Waiting for a free slot
+    Queued,
+    Running,
+    Done(T),
+    /// The job returned an error or panicked
+    Failed(String),
+}
+
+impl<T> JobStatus<T> {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done(_) | JobStatus::Failed(_))
+    }
+}
+
+/// The work behind a job, boxed so the queue can hold any future
+type JobWork<T> = BoxFuture<'static, Result<T, String>>;
+
+pub enum JobRequest<T> {
+    Submit {
+        work: JobWork<T>,
+        respond_to: oneshot::Sender<JobId>,
+    },
+    /// Current status; `None` for an unknown (or long-forgotten) job
+    Status {
+        id: JobId,
+        respond_to: oneshot::Sender<Option<JobStatus<T>>>,
+    },
+    /// Answered once the job has finished, or at once if it already has
+    Await {
+        id: JobId,
+        respond_to: oneshot::Sender<Option<JobStatus<T>>>,
+    },
+    Shutdown {
+        respond_to: oneshot::Sender<()>,
+    },
+}
+
+impl<T> JobRequest<T> {
+    fn variant(&self) -> &'static str {
+        match self {
+            JobRequest::Submit { .. } => "Submit",
+            JobRequest::Status { .. } => "Status",
+            JobRequest::Await { .. } => "Await",
+            JobRequest::Shutdown { .. } => "Shutdown",
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for JobRequest<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobRequest::Submit { .. } => f.write_str("Submit"),
+            JobRequest::Status { id, .. } => f.debug_struct("Status").field("id", id).finish(),
+            JobRequest::Await { id, .. } => f.debug_struct("Await").field("id", id).finish(),
+            JobRequest::Shutdown { .. } => f.write_str("Shutdown"),
+        }
+    }
+}
+
+/// ## Ingredient 26: Job Queue
+///
+/// **Pattern:** "Return a job ID, work continues" needs somebody to own the
+/// job after the handler returns. The `JobService` does: it queues submitted
+/// work, runs at most `max_running` jobs at a time, and records each job's
+/// status and result, so any holder of the ID can poll it with
+/// [`JobClient::get_job_status`] or wait for it with [`JobClient::await_job`].
+///
+/// ```rust,ignore
+/// let (jobs, job_client) = JobService::new(4, 100);
+/// tokio::spawn(jobs.run());
+/// let client = user_client.clone();
+/// let id = job_client.submit(async move { client.generate_report(user_id).await }).await?;
+/// // ...hand `id` back to the caller, who later asks:
+/// let status = job_client.await_job(id).await?;
+/// ```
+///
+/// A panicking job fails on its own; the service carries on. Results are kept
+/// in memory for the last `retention` finished jobs. Shutdown abandons queued
+/// and running jobs, so their waiters see `channel closed`.
+pub struct JobService<T> {
+    receiver: mpsc::Receiver<JobRequest<T>>,
+    max_running: usize,
+    retention: usize,
+    next_id: JobId,
+    jobs: HashMap<JobId, JobStatus<T>>,
+    queue: VecDeque<(JobId, JobWork<T>)>,
+    running: tokio::task::JoinSet<(JobId, Result<T, String>)>,
+    /// Finished jobs, oldest first, for dropping results past `retention`
+    finished: VecDeque<JobId>,
+    waiters: HashMap<JobId, Vec<oneshot::Sender<Option<JobStatus<T>>>>>,
+}
+
+impl<T: Clone + Send + 'static> JobService<T> {
+    pub fn new(max_running: usize, buffer_size: usize) -> (Self, JobClient<T>) {
+        let (sender, receiver) = mpsc::channel(buffer_size);
+        let service = Self {
+            receiver,
+            max_running: max_running.max(1),
+            retention: 1000,
+            next_id: 1,
+            jobs: HashMap::new(),
+            queue: VecDeque::new(),
+            running: tokio::task::JoinSet::new(),
+            finished: VecDeque::new(),
+            waiters: HashMap::new(),
+        };
+        (service, JobClient { sender })
+    }
+
+    /// Keep the results of at most this many finished jobs
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    #[instrument(target = "actor::jobs", name = "job_service", skip(self))]
+    pub async fn run(mut self) {
+        info!(target: JOB_TARGET, max_running = self.max_running, "JobService starting");
+
+        let mut shutdown_ack = None;
+        loop {
+            tokio::select! {
+                msg = self.receiver.recv() => match msg {
+                    Some(JobRequest::Shutdown { respond_to }) => {
+                        shutdown_ack = Some(respond_to);
+                        break;
+                    }
+                    Some(msg) => {
+                        let variant = msg.variant();
+                        let started = tokio::time::Instant::now();
+                        self.handle_message(msg);
+                        Metrics::global().record_handler("JobRequest", variant, started.elapsed());
+                    }
+                    None => break,
+                },
+                Some(joined) = self.running.join_next(), if !self.running.is_empty() => {
+                    // Panics are caught inside the job, so only an abort lands in `Err`
+                    if let Ok((id, result)) = joined {
+                        self.finish(id, result);
+                    }
+                    self.start_queued();
+                }
+            }
+        }
+
+        info!(
+            target: JOB_TARGET,
+            queued = self.queue.len(),
+            running = self.running.len(),
+            "JobService stopped, abandoning unfinished jobs"
+        );
+        self.running.shutdown().await;
+        if let Some(ack) = shutdown_ack {
+            let _ = ack.send(());
+        }
+    }
+
+    fn handle_message(&mut self, msg: JobRequest<T>) {
+        match msg {
+            JobRequest::Submit { work, respond_to } => {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.jobs.insert(id, JobStatus::Queued);
+                self.queue.push_back((id, work));
+                debug!(target: JOB_TARGET, job = id, queued = self.queue.len(), "Job queued");
+                let _ = respond_to.send(id);
+                self.start_queued();
+            }
+            JobRequest::Status { id, respond_to } => {
+                let _ = respond_to.send(self.jobs.get(&id).cloned());
+            }
+            JobRequest::Await { id, respond_to } => match self.jobs.get(&id) {
+                Some(status) if !status.is_finished() => {
+                    self.waiters.entry(id).or_default().push(respond_to);
+                }
+                status => {
+                    let _ = respond_to.send(status.cloned());
+                }
+            },
+            JobRequest::Shutdown { .. } => {}
+        }
+    }
+
+    /// Move jobs from the queue into free running slots
+    fn start_queued(&mut self) {
+        while self.running.len() < self.max_running {
+            let Some((id, work)) = self.queue.pop_front() else {
+                return;
+            };
+            self.jobs.insert(id, JobStatus::Running);
+            debug!(target: JOB_TARGET, job = id, "Job started");
+            self.running.spawn(
+                async move {
+                    let result = match catch_unwind(std::pin::pin!(work)).await {
+                        Ok(result) => result,
+                        Err(panic) => Err(format!("Job panicked: {}", panic_message(&panic))),
+                    };
+                    (id, result)
+                }
+                .in_current_span(),
+            );
+        }
+    }
+
+    fn finish(&mut self, id: JobId, result: Result<T, String>) {
+        let status = match result {
+            Ok(output) => {
+                info!(target: JOB_TARGET, job = id, "Job done");
+                JobStatus::Done(output)
+            }
+            Err(error) => {
+                warn!(target: JOB_TARGET, job = id, error = %error, "Job failed");
+                JobStatus::Failed(error)
+            }
+        };
+        for waiter in self.waiters.remove(&id).unwrap_or_default() {
+            let _ = waiter.send(Some(status.clone()));
+        }
+        self.jobs.insert(id, status);
+
+        self.finished.push_back(id);
+        while self.finished.len() > self.retention {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.jobs.remove(&oldest);
+            }
+        }
+    }
+}
+
+pub struct JobClient<T> {
+    sender: mpsc::Sender<JobRequest<T>>,
+}
+
+// Manual impl: cloning the client must not require `T: Clone`
+impl<T> Clone for JobClient<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> JobClient<T> {
+    /// Queue `work` and return its ID at once
+    #[instrument(target = "actor::jobs", skip(self, work))]
+    pub async fn submit<F>(&self, work: F) -> Result<JobId, String>
+    where
+        F: std::future::Future<Output = Result<T, String>> + Send + 'static,
+    {
+        let (respond_to, response) = oneshot::channel();
+        self.request(JobRequest::Submit {
+            work: Box::pin(work),
+            respond_to,
+        })
+        .await?;
+        response.await.map_err(|e| e.to_string())
+    }
+
+    /// Where the job is now; `None` if the ID is unknown
+    #[instrument(target = "actor::jobs", skip(self))]
+    pub async fn get_job_status(&self, id: JobId) -> Result<Option<JobStatus<T>>, String> {
+        let (respond_to, response) = oneshot::channel();
+        self.request(JobRequest::Status { id, respond_to }).await?;
+        response.await.map_err(|e| e.to_string())
+    }
+
+    /// Wait until the job is `Done` or `Failed`
+    #[instrument(target = "actor::jobs", skip(self))]
+    pub async fn await_job(&self, id: JobId) -> Result<JobStatus<T>, String> {
+        let (respond_to, response) = oneshot::channel();
+        self.request(JobRequest::Await { id, respond_to }).await?;
+        response
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Job not found: {}", id))
+    }
+
+    #[instrument(target = "actor::jobs", skip(self))]
+    pub async fn shutdown(&self) -> Result<(), String> {
+        debug!(target: JOB_TARGET, "Sending shutdown request");
+        let (respond_to, response) = oneshot::channel();
+        self.request(JobRequest::Shutdown { respond_to }).await?;
+        response.await.map_err(|e| e.to_string())
+    }
+
+    async fn request(&self, request: JobRequest<T>) -> Result<(), String> {
+        self.sender
+            .send(request)
+            .await
+            .map_err(|_| "Job service closed".to_string())
+    }
+}
+
+// =============================================================================
+// INGREDIENT 27: SAGAS
+// =============================================================================
+
+/// One forward step of a [`Saga`]
+type SagaAction<E> = Box<dyn FnOnce() -> BoxFuture<'static, Result<(), E>> + Send>;
+
+/// Undoes a completed step. It has no way to fail the saga, so it must log
+/// (or retry) its own errors.
+type Compensation = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+struct SagaStep<E> {
+    name: &'static str,
+    action: SagaAction<E>,
+    compensation: Option<Compensation>,
+}
+
+/// ## Ingredient 27: Saga
+///
+/// **Pattern:** A workflow across several actors can't be one transaction, so
+/// each step that changes another actor's state comes with a compensation
+/// that undoes it. Steps run in order; when one fails, the compensations of
+/// the steps before it run newest first and the step's error is returned.
+///
+/// ```rust,ignore
+/// let reservation = Saga::new("create_order")
+///     .step("validate_user", move || check_user(users, user_id))
+///     .compensated_step(
+///         "reserve_stock",
+///         move || reserve(products, id, quantity),
+///         move || release(products_again, id_again, quantity),
+///     )
+///     .run()
+///     .await?;
+/// // A later local step failed: undo the whole saga
+/// reservation.compensate().await;
+/// ```
+///
+/// Steps own their inputs (clients are cheap to clone), so a saga can be built
+/// in a handler and awaited there, keeping every step inside the handler span.
+pub struct Saga<E> {
+    name: &'static str,
+    steps: Vec<SagaStep<E>>,
+}
+
+impl<E: std::fmt::Display + Send + 'static> Saga<E> {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            steps: Vec::new(),
+        }
+    }
+
+    /// A step with nothing to undo, e.g. a validation
+    pub fn step<A, Fut>(mut self, name: &'static str, action: A) -> Self
+    where
+        A: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.steps.push(SagaStep {
+            name,
+            action: Box::new(move || Box::pin(action())),
+            compensation: None,
+        });
+        self
+    }
+
+    /// A step whose effect `compensation` undoes if anything after it fails
+    pub fn compensated_step<A, Fut, C, CFut>(
+        mut self,
+        name: &'static str,
+        action: A,
+        compensation: C,
+    ) -> Self
+    where
+        A: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<(), E>> + Send + 'static,
+        C: FnOnce() -> CFut + Send + 'static,
+        CFut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.steps.push(SagaStep {
+            name,
+            action: Box::new(move || Box::pin(action())),
+            compensation: Some(Box::new(move || Box::pin(compensation()))),
+        });
+        self
+    }
+
+    /// Run every step; on the first failure, compensate and return its error
+    pub async fn run(self) -> Result<CompletedSaga, E> {
+        let mut completed = CompletedSaga {
+            name: self.name,
+            compensations: Vec::new(),
+        };
+        for step in self.steps {
+            debug!(target: SAGA_TARGET, saga = self.name, step = step.name, "Saga step starting");
+            if let Err(error) = (step.action)().await {
+                warn!(
+                    target: SAGA_TARGET,
+                    saga = self.name,
+                    step = step.name,
+                    error = %error,
+                    compensations = completed.compensations.len(),
+                    "Saga step failed, compensating"
+                );
+                completed.compensate().await;
+                return Err(error);
+            }
+            if let Some(compensation) = step.compensation {
+                completed.compensations.push((step.name, compensation));
+            }
+        }
+        Ok(completed)
+    }
+}
+
+/// A saga whose steps all succeeded. Dropping it keeps their effects;
+/// [`compensate`](Self::compensate) undoes them, e.g. when local work after
+/// the saga fails.
+#[must_use = "drop the saga to keep its effects, or call `compensate` to undo them"]
+pub struct CompletedSaga {
+    name: &'static str,
+    compensations: Vec<(&'static str, Compensation)>,
+}
+
+impl CompletedSaga {
+    /// Undo every compensated step, newest first
+    pub async fn compensate(self) {
+        for (step, compensation) in self.compensations.into_iter().rev() {
+            info!(target: SAGA_TARGET, saga = self.name, step, "Compensating saga step");
+            compensation().await;
+        }
+    }
+}
+
+// =============================================================================
+// INGREDIENT 28: TWO-PHASE COMMIT
+// =============================================================================
+
+/// A prepare or commit call on one participant
+type TransactionPhase = Box<dyn FnOnce() -> BoxFuture<'static, Result<(), String>> + Send>;
+
+/// One actor taking part in a [`TwoPhaseCommit`]
+pub struct Participant {
+    name: &'static str,
+    timeout: Duration,
+    prepare: TransactionPhase,
+    commit: Option<TransactionPhase>,
+    abort: Option<Compensation>,
+}
+
+impl Participant {
+    /// `prepare` makes the change certain to succeed without making it final,
+    /// e.g. reserving stock or authorizing a payment
+    pub fn new<P, Fut>(name: &'static str, prepare: P) -> Self
+    where
+        P: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<(), String>> + Send + 'static,
+    {
+        Self {
+            name,
+            timeout: ActorConfig::default().call_timeout,
+            prepare: Box::new(move || Box::pin(prepare())),
+            commit: None,
+            abort: None,
+        }
+    }
+
+    /// Make the prepared change final. Without one, a successful prepare is
+    /// already the final state (as with reserved stock).
+    pub fn on_commit<C, Fut>(mut self, commit: C) -> Self
+    where
+        C: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.commit = Some(Box::new(move || Box::pin(commit())));
+        self
+    }
+
+    /// Undo a successful prepare when another participant refuses
+    pub fn on_abort<A, Fut>(mut self, abort: A) -> Self
+    where
+        A: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.abort = Some(Box::new(move || Box::pin(abort())));
+        self
+    }
+
+    /// Bound this participant's prepare and commit (default: the actor call timeout)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Why a [`TwoPhaseCommit`] did not complete
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionError {
+    /// A participant refused or timed out in prepare; the prepared ones were aborted
+    Aborted {
+        participant: &'static str,
+        reason: String,
+    },
+    /// Everyone prepared, but these participants failed to commit and need reconciling
+    CommitFailed { participants: Vec<&'static str> },
+}
+
+impl std::fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionError::Aborted {
+                participant,
+                reason,
+            } => write!(f, "Transaction aborted by {}: {}", participant, reason),
+            TransactionError::CommitFailed { participants } => {
+                write!(f, "Commit failed for {}", participants.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransactionError {}
+
+/// ## Ingredient 28: Two-Phase Commit
+///
+/// **Pattern:** Where a [`Saga`] makes each change and undoes it after a
+/// failure, two-phase commit asks every participant to *prepare* first,
+/// concurrently, and makes the changes final only once all of them agreed.
+/// Any refusal or timeout aborts every participant that did prepare.
+///
+/// ```rust,ignore
+/// TwoPhaseCommit::new("checkout")
+///     .participant(
+///         Participant::new("stock", move || reserve(products, id, quantity))
+///             .on_abort(move || release(products_again, id_again, quantity)),
+///     )
+///     .participant(
+///         Participant::new("payment", move || payments.authorize(amount))
+///             .on_commit(move || payments_again.capture())
+///             .on_abort(move || payments_third.void())
+///             .with_timeout(Duration::from_secs(2)),
+///     )
+///     .run()
+///     .await?;
+/// ```
+///
+/// **Timeouts:** A participant that times out counts as a refusal but is
+/// *not* aborted: its request may still be waiting in the actor's mailbox, so
+/// whether it prepared is unknown. Give slow participants a prepare that
+/// undoes itself, like a stock hold with a TTL.
+pub struct TwoPhaseCommit {
+    name: &'static str,
+    participants: Vec<Participant>,
+}
+
+impl TwoPhaseCommit {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            participants: Vec::new(),
+        }
+    }
+
+    pub fn participant(mut self, participant: Participant) -> Self {
+        self.participants.push(participant);
+        self
+    }
+
+    /// Prepare everyone, then commit everyone or abort the prepared ones
+    pub async fn run(self) -> Result<(), TransactionError> {
+        let mut prepares = Vec::new();
+        let mut decided = Vec::new();
+        for participant in self.participants {
+            prepares.push((participant.name, participant.timeout, participant.prepare));
+            decided.push((
+                participant.name,
+                participant.timeout,
+                participant.commit,
+                participant.abort,
+            ));
+        }
+
+        let outcomes = run_transaction_phase(self.name, "prepare", prepares).await;
+        let refusal = outcomes
+            .iter()
+            .enumerate()
+            .find_map(|(i, outcome)| outcome.as_ref().err().map(|reason| (i, reason.clone())));
+        if let Some((refused, reason)) = refusal {
+            let participant = decided[refused].0;
+            warn!(
+                target: TRANSACTION_TARGET,
+                transaction = self.name,
+                participant,
+                reason = %reason,
+                "Prepare refused, aborting"
+            );
+            for ((name, _, _, abort), outcome) in decided.into_iter().zip(outcomes) {
+                if let (Some(abort), Ok(())) = (abort, outcome) {
+                    info!(
+                        target: TRANSACTION_TARGET,
+                        transaction = self.name,
+                        participant = name,
+                        "Aborting participant"
+                    );
+                    abort().await;
+                }
+            }
+            return Err(TransactionError::Aborted {
+                participant,
+                reason,
+            });
+        }
+
+        let commits = decided
+            .into_iter()
+            .filter_map(|(name, timeout, commit, _)| commit.map(|commit| (name, timeout, commit)))
+            .collect::<Vec<_>>();
+        let names = commits.iter().map(|(name, _, _)| *name).collect::<Vec<_>>();
+        let outcomes = run_transaction_phase(self.name, "commit", commits).await;
+        let failed = names
+            .into_iter()
+            .zip(outcomes)
+            .filter_map(|(name, outcome)| outcome.err().map(|_| name))
+            .collect::<Vec<_>>();
+        if failed.is_empty() {
+            debug!(target: TRANSACTION_TARGET, transaction = self.name, "Transaction committed");
+            Ok(())
+        } else {
+            Err(TransactionError::CommitFailed {
+                participants: failed,
+            })
+        }
+    }
+}
+
+/// Run one phase on every participant at once, each under its own timeout.
+/// Outcomes come back in participant order.
+async fn run_transaction_phase(
+    transaction: &'static str,
+    phase: &'static str,
+    calls: Vec<(&'static str, Duration, TransactionPhase)>,
+) -> Vec<Result<(), String>> {
+    let mut outcomes = vec![Err("phase did not finish".to_string()); calls.len()];
+    let mut running = tokio::task::JoinSet::new();
+    for (index, (participant, timeout, call)) in calls.into_iter().enumerate() {
+        running.spawn(
+            async move {
+                let call = std::pin::pin!(call());
+                let caught = catch_unwind(call);
+                let outcome = match tokio::time::timeout(timeout, caught).await {
+                    Ok(Ok(outcome)) => outcome,
+                    Ok(Err(panic)) => Err(format!("panicked: {}", panic_message(&panic))),
+                    Err(_) => Err(format!("{} timed out after {:?}", phase, timeout)),
+                };
+                if let Err(reason) = &outcome {
+                    warn!(
+                        target: TRANSACTION_TARGET,
+                        transaction,
+                        participant,
+                        phase,
+                        reason = %reason,
+                        "Participant failed"
+                    );
+                }
+                (index, outcome)
+            }
+            .in_current_span(),
+        );
+    }
+    while let Some(joined) = running.join_next().await {
+        if let Ok((index, outcome)) = joined {
+            outcomes[index] = outcome;
+        }
+    }
+    outcomes
+}
+
+// =============================================================================
+// INGREDIENT 29: TRANSACTIONAL OUTBOX
+// =============================================================================
+
+/// ## Ingredient 29: Transactional Outbox
+///
+/// **Pattern:** A handler that publishes straight to the bus can lose the
+/// event: the bus may be gone, or the process may stop between the state
+/// change and the send. With an outbox the handler only *records* the event,
+/// in the actor's own state, in the same `&mut self` step as the change. The
+/// run loop relays recorded events to the [`EventBusClient`] after each
+/// message and on every tick, and drops each one only once the bus has taken
+/// it.
+///
+/// Because the outbox is part of the actor state, it is part of every
+/// snapshot too: a [`ProductSnapshot`] never holds a state change whose event
+/// was neither delivered nor kept, and restoring it relays the leftovers.
+/// Delivery is therefore at least once, and subscribers should tolerate a
+/// repeat after a restore.
+///
+/// **Bounded:** Once `with_outbox_capacity` events (1000 by default) wait
+/// undelivered, requests that would record another are refused with
+/// `Overloaded` and a `SYS-010` warning, so no accepted change loses its
+/// event. Expiring holds are the exception: nobody can be told no, so their
+/// events are kept past the bound. The `actor_outbox_pending` gauge shows a
+/// relay that has stopped draining, `actor_outbox_dropped_total` the refusals.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outbox {
+    pending: VecDeque<DomainEvent>,
+}
+
+impl Outbox {
+    pub fn push(&mut self, event: DomainEvent) {
+        self.pending.push_back(event);
+    }
+
+    /// Events not yet accepted by the bus
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Move every event of `other` to the back of this outbox
+    pub fn append(&mut self, mut other: Outbox) {
+        self.pending.append(&mut other.pending);
+    }
+
+    pub fn into_events(self) -> impl Iterator<Item = DomainEvent> {
+        self.pending.into_iter()
+    }
+
+    /// Publish pending events oldest first, stopping at the first one the bus
+    /// refuses so order is kept. Returns how many were delivered.
+    pub async fn relay(&mut self, events: &EventBusClient) -> usize {
+        let mut delivered = 0;
+        while let Some(event) = self.pending.pop_front() {
+            if let Err(event) = events.try_publish(event).await {
+                self.pending.push_front(event);
+                debug!(
+                    target: EVENT_BUS_TARGET,
+                    retained = self.pending.len(),
+                    "Event bus unavailable, events kept in outbox"
+                );
+                break;
+            }
+            delivered += 1;
+        }
+        delivered
+    }
+}
+
+// =============================================================================
+// INTERACTIVE SHELL
+// =============================================================================
+
+const SHELL_HELP: &str = "\
+commands:
+  user create <name> <email>
+  user get <id>
+  user list
+  product add <id> <name> <price> <stock>
+  product get <id>
+  product stock <id>
+  order create <user_id> <product_id> <quantity>
+  order get <id>
+  help
+  quit";
+
+/// Line-oriented front end for exploring a running [`OrderSystem`] by hand
+/// (`cargo run -- repl`). Every command is one client call, so the usual
+/// tracing output shows what each line did inside the actors.
+pub struct Shell<'a> {
+    system: &'a OrderSystem,
+    next_order: u64,
+}
+
+impl<'a> Shell<'a> {
+    pub fn new(system: &'a OrderSystem) -> Self {
+        Self {
+            system,
+            next_order: 1,
+        }
+    }
+
+    /// Read commands from stdin until `quit` or end of input
+    pub async fn run(mut self) -> Result<(), String> {
+        use tokio::io::AsyncBufReadExt;
+
+        println!("{}", SHELL_HELP);
+        let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+        while let Some(line) = lines.next_line().await.map_err(|e| e.to_string())? {
+            match line.trim() {
+                "" => continue,
+                "quit" | "exit" => break,
+                line => match self.execute(line).await {
+                    Ok(output) => println!("{}", output),
+                    Err(e) => println!("error: {}", e),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Run one command line, returning what to print
+    pub async fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let system = self.system;
+        match words.as_slice() {
+            ["help"] => Ok(SHELL_HELP.to_string()),
+            ["user", "create", name, email] => {
+                let user = User::builder(*name, *email)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                system.user_client.create_user(user).await
+            }
+            ["user", "get", id] => match system.user_client.get_user(id.to_string()).await? {
+                Some(user) => Ok(format!("{} {} <{}>", id, user.name, user.email)),
+                None => Err(format!("no user {}", id)),
+            },
+            ["user", "list"] => {
+                // The snapshot keys users by ID; stored users may not carry theirs
+                let users: BTreeMap<_, _> = system
+                    .user_client
+                    .snapshot()
+                    .await?
+                    .users
+                    .into_iter()
+                    .collect();
+                let lines: Vec<String> = users
+                    .iter()
+                    .map(|(id, user)| format!("{} {} <{}>", id, user.name, user.email))
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            ["product", "add", id, name, price, stock] => {
+                let price = price.parse().map_err(|_| format!("bad price: {}", price))?;
+                let stock = stock.parse().map_err(|_| format!("bad stock: {}", stock))?;
+                let product = Product::builder(*id, *name)
+                    .price(price)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                let snapshot = ProductSnapshot {
+                    products: HashMap::from([(id.to_string(), product)]),
+                    stock: HashMap::from([(id.to_string(), stock)]),
+                    ..Default::default()
+                };
+                system.product_client.import(snapshot).await?;
+                Ok(format!("added {}", id))
+            }
+            ["product", "get", id] => {
+                match system.product_client.get_product(id.to_string()).await? {
+                    Some(product) => Ok(format!("{} {} {:.2}", id, product.name, product.price)),
+                    None => Err(format!("no product {}", id)),
+                }
+            }
+            ["product", "stock", id] => {
+                let stock = system.product_client.check_stock(id.to_string()).await?;
+                Ok(format!("{} in stock: {}", id, stock))
+            }
+            ["order", "create", user_id, product_id, quantity] => {
+                let quantity: u32 = quantity
+                    .parse()
+                    .map_err(|_| format!("bad quantity: {}", quantity))?;
+                let product = system
+                    .product_client
+                    .get_product(product_id.to_string())
+                    .await?
+                    .ok_or_else(|| format!("no product {}", product_id))?;
+                let id = format!("order_{}", self.next_order);
+                self.next_order += 1;
+                let order = Order::builder(id, *user_id, *product_id)
+                    .quantity(quantity)
+                    .total(product.price * f64::from(quantity))
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                system.order_client.create_order(order).await
+            }
+            ["order", "get", id] => match system.order_client.get_order(id.to_string()).await? {
+                Some(order) => Ok(format!(
+                    "{} user={} product={} quantity={} total={:.2}",
+                    id, order.user_id, order.product_id, order.quantity, order.total
+                )),
+                None => Err(format!("no order {}", id)),
+            },
+            _ => Err(format!("unknown command: {} (try help)", line)),
+        }
+    }
+}
+
+// =============================================================================
+// MAILBOX BENCHMARK
+// =============================================================================
+
+/// Requests sent per benchmark round, split across the callers
+const BENCH_REQUESTS: usize = 200_000;
+const BENCH_CALLERS: usize = 16;
+
+/// Throughput of one [`UserService`] under a burst of concurrent callers, at
+/// batch size 1 (one message per wakeup) and at the default batch size
+/// (`cargo run --release -- bench`). The requests are `count()` calls, which
+/// do no work, so the numbers measure the mailbox loop itself.
+async fn run_benchmark() -> Result<(), String> {
+    for batch_size in [1, ActorConfig::default().batch_size] {
+        let (service, client) = UserService::new(ActorConfig::default().mailbox_size);
+        let actor = tokio::spawn(service.with_batch_size(batch_size).run());
+
+        let started = std::time::Instant::now();
+        let callers: Vec<_> = (0..BENCH_CALLERS)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    for _ in 0..BENCH_REQUESTS / BENCH_CALLERS {
+                        client.count().await?;
+                    }
+                    Ok::<_, String>(())
+                })
+            })
+            .collect();
+        for caller in callers {
+            caller.await.map_err(|e| e.to_string())??;
+        }
+        let elapsed = started.elapsed();
+
+        client.shutdown().await?;
+        let _ = actor.await;
+        println!(
+            "batch_size={:<3} {:>9.0} requests/s ({} requests in {:.2?})",
+            batch_size,
+            BENCH_REQUESTS as f64 / elapsed.as_secs_f64(),
+            BENCH_REQUESTS,
+            elapsed
+        );
+    }
+    Ok(())
+}
+
+// =============================================================================
+// USAGE EXAMPLE AND DEMO
+// =============================================================================
+
+/// The demo scenario: seed data, concurrent orders, a compensation, a report
+async fn run_demo(system: &OrderSystem) -> Result<(), String> {
+    // Stage 1: seed the catalog. Products only enter through import.
+    let catalog = ProductSnapshot {
+        products: HashMap::from([
+            ("p1".to_string(), Product::new("p1", "Widget", 10.0)),
+            ("p2".to_string(), Product::new("p2", "Gadget", 25.0)),
+        ]),
+        stock: HashMap::from([("p1".to_string(), 10), ("p2".to_string(), 3)]),
+        ..Default::default()
+    };
+    system.product_client.import(catalog).await?;
+
+    // Stage 2: create customers (builders validate before anything is sent)
+    let span = tracing::info_span!("user_creation");
+    let (alice, bob) = async {
+        info!("Creating customers");
+        let alice = User::builder("Alice", "alice@example.com")
+            .build()
+            .map_err(|e| e.to_string())?;
+        let bob = User::builder("Bob", "bob@example.com")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok::<_, String>((
+            system.user_client.create_user(alice).await?,
+            system.user_client.create_user(bob).await?,
+        ))
+    }
+    .instrument(span)
+    .await?;
+
+    // Stage 3: concurrent orders. Together they ask for more than is in stock,
+    // so the actors' serial processing decides which ones fail.
+    let requests = [
+        (&alice, "p1", 4, 10.0),
+        (&bob, "p1", 4, 10.0),
+        (&alice, "p2", 2, 25.0),
+        (&bob, "p2", 2, 25.0),
+        (&alice, "p1", 5, 10.0),
+    ];
+    let mut pending = Vec::new();
+    for (n, (user_id, product_id, quantity, price)) in requests.into_iter().enumerate() {
+        let order = Order::builder(format!("order_{}", n + 1), user_id.as_str(), product_id)
+            .quantity(quantity)
+            .total(price * quantity as f64)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let order_client = system.order_client.clone();
+        let span = tracing::info_span!("order_processing", order_id = %order.id);
+        pending.push(tokio::spawn(
+            async move {
+                info!("Processing order through order system");
+                (order.clone(), order_client.create_order(order).await)
+            }
+            .instrument(span),
+        ));
+    }
+
+    let mut placed = Vec::new();
+    for task in pending {
+        let (order, result) = task.await.map_err(|e| e.to_string())?;
+        match result {
+            Ok(order_id) => {
+                info!(order_id = %order_id, "Order processed successfully");
+                placed.push(order);
+            }
+            Err(e) => warn!(order_id = %order.id, error = %e, "Order rejected"),
+        }
+    }
+
+    // Stage 4: a customer cancels. There is no order cancellation API, so the
+    // compensation shown is the stock side: the reserved units go back on sale.
+    if let Some(cancelled) = placed.pop() {
+        let span = tracing::info_span!("compensation", order_id = %cancelled.id);
+        async {
+            let quantity = cancelled.quantity;
+            system
+                .product_client
+                .modify_stock(cancelled.product_id.clone(), move |stock| {
+                    *stock += quantity;
+                    Ok(())
+                })
+                .await?;
+            info!(quantity, "Cancelled order's stock returned");
+            Ok::<_, String>(())
+        }
+        .instrument(span)
+        .await?;
+    }
+
+    // Stage 5: final report
+    let users = system.user_client.list_users().await?;
+    let orders = system.order_client.snapshot().await?.orders;
+    let revenue: f64 = placed.iter().map(|order| order.total).sum();
+    info!(
+        users = users.len(),
+        orders_stored = orders.len(),
+        orders_kept = placed.len(),
+        revenue,
+        "Final report"
+    );
+    for product_id in ["p1", "p2"] {
+        let stock = system
+            .product_client
+            .check_stock(product_id.to_string())
+            .await?;
+        info!(product_id, stock, "Remaining stock");
+    }
+
+    Ok(())
+}
+
+/// ## Complete Usage Example
+///
+/// This example demonstrates all the patterns working together:
+/// - System startup and coordination
+/// - Cross-actor request flows
+/// - Error handling and tracing
+/// - Graceful shutdown
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    // Setup tracing once for the entire application
+    setup_tracing();
+
+    if std::env::args().nth(1).as_deref() == Some("bench") {
+        return run_benchmark().await;
+    }
+
+    info!("Starting application with complete order system");
+
+    // Create the entire order system and wait until every service is running
+    let system = OrderSystem::start().await.map_err(|e| e.to_string())?;
+
+    // DEMO_PROFILE=chaos makes sub-actors slow and flaky
+    if let Ok(name) = std::env::var("DEMO_PROFILE") {
+        let profile = DemoProfile::named(&name)
+            .ok_or_else(|| format!("Unknown DEMO_PROFILE: {} (use calm or chaos)", name))?;
+        system.set_demo_profile(profile);
+    }
+
+    // METRICS_ADDR=127.0.0.1:9090 serves Prometheus metrics while the demo runs
+    if let Ok(addr) = std::env::var("METRICS_ADDR") {
+        let listener = tokio::net::TcpListener::bind(&addr)
+            .await
+            .map_err(|e| format!("Cannot bind METRICS_ADDR {}: {}", addr, e))?;
+        tokio::spawn(serve_metrics(listener, Metrics::global()));
+        let events = system.event_bus.subscribe().await?;
+        tokio::spawn(DomainGauges::new(events, Metrics::global()).run());
+    }
+
+    // Run the demo (or `repl`, the interactive shell) until it completes or
+    // ctrl-c/SIGTERM arrives, then stop every actor, forcing the issue after a
+    // grace period
+    let controller = ShutdownController::new(Duration::from_secs(10));
+    let outcome = if std::env::args().nth(1).as_deref() == Some("repl") {
+        controller.run_until_signal(Shell::new(&system).run()).await
+    } else {
+        controller.run_until_signal(run_demo(&system)).await
+    };
+    controller.shutdown(system).await?;
+    match outcome {
+        Some(result) => result?,
+        None => warn!("Demo interrupted by signal"),
+    }
+
+    info!("Application completed successfully");
+    Ok(())
+}
+
+// =============================================================================
+// RECIPE SUMMARY
+// =============================================================================
+
+/// This recipe provides a solid foundation for building production actor systems in Rust!
+///
+/// ## To Run This Example
+///
+/// ```bash
+/// # Basic run
+/// cargo run
+///
+/// # With debug logging
+/// RUST_LOG=debug cargo run
+///
+/// # With warning level only  
+/// RUST_LOG=warn cargo run
+///
+/// # With injected latency and failures
+/// DEMO_PROFILE=chaos cargo run
+///
+/// # Multi-line logs with span timings
+/// LOG_FORMAT=pretty LOG_SPAN_TIMING=1 cargo run
+///
+/// # Interactive shell instead of the scripted demo (type `help`)
+/// RUST_LOG=warn cargo run -- repl
+///
+/// # Serving Prometheus metrics (scrape http://127.0.0.1:9090/metrics)
+/// METRICS_ADDR=127.0.0.1:9090 cargo run
+///
+/// # Generate documentation
+/// cargo doc --open
+/// ```
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        while let Some(msg) = self.receiver.recv().await {
-            match msg {
-                ProductRequest::GetProduct { id, respond_to } => {
-                    self.handle_get_product(id, respond_to);
-                }
-                ProductRequest::CheckStock { id, respond_to } => {
-                    self.handle_check_stock(id, respond_to);
+    /// Checks actor state through the public `count` and `exists` inspection requests
+    #[tokio::test]
+    async fn test_user_service_internal_state() -> Result<(), Box<dyn std::error::Error>> {
+        // Start just the UserService for testing
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        // Initially should have 0 users
+        let count = user_client.count().await?;
+        assert_eq!(count, 0);
+
+        // Create a user
+        let user = User::new("Test User", "test@example.com");
+        let user_id = user_client.create_user(user).await?;
+
+        // Now should have 1 user
+        let count = user_client.count().await?;
+        assert_eq!(count, 1);
+        assert!(user_client.exists(user_id).await?);
+        assert!(!user_client.exists("user_99".to_string()).await?);
+
+        // Shutdown
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_users_stream() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        for i in 0..5 {
+            let user = User::new(format!("User {}", i), format!("user{}@example.com", i));
+            user_client.create_user(user).await?;
+        }
+
+        // The stream waits on the consumer, the actor does not
+        let mut stream = user_client.list_users_stream(2).await?;
+        assert_eq!(user_client.count().await?, 5);
+        let mut streamed = 0;
+        while stream.recv().await.is_some() {
+            streamed += 1;
+        }
+        assert_eq!(streamed, 5);
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_user_sees_create_and_update() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        // Watch an ID before it exists
+        let mut watcher = user_client.watch_user("user_1".to_string()).await?;
+        assert!(watcher.borrow().is_none());
+
+        let user_id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        watcher.changed().await?;
+        assert_eq!(
+            watcher.borrow().as_ref().map(|u| u.name.clone()),
+            Some("Alice".to_string())
+        );
+
+        user_client
+            .update_user(user_id, User::new("Alicia", "alice@example.com"))
+            .await?;
+        watcher.changed().await?;
+        assert_eq!(
+            watcher.borrow().as_ref().map(|u| u.name.clone()),
+            Some("Alicia".to_string())
+        );
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_event_bus_receives_user_events() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::new();
+        let mut events = system.event_bus.subscribe().await?;
+
+        let user_id = system
+            .user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+
+        match events.recv().await? {
+            DomainEvent::UserCreated { user_id: id } => assert_eq!(id, user_id),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        system.shutdown().await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_builders_validate_payloads() {
+        assert!(User::builder("Alice", "alice@example.com").build().is_ok());
+        assert!(User::builder("Alice", "not-an-email").build().is_err());
+        assert!(User::builder("  ", "alice@example.com").build().is_err());
+
+        assert!(Product::builder("p1", "Widget").price(9.99).build().is_ok());
+        assert!(Product::builder("p1", "Widget")
+            .price(-1.0)
+            .build()
+            .is_err());
+
+        assert!(Order::builder("o1", "user_1", "p1")
+            .quantity(0)
+            .build()
+            .is_err());
+        let order = Order::builder("o1", "user_1", "p1")
+            .quantity(2)
+            .total(19.98)
+            .build()
+            .unwrap();
+        assert_eq!(order.quantity, 2);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_restore_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::new();
+        let user_id = system
+            .user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        let snapshot = system.snapshot().await?;
+        system.shutdown().await?;
+
+        let restored = OrderSystem::restore(snapshot);
+        let user = restored.user_client.get_user(user_id).await?;
+        assert_eq!(user.map(|u| u.name), Some("Alice".to_string()));
+
+        // IDs keep counting from where the snapshot left off
+        let next_id = restored
+            .user_client
+            .create_user(User::new("Bob", "bob@example.com"))
+            .await?;
+        assert_eq!(next_id, "user_2");
+
+        restored.shutdown().await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_dyn_client_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::new();
+        let users = system.dyn_client("users").ok_or("no users client")?;
+
+        let id = users
+            .call(
+                "create_user",
+                serde_json::json!({"user": {"name": "Alice", "email": "alice@example.com"}}),
+            )
+            .await?;
+        let user = users
+            .call("get_user", serde_json::json!({ "id": id }))
+            .await?;
+        assert_eq!(user["name"], "Alice");
+
+        assert!(users
+            .call("delete_everything", serde_json::json!({}))
+            .await
+            .is_err());
+
+        // Every advertised method is one `call` knows, whatever its arguments
+        for name in ["users", "products", "orders"] {
+            let client = system.dyn_client(name).ok_or("no client")?;
+            for method in client.methods() {
+                if let Err(e) = client.call(method, serde_json::json!({})).await {
+                    assert!(!e.starts_with("Unknown method"), "{}: {}", name, e);
                 }
-                ProductRequest::ReserveStock {
-                    id,
-                    quantity,
-                    respond_to,
-                } => {
-                    self.handle_reserve_stock(id, quantity, respond_to).await;
+            }
+        }
+
+        system.shutdown().await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_json_export_import() -> Result<(), Box<dyn std::error::Error>> {
+        let source = OrderSystem::new();
+        let user_id = source
+            .user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        let json = source.user_client.export_json().await?;
+        source.shutdown().await?;
+
+        let target = OrderSystem::new();
+        assert_eq!(target.user_client.import_json(&json).await?, 1);
+        assert!(target.user_client.get_user(user_id).await?.is_some());
+
+        target.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_timeout_on_stalled_actor() {
+        // Nobody drains this channel, simulating a stuck actor
+        let (sender, _receiver) = mpsc::channel(10);
+        let client = UserClient::new(sender).with_timeout(Duration::from_millis(20));
+
+        let result = client.get_user("user_1".to_string()).await;
+        assert!(result.unwrap_err().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_after_timeout() -> Result<(), Box<dyn std::error::Error>> {
+        let (sender, mut receiver) = mpsc::channel(10);
+        let client = UserClient::new(sender)
+            .with_timeout(Duration::from_millis(20))
+            .with_retry(RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(5),
+                jitter: false,
+            });
+
+        // Ignore the first request, answer the second
+        tokio::spawn(async move {
+            let _first = receiver.recv().await;
+            if let Some(UserRequest::GetUser { respond_to, .. }) = receiver.recv().await {
+                let _ = respond_to.send(Ok(None));
+            }
+        });
+
+        assert!(client.get_user("user_1".to_string()).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_modify_is_not_retried_after_timeout() {
+        // Nobody reads this mailbox, so every attempt times out
+        let (sender, mut receiver) = mpsc::channel(10);
+        let client = UserClient::new(sender)
+            .with_timeout(Duration::from_millis(20))
+            .with_retry(RetryPolicy::default());
+
+        let result = client.modify_user("user_1".to_string(), |_| Ok(())).await;
+        assert_eq!(result, Err("Request timed out after 20ms".to_string()));
+        assert!(receiver.try_recv().is_ok());
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_opens_and_recovers() {
+        let (sender, mut receiver) = mpsc::channel::<UserRequest>(10);
+        let client = UserClient::new(sender)
+            .with_timeout(Duration::from_millis(10))
+            .with_circuit_breaker(CircuitBreakerConfig {
+                failure_threshold: 2,
+                reset_timeout: Duration::from_secs(1),
+            });
+
+        // Two timeouts open the circuit; the third call never reaches the actor
+        assert!(client.get_user("a".to_string()).await.is_err());
+        assert!(client.get_user("a".to_string()).await.is_err());
+        let rejected = client.get_user("a".to_string()).await.unwrap_err();
+        assert!(rejected.contains("Circuit open"));
+
+        // After the reset timeout a healthy actor closes the circuit again
+        while receiver.try_recv().is_ok() {}
+        tokio::spawn(async move {
+            while let Some(UserRequest::GetUser { respond_to, .. }) = receiver.recv().await {
+                let _ = respond_to.send(Ok(None));
+            }
+        });
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(client.get_user("a".to_string()).await.is_ok());
+        assert!(!client.policy.breaker.as_ref().unwrap().is_open());
+    }
+
+    #[tokio::test]
+    async fn test_invariant_violation_rolls_back_update() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        let user_id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+
+        // update_user validates its input, so the bad email never reaches the store
+        let result = user_client
+            .update_user(user_id.clone(), User::new("Alice", ""))
+            .await;
+        assert!(result
+            .unwrap_err()
+            .contains("email: must look like name@domain"));
+
+        // modify_user runs arbitrary caller code; the invariant check catches it
+        let result = user_client
+            .modify_user(user_id.clone(), |user| {
+                user.email.clear();
+                Ok(())
+            })
+            .await;
+        assert!(result.unwrap_err().contains("Invariant violated"));
+
+        let stored = user_client.get_user(user_id).await?.ok_or("user missing")?;
+        assert_eq!(stored.email, "alice@example.com");
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_restarts_panicked_actor() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A tiny actor that doubles numbers and panics on zero
+        let starts = Arc::new(AtomicUsize::new(0));
+        let counter = starts.clone();
+        let (supervisor, sender) = Supervisor::new("doubler", 10, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let (sender, mut receiver) = mpsc::channel::<(u32, oneshot::Sender<u32>)>(10);
+            let handle = tokio::spawn(async move {
+                while let Some((n, respond_to)) = receiver.recv().await {
+                    assert!(n != 0, "boom");
+                    let _ = respond_to.send(n * 2);
                 }
-                ProductRequest::Shutdown => {
-                    info!("ProductService shutting down");
-                    break;
+            });
+            (sender, handle)
+        });
+        let _handle = tokio::spawn(supervisor.run());
+
+        let ask = |n: u32| {
+            let sender = sender.clone();
+            async move {
+                let (respond_to, response) = oneshot::channel();
+                sender.send((n, respond_to)).await.ok()?;
+                response.await.ok()
+            }
+        };
+
+        assert_eq!(ask(2).await, Some(4));
+        assert_eq!(ask(0).await, None); // response dropped by the panic
+        assert_eq!(ask(3).await, Some(6)); // same sender, new actor
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restart_policy_gives_up_after_limit() {
+        let (supervisor, sender) = Supervisor::new("always_panics", 10, || {
+            let (sender, mut receiver) = mpsc::channel::<()>(10);
+            let handle = tokio::spawn(async move {
+                if receiver.recv().await.is_some() {
+                    panic!("boom");
                 }
+            });
+            (sender, handle)
+        });
+        let supervisor = supervisor.with_restart_policy(RestartPolicy::OnFailure {
+            max_restarts: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+        });
+        let handle = tokio::spawn(supervisor.run());
+
+        // Initial actor plus two restarts each take one message, then supervision ends
+        for _ in 0..3 {
+            let _ = sender.send(()).await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        handle.await.unwrap();
+        assert!(sender.send(()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_modify_stock_is_conditional() -> Result<(), Box<dyn std::error::Error>> {
+        let (product_service, product_client) = ProductService::new(10);
+        let _handle = tokio::spawn(product_service.run());
+        product_client
+            .import(ProductSnapshot {
+                products: HashMap::from([("p1".to_string(), Product::new("p1", "Widget", 9.99))]),
+                stock: HashMap::from([("p1".to_string(), 3)]),
+                ..Default::default()
+            })
+            .await?;
+
+        // Decrement only while more than 2 remain
+        let take_if_plenty = |stock: &mut u32| {
+            if *stock <= 2 {
+                return Err(ProductError::InsufficientStock {
+                    requested: 1,
+                    available: *stock,
+                });
             }
+            *stock -= 1;
+            Ok(*stock)
+        };
+
+        assert_eq!(
+            product_client
+                .modify_stock("p1".to_string(), take_if_plenty)
+                .await?,
+            2
+        );
+        assert!(product_client
+            .modify_stock("p1".to_string(), take_if_plenty)
+            .await
+            .is_err());
+        assert_eq!(product_client.check_stock("p1".to_string()).await?, 2);
+
+        product_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_keeps_actor_alive() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let handle = tokio::spawn(user_service.run());
+
+        let user_id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+
+        let result = user_client
+            .modify_user(user_id.clone(), |_| -> Result<(), UserError> {
+                panic!("boom")
+            })
+            .await;
+        assert_eq!(result.unwrap_err(), "User handler panicked");
+
+        // Same actor, same store
+        let stored = user_client.get_user(user_id).await?.ok_or("user missing")?;
+        assert_eq!(stored.email, "alice@example.com");
+        assert!(!handle.is_finished());
+
+        user_client.shutdown().await?;
+        handle.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_user_if() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        let user_id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+
+        let rejected = user_client
+            .update_user_if(
+                user_id.clone(),
+                |u| u.email == "old@example.com",
+                User::new("Alice", "new@example.com"),
+            )
+            .await;
+        assert!(rejected.unwrap_err().contains("precondition failed"));
+
+        let updated = user_client
+            .update_user_if(
+                user_id.clone(),
+                |u| u.email == "alice@example.com",
+                User::new("Alice", "new@example.com"),
+            )
+            .await?;
+        assert_eq!(updated.email, "new@example.com");
+
+        // The replacement is validated like any update, and not stored
+        let invalid = user_client
+            .update_user_if(user_id.clone(), |_| true, User::new(" ", "not-an-email"))
+            .await;
+        assert!(invalid.unwrap_err().starts_with("Invalid user"));
+        let stored = user_client.get_user(user_id).await?.ok_or("missing")?;
+        assert_eq!(
+            (stored.name.as_str(), stored.email.as_str()),
+            ("Alice", "new@example.com")
+        );
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserve_id_and_create_with_id() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        let reserved = user_client.reserve_id().await?;
+        user_client
+            .create_user_with_id(reserved.clone(), User::new("Alice", "alice@example.com"))
+            .await?;
+        let duplicate = user_client
+            .create_user_with_id(reserved.clone(), User::new("Bob", "bob@example.com"))
+            .await;
+        assert!(duplicate.unwrap_err().contains("already exists"));
+
+        // An externally chosen user_N pushes generated IDs past it
+        user_client
+            .create_user_with_id(
+                "user_10".to_string(),
+                User::new("Carol", "carol@example.com"),
+            )
+            .await?;
+        let generated = user_client
+            .create_user(User::new("Dave", "dave@example.com"))
+            .await?;
+        assert_eq!(generated, "user_11");
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_replay() -> Result<(), Box<dyn std::error::Error>> {
+        let (dlq_service, dead_letters) = DeadLetterService::<UserRequest>::new(10, 100);
+        let _dlq = tokio::spawn(dlq_service.run());
+
+        // An actor that has already stopped, so nothing can be delivered
+        let (supervisor, sender) = Supervisor::new("users", 10, || {
+            let (actor, _) = mpsc::channel(1);
+            (actor, tokio::spawn(async {}))
+        });
+        let (respond_to, response) = ServiceResponse::channel();
+        sender.send(UserRequest::ListUsers { respond_to }).await?;
+        tokio::spawn(supervisor.with_dead_letters(dead_letters.clone()).run()).await?;
+
+        let letters = dead_letters.list().await?;
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].reason, DeadLetterReason::ActorStopped);
+        assert!(letters[0].message.starts_with("ListUsers"));
+
+        // A fresh actor answers the original caller
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+        assert_eq!(dead_letters.replay(user_client.sender.clone()).await?, 1);
+        assert!(response.await?.map_err(|e| e.to_string())?.is_empty());
+        assert!(dead_letters.list().await?.is_empty());
+
+        user_client.shutdown().await?;
+        dead_letters.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_test_clock_follows_paused_time() {
+        let clock: SharedClock = Arc::new(TestClock::default());
+        let start = clock.now();
+        assert_eq!(clock.unix_secs(), 0);
+
+        tokio::time::advance(Duration::from_secs(90)).await;
+        assert_eq!(clock.now() - start, Duration::from_secs(90));
+        assert_eq!(clock.unix_secs(), 90);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_queued_requests() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+
+        // Queue work before the actor even starts, then ask it to stop
+        let creates: Vec<_> = (0..3)
+            .map(|i| {
+                let user_client = user_client.clone();
+                tokio::spawn(async move {
+                    let user = User::new(format!("User {}", i), format!("user{}@example.com", i));
+                    user_client.create_user(user).await
+                })
+            })
+            .collect();
+        while user_client.sender.capacity() > 7 {
+            tokio::task::yield_now().await;
+        }
+        let shutdown = tokio::spawn({
+            let user_client = user_client.clone();
+            async move { user_client.shutdown().await }
+        });
+
+        let handle = tokio::spawn(user_service.run());
+        shutdown.await??;
+        for create in creates {
+            assert!(create.await?.is_ok());
         }
+        handle.await?;
+
+        // The mailbox is closed for good
+        assert!(user_client.list_users().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_drain_refuses_writes_but_serves_reads() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+
+        let user_id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        user_client.drain().await?;
+
+        let refused = user_client
+            .create_user(User::new("Bob", "bob@example.com"))
+            .await;
+        assert!(refused.unwrap_err().contains("draining"));
+        assert!(user_client.get_user(user_id).await?.is_some());
+        assert_eq!(user_client.list_users().await?.len(), 1);
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fault_injection() -> Result<(), Box<dyn std::error::Error>> {
+        let faults = FaultInjector::default();
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.with_faults(faults.clone()).run());
+
+        faults.set(FaultProfile {
+            latency: Duration::from_millis(300),
+            error_rate: 0.0,
+        });
+        let start = tokio::time::Instant::now();
+        user_client.list_users().await?;
+        assert!(start.elapsed() >= Duration::from_millis(300));
+
+        faults.set(FaultProfile {
+            latency: Duration::ZERO,
+            error_rate: 1.0,
+        });
+        assert!(user_client.list_users().await.is_err());
+
+        // Lifecycle messages always get through
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_order_system_clients_use_call_timeout() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let config = SystemConfig {
+            orders: ActorOverrides {
+                call_timeout: Some(Duration::from_millis(200)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let system = OrderSystem::builder().config(config).try_build()?;
+        let stuck = FaultProfile {
+            latency: Duration::from_secs(60),
+            error_rate: 0.0,
+        };
+        system.set_demo_profile(DemoProfile {
+            users: stuck.clone(),
+            orders: stuck,
+            ..DemoProfile::default()
+        });
+
+        // The override bounds the order client, the default bounds the others
+        let result = system.order_client.count().await;
+        assert_eq!(result, Err("Request timed out after 200ms".to_string()));
+        let result = system.user_client.count().await;
+        assert_eq!(result, Err("Request timed out after 5000ms".to_string()));
+
+        system.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_controller_aborts_after_grace_period() {
+        let system = OrderSystem::new();
+        system.set_demo_profile(DemoProfile {
+            users: FaultProfile {
+                latency: Duration::from_secs(60),
+                error_rate: 0.0,
+            },
+            ..DemoProfile::default()
+        });
+
+        // Occupy the user service well past the grace period
+        let user_client = system.user_client.clone();
+        let _stuck = tokio::spawn(async move { user_client.list_users().await });
+        tokio::time::sleep(Duration::from_millis(1)).await;
+
+        let controller = ShutdownController::new(Duration::from_secs(1));
+        let result = controller.shutdown(system).await;
+        assert!(result.unwrap_err().contains("grace period"));
+    }
+
+    #[tokio::test]
+    async fn test_order_system_builder_with_mock_user_client(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // A hand-driven mailbox stands in for the user service
+        let (mock_sender, mut mock_receiver) = mpsc::channel(10);
+        let mock = tokio::spawn(async move {
+            while let Some(request) = mock_receiver.recv().await {
+                match request {
+                    UserRequest::GetUser { id, respond_to } => {
+                        let _ = respond_to.send(Ok(Some(User {
+                            id,
+                            ..User::new("Mock", "mock@example.com")
+                        })));
+                    }
+                    UserRequest::Shutdown { respond_to } => {
+                        let _ = respond_to.send(Ok(()));
+                        break;
+                    }
+                    other => panic!("unexpected request: {:?}", other),
+                }
+            }
+        });
+
+        let catalog = ProductSnapshot {
+            products: HashMap::from([("p1".to_string(), Product::new("p1", "Widget", 10.0))]),
+            stock: HashMap::from([("p1".to_string(), 5)]),
+            ..Default::default()
+        };
+        let system = OrderSystem::builder()
+            .snapshot(SystemSnapshot {
+                products: catalog,
+                ..SystemSnapshot::default()
+            })
+            .order_buffer(8)
+            .user_client(UserClient::new(mock_sender))
+            .build();
+
+        let order = Order::new("order_1", "anyone", "p1", 2, 20.0);
+        assert_eq!(system.order_client.create_order(order).await?, "order_1");
+
+        system.shutdown().await?;
+        mock.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_random_id_strategy() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.with_id_strategy(IdStrategy::Random).run());
+
+        let first = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        let second = user_client
+            .create_user(User::new("Bob", "bob@example.com"))
+            .await?;
+        assert_eq!(first.len(), "user_".len() + 16);
+        assert_ne!(first, second);
+
+        user_client.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_actor_system_registry() -> Result<(), Box<dyn std::error::Error>> {
+        let mut system = ActorSystem::new();
+        let (user_service, user_client) = UserService::new(10);
+        system.register("users", user_client, tokio::spawn(user_service.run()))?;
+        let (product_service, product_client) = ProductService::new(10);
+        system.register(
+            "products",
+            product_client,
+            tokio::spawn(product_service.run()),
+        )?;
+
+        let (duplicate, duplicate_client) = UserService::new(10);
+        let rejected = system.register("users_2", duplicate_client, tokio::spawn(duplicate.run()));
+        assert!(rejected
+            .unwrap_err()
+            .contains("already registered as users"));
+
+        let user_client = system
+            .client::<UserClient>()
+            .ok_or("users not registered")?;
+        user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        assert!(system.client::<OrderClient>().is_none());
+        assert_eq!(system.names(), vec!["users", "products"]);
+
+        system.shutdown().await?;
+        assert!(user_client.list_users().await.is_err());
+        Ok(())
+    }
 
-        info!("ProductService stopped");
+    #[test]
+    fn test_event_codes() {
+        assert_eq!(
+            OrderError::InsufficientStock("p1".to_string())
+                .code()
+                .as_str(),
+            "ORD-001"
+        );
+        assert_eq!(
+            UserError::NotFound("u".to_string()).code().to_string(),
+            "USR-001"
+        );
+        assert_eq!(ProductError::Draining.code(), EventCode::ActorDraining);
     }
 
-    #[instrument(fields(product_id = %id), skip(self, respond_to))]
-    fn handle_get_product(
-        &self,
-        id: String,
-        respond_to: ServiceResponse<Option<Product>, ProductError>,
-    ) {
-        debug!("Processing get_product request");
+    #[tokio::test]
+    async fn test_named_registry() -> Result<(), Box<dyn std::error::Error>> {
+        let (registry_service, registry) = RegistryService::new(10);
+        let _registry = tokio::spawn(registry_service.run());
+
+        // Two product stores side by side
+        let (eu_service, eu_products) = ProductService::new(10);
+        let _eu = tokio::spawn(eu_service.run());
+        let (us_service, us_products) = ProductService::new(10);
+        let _us = tokio::spawn(us_service.run());
+        registry.register("inventory-eu", eu_products).await?;
+        registry
+            .register("inventory-us", us_products.clone())
+            .await?;
+        assert!(registry
+            .register("inventory-us", us_products)
+            .await
+            .unwrap_err()
+            .contains("Already registered"));
+
+        let eu = registry
+            .lookup::<ProductClient>("inventory-eu")
+            .await?
+            .ok_or("inventory-eu missing")?;
+        let catalog = ProductSnapshot {
+            products: HashMap::from([("p1".to_string(), Product::new("p1", "Widget", 10.0))]),
+            stock: HashMap::from([("p1".to_string(), 5)]),
+            ..Default::default()
+        };
+        eu.import(catalog).await?;
+        assert_eq!(eu.check_stock("p1".to_string()).await?, 5);
+
+        // Same name, different type: a separate entry
+        assert!(registry
+            .lookup::<UserClient>("inventory-eu")
+            .await?
+            .is_none());
+        assert!(registry.unregister::<ProductClient>("inventory-eu").await?);
+        assert!(registry
+            .lookup::<ProductClient>("inventory-eu")
+            .await?
+            .is_none());
+
+        registry.shutdown().await?;
+        Ok(())
+    }
 
-        let product = self.products.get(&id).cloned();
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_actor_passivates_and_respawns() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        // The "storage backend": state parked here between incarnations
+        let store = Arc::new(Mutex::new(None::<UserSnapshot>));
+        let starts = Arc::new(AtomicUsize::new(0));
+        let (counter, storage) = (starts.clone(), store.clone());
+        let (supervisor, sender) = Supervisor::new("users", 10, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let saved = storage.lock().unwrap().take().unwrap_or_default();
+            let storage = storage.clone();
+            let (service, client) = UserService::new(10);
+            let service = service
+                .restore(saved)
+                .on_stop(move |snapshot| *storage.lock().unwrap() = Some(snapshot));
+            (client.sender, tokio::spawn(service.run()))
+        });
+        let _handle = tokio::spawn(supervisor.with_idle_timeout(Duration::from_secs(60)).run());
+        let user_client = UserClient::new(sender);
+
+        let id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert!(
+            store.lock().unwrap().is_some(),
+            "state persisted on passivation"
+        );
 
-        match &product {
-            Some(product) => {
-                info!(product_name = %product.name, price = %product.price, "Product found")
-            }
-            None => debug!("Product not found"),
-        }
+        // The next call respawns the actor with the persisted state
+        assert_eq!(
+            user_client.get_user(id).await?.map(|u| u.name).as_deref(),
+            Some("Alice")
+        );
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
 
-        let _ = respond_to.send(Ok(product));
+        user_client.shutdown().await?;
+        Ok(())
     }
 
-    #[instrument(fields(product_id = %id), skip(self, respond_to))]
-    fn handle_check_stock(&self, id: String, respond_to: ServiceResponse<u32, ProductError>) {
-        debug!("Processing check_stock request");
-
-        let stock = self.stock.get(&id).copied().unwrap_or(0);
-        info!(stock_level = stock, "Stock checked");
+    #[tokio::test]
+    async fn test_sharded_product_service() -> Result<(), Box<dyn std::error::Error>> {
+        let mut catalog = ProductSnapshot::default();
+        for i in 0..20 {
+            let id = format!("p{}", i);
+            let product = Product::new(id.clone(), format!("Widget {}", i), 1.0);
+            catalog.products.insert(id.clone(), product);
+            catalog.stock.insert(id, 10);
+        }
+        let system = OrderSystem::builder()
+            .snapshot(SystemSnapshot {
+                products: catalog,
+                ..Default::default()
+            })
+            .product_shards(4)
+            .build();
+
+        // Keyed requests reach the owning shard; whole-store requests merge
+        let products = &system.product_client;
+        products.reserve_stock("p7".to_string(), 3).await?;
+        assert_eq!(products.check_stock("p7".to_string()).await?, 7);
+        let snapshot = products.snapshot().await?;
+        assert_eq!(snapshot.products.len(), 20);
+        assert_eq!(snapshot.stock["p7"], 7);
+
+        system.shutdown().await?;
+        Ok(())
+    }
 
-        let _ = respond_to.send(Ok(stock));
+    #[test]
+    fn test_system_config_overrides() {
+        let config = SystemConfig {
+            orders: ActorOverrides {
+                mailbox_size: Some(1000),
+                // Orders keep their audit history forever
+                audit_retention: Some(usize::MAX),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let orders = config.resolve(&config.orders);
+        assert_eq!(orders.mailbox_size, 1000);
+        assert_eq!(orders.audit_retention, usize::MAX);
+        assert_eq!(orders.call_timeout, config.defaults.call_timeout);
+        assert_eq!(config.resolve(&config.users), config.defaults);
+
+        // Rejected before any actor is spawned
+        let result = OrderSystem::builder()
+            .config(config)
+            .user_buffer(0)
+            .try_build();
+        assert_eq!(result.err(), Some(ConfigError::InvalidMailboxSize("users")));
+
+        let config = SystemConfig {
+            products: ActorOverrides {
+                tick_interval: Some(Duration::ZERO),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::InvalidTickInterval("products"))
+        );
     }
 
-    #[instrument(fields(product_id = %id, quantity = %quantity), skip(self, respond_to))]
-    async fn handle_reserve_stock(
-        &mut self,
-        id: String,
-        quantity: u32,
-        respond_to: ServiceResponse<(), ProductError>,
-    ) {
-        debug!("Processing reserve_stock request");
+    #[tokio::test]
+    async fn test_worker_pool_round_robin() -> Result<(), Box<dyn std::error::Error>> {
+        // Each worker reports which task ran the request
+        let (pool, workers) = WorkerPool::new(3, 10, |n: u32| async move {
+            assert!(n != 0, "boom");
+            format!("{:?}", tokio::task::id())
+        });
+        let _handle = tokio::spawn(pool.run());
 
-        let result = match self.stock.get_mut(&id) {
-            Some(current_stock) => {
-                if *current_stock >= quantity {
-                    *current_stock -= quantity;
-                    info!(
-                        remaining_stock = *current_stock,
-                        "Stock reserved successfully"
-                    );
-                    Ok(())
-                } else {
-                    error!(
-                        available = *current_stock,
-                        requested = quantity,
-                        "Insufficient stock"
-                    );
-                    Err(ProductError::InsufficientStock {
-                        requested: quantity,
-                        available: *current_stock,
-                    })
-                }
-            }
-            None => {
-                error!("Product not found");
-                Err(ProductError::NotFound(id))
-            }
-        };
+        let mut seen = Vec::new();
+        for n in 1..=6 {
+            seen.push(workers.call(n).await?);
+        }
+        assert_eq!(seen[0..3], seen[3..6]);
+        assert_ne!(seen[0], seen[1]);
+        assert_ne!(seen[1], seen[2]);
 
-        let _ = respond_to.send(result);
+        // A panicking job costs only its own response
+        assert_eq!(workers.call(0).await.unwrap_err(), "Handler panicked: boom");
+        assert!(workers.call(7).await.is_ok());
+
+        workers.shutdown().await?;
+        Ok(())
     }
-}
 
-#[derive(Clone)]
-pub struct ProductClient {
-    sender: mpsc::Sender<ProductRequest>,
-}
+    #[tokio::test]
+    async fn test_priority_mailbox_ordering() -> Result<(), Box<dyn std::error::Error>> {
+        // Zero is "urgent" even on the normal lane
+        let (actor, mut inbox) = mpsc::channel::<u32>(1);
+        let (mailbox, lanes) = PriorityMailbox::new(10, actor, |n| *n == 0);
+        for n in [1, 2, 0, 3] {
+            lanes.normal.send(n).await?;
+        }
+        lanes.high.send(99).await?;
+        drop(lanes);
+        let _handle = tokio::spawn(mailbox.run());
 
-impl ProductClient {
-    pub fn new(sender: mpsc::Sender<ProductRequest>) -> Self {
-        Self { sender }
+        let mut received = Vec::new();
+        while let Some(n) = inbox.recv().await {
+            received.push(n);
+        }
+        assert_eq!(received, vec![99, 0, 1, 2, 3]);
+        Ok(())
     }
 
-    #[instrument(skip(self))]
-    pub async fn shutdown(&self) -> Result<(), String> {
-        debug!("Sending shutdown request");
-        self.sender
-            .send(ProductRequest::Shutdown)
-            .await
-            .map_err(|e| e.to_string())?;
+    #[tokio::test]
+    async fn test_backpressure_policies() -> Result<(), Box<dyn std::error::Error>> {
+        // Nobody reads this mailbox, so it fills up
+        let (sender, _receiver) = mpsc::channel(1);
+        let users = UserClient::new(sender).with_backpressure(Backpressure::FailFast);
+        let first = users.clone();
+        let _pending = tokio::spawn(async move { first.count().await });
+        tokio::task::yield_now().await;
+        assert_eq!(users.count().await, Err(BUSY.to_string()));
+
+        // Drop-oldest keeps the newest requests when the backlog overflows
+        let (actor, mut inbox) = mpsc::channel::<u32>(1);
+        let (mailbox, lanes) = PriorityMailbox::new(2, actor, |_| false);
+        let (high, normal) = (lanes.high, lanes.normal);
+        high.send(0).await?; // occupies the actor's mailbox
+        for n in 1..=2 {
+            normal.send(n).await?;
+        }
+        let _handle = tokio::spawn(mailbox.with_drop_oldest().run());
+        tokio::task::yield_now().await;
+        normal.send(3).await?;
+        drop((high, normal));
+
+        let mut received = Vec::new();
+        while let Some(n) = inbox.recv().await {
+            received.push(n);
+        }
+        assert_eq!(received, vec![0, 2, 3]);
         Ok(())
     }
-}
 
-// Generate product client methods
-client_method!(ProductClient => fn get_product(id: String) -> Option<Product> as ProductRequest::GetProduct);
-client_method!(ProductClient => fn check_stock(id: String) -> u32 as ProductRequest::CheckStock);
-client_method!(ProductClient => fn reserve_stock(id: String, quantity: u32) -> () as ProductRequest::ReserveStock);
+    #[tokio::test(start_paused = true)]
+    async fn test_mailbox_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let probe = {
+            let user_client = user_client.clone();
+            tokio::spawn(async move { user_client.get_stats().await })
+        };
+        for _ in 0..3 {
+            let user_client = user_client.clone();
+            tokio::spawn(async move { user_client.list_users().await });
+        }
+        tokio::task::yield_now().await;
 
-// =============================================================================
-// INGREDIENT 7: ROOT ACTOR (ORCHESTRATOR)
-// =============================================================================
+        // The probe waits in line until the actor starts
+        tokio::time::advance(Duration::from_millis(250)).await;
+        let _handle = tokio::spawn(user_service.run());
+        let stats = probe.await??;
+        assert_eq!(stats.capacity, 10);
+        assert_eq!(stats.depth, 3);
+        assert_eq!(stats.queue_time, Duration::from_millis(250));
+        // The run loop publishes the same sample without being asked
+        let rendered = Metrics::global().render();
+        assert!(rendered.contains("actor_mailbox_depth{entity=\"User\"}"));
+        assert!(rendered.contains("actor_mailbox_capacity{entity=\"User\"}"));
+        assert!(rendered.contains("actor_mailbox_queue_time_ms{entity=\"User\"}"));
 
-/// Order-specific error types
-#[derive(Debug, Clone)]
-pub enum OrderError {
-    NotFound(String),
-    InvalidProduct(String),
-    InvalidUser(String),
-    InsufficientStock(String),
-    ValidationError(String),
-    DatabaseError(String),
-}
+        user_client.shutdown().await?;
+        Ok(())
+    }
 
-impl std::fmt::Display for OrderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            OrderError::NotFound(id) => write!(f, "Order not found: {}", id),
-            OrderError::InvalidProduct(id) => write!(f, "Invalid product: {}", id),
-            OrderError::InvalidUser(id) => write!(f, "Invalid user: {}", id),
-            OrderError::InsufficientStock(msg) => write!(f, "Insufficient stock: {}", msg),
-            OrderError::ValidationError(msg) => write!(f, "Order validation error: {}", msg),
-            OrderError::DatabaseError(msg) => write!(f, "Order database error: {}", msg),
-        }
+    #[tokio::test]
+    async fn test_metrics_exporter() -> Result<(), Box<dyn std::error::Error>> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let metrics: &'static Metrics = Box::leak(Box::default());
+        metrics.record("UserRequest", "GetUser", Duration::from_millis(3), true);
+        metrics.record("UserRequest", "GetUser", Duration::from_millis(30), false);
+        metrics.record_handler("UserRequest", "GetUser", Duration::from_millis(2));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _handle = tokio::spawn(serve_metrics(listener, metrics));
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await?;
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+
+        let labels = "entity=\"User\",request=\"GetUser\"";
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(&format!("actor_requests_total{{{}}} 2", labels)));
+        assert!(response.contains(&format!("actor_request_errors_total{{{}}} 1", labels)));
+        assert!(response.contains(&format!(
+            "actor_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1",
+            labels
+        )));
+        assert!(response.contains(&format!(
+            "actor_handler_duration_seconds_count{{{}}} 1",
+            labels
+        )));
+        Ok(())
     }
-}
 
-impl std::error::Error for OrderError {}
+    #[test]
+    fn test_snapshot_diff() {
+        let mut before = SystemSnapshot::default();
+        let alice = User {
+            id: "user_1".to_string(),
+            ..User::new("Alice", "alice@example.com")
+        };
+        before.users.users.insert(alice.id.clone(), alice.clone());
+        before.products.stock.insert("p1".to_string(), 5);
+
+        let mut after = before.clone();
+        after.users.users.insert(
+            alice.id.clone(),
+            User {
+                email: "alice@example.org".to_string(),
+                ..alice
+            },
+        );
+        after.products.stock.remove("p1");
+        let order = Order::new("order_1", "user_1", "p1", 1, 10.0);
+        after.orders.orders.insert(order.id.clone(), order);
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.entities.len(), 3);
+        assert_eq!(
+            diff.to_string(),
+            "~ user user_1: email \"alice@example.com\" -> \"alice@example.org\";\n\
+             - stock p1\n\
+             + order order_1\n"
+        );
+        assert!(after.diff(&after).is_empty());
+    }
 
-/// ## Ingredient 7: Root Actor for Orchestration
-///
-/// **Pattern:** Root actors coordinate multiple sub-actors to implement complex
-/// business workflows. They don't store domain data themselves - instead they
-/// delegate to specialized sub-actors.
-///
-/// **Why This Works:**
-/// - **Separation of concerns** - Root actor focuses on orchestration
-/// - **Composition** - Complex operations built from simpler sub-operations
-/// - **Error handling** - Centralized error handling and rollback logic
-/// - **Tracing** - Full request flow visible across multiple actors
-///
-/// **Delegation Pattern:**
-/// 1. Validate inputs using sub-actors
-/// 2. Perform business logic steps in sequence
-/// 3. Handle errors and rollbacks appropriately
-/// 4. Return results to caller
-pub struct OrderService {
-    receiver: mpsc::Receiver<OrderRequest>,
-    user_client: UserClient,
-    product_client: ProductClient,
-    orders: HashMap<String, Order>,
-}
+    #[tokio::test]
+    async fn test_consistency_checker() -> Result<(), Box<dyn std::error::Error>> {
+        let mut snapshot = SystemSnapshot::default();
+        let product = Product::new("p1", "Widget", 10.0);
+        snapshot.products.products.insert("p1".to_string(), product);
+        snapshot.products.stock.insert("p1".to_string(), 5);
+        snapshot.products.stock.insert("p2".to_string(), 5);
+        let order = Order::new("order_1", "user_404", "p1", 1, 10.0);
+        snapshot.orders.orders.insert(order.id.clone(), order);
+        let system = OrderSystem::restore(snapshot);
+
+        let checker = ConsistencyChecker::new(
+            system.user_client.clone(),
+            system.product_client.clone(),
+            system.order_client.clone(),
+            Duration::from_secs(60),
+        );
+        assert_eq!(
+            checker.check().await?,
+            vec![
+                Violation::OrderUserMissing {
+                    order_id: "order_1".to_string(),
+                    user_id: "user_404".to_string(),
+                },
+                Violation::OrphanedStock {
+                    product_id: "p2".to_string(),
+                },
+            ]
+        );
 
-impl OrderService {
-    pub fn new(
-        buffer_size: usize,
-        user_client: UserClient,
-        product_client: ProductClient,
-    ) -> (Self, OrderClient) {
-        let (sender, receiver) = mpsc::channel(buffer_size);
-        let service = Self {
-            receiver,
-            user_client,
-            product_client,
-            orders: HashMap::new(),
-        };
-        let client = OrderClient::new(sender);
-        (service, client)
+        // The periodic loop ends on its own once the system is gone
+        system.shutdown().await?;
+        checker.run().await;
+        Ok(())
     }
 
-    #[instrument(name = "order_service", skip(self))]
-    pub async fn run(mut self) {
-        info!("OrderService starting");
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_event_codec_upcasts_old_versions() -> Result<(), Box<dyn std::error::Error>> {
+        // Pretend version 0 called the field `id`
+        let codec = EventCodec::new().with_upcaster(0, |mut event| {
+            let created = &mut event["UserCreated"];
+            created["user_id"] = created["id"].take();
+            Ok(event)
+        });
+        let old = r#"{"version":0,"event":{"UserCreated":{"id":"user_1"}}}"#;
+        let DomainEvent::UserCreated { user_id } = codec.decode(old)? else {
+            panic!("wrong variant");
+        };
+        assert_eq!(user_id, "user_1");
 
-        while let Some(msg) = self.receiver.recv().await {
-            match msg {
-                OrderRequest::CreateOrder { order, respond_to } => {
-                    self.handle_create_order(order, respond_to).await;
-                }
-                OrderRequest::GetOrder { id, respond_to } => {
-                    self.handle_get_order(id, respond_to);
-                }
-                OrderRequest::Shutdown => {
-                    info!("OrderService shutting down");
-                    break;
+        // Current events round-trip untouched; unknown versions are refused
+        let event = DomainEvent::UserUpdated {
+            user_id: "user_2".to_string(),
+        };
+        let json = codec.encode(&event)?;
+        assert!(matches!(
+            codec.decode(&json)?,
+            DomainEvent::UserUpdated { .. }
+        ));
+        assert!(EventCodec::new().decode(old).is_err());
+        assert!(codec.decode(r#"{"version":99,"event":null}"#).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handler_span_nests_under_client_call() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::Mutex;
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::registry::LookupSpan;
+
+        // Records (span, parent) names as spans open
+        type SpanParent = (String, Option<String>);
+        #[derive(Clone, Default)]
+        struct Parents(Arc<Mutex<Vec<SpanParent>>>);
+
+        impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for Parents {
+            fn on_new_span(
+                &self,
+                _attrs: &tracing::span::Attributes<'_>,
+                id: &tracing::span::Id,
+                ctx: Context<'_, S>,
+            ) {
+                if let Some(span) = ctx.span(id) {
+                    let parent = span.parent().map(|parent| parent.name().to_string());
+                    let mut parents = self.0.lock().unwrap_or_else(|e| e.into_inner());
+                    parents.push((span.name().to_string(), parent));
                 }
             }
         }
 
-        info!("OrderService stopped");
-    }
+        let parents = Parents::default();
+        let subscriber = tracing_subscriber::registry().with(parents.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
 
-    /// **Orchestration Handler Example** - Coordinates multiple sub-actors
-    ///
-    /// This demonstrates the core orchestration pattern:
-    /// 1. **Validate user** via UserService
-    /// 2. **Validate product** via ProductService  
-    /// 3. **Reserve stock** via ProductService
-    /// 4. **Create order** locally
-    ///
-    /// **Error Handling:** Each step can fail, and errors are propagated appropriately.
-    /// In a production system, you might add compensation logic (rollbacks).
-    ///
-    /// **Tracing:** The full workflow is traced across multiple actors, making
-    /// debugging complex flows much easier.
-    #[instrument(
-        fields(
-            order_id = %order.id,
-            user_id = %order.user_id,
-            product_id = %order.product_id,
-            quantity = %order.quantity,
-            total = %order.total
-        ),
-        skip(self, order, respond_to)
-    )]
-    async fn handle_create_order(
-        &mut self,
-        order: Order,
-        respond_to: ServiceResponse<String, OrderError>,
-    ) {
-        info!("Processing create_order request");
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+        user_client.get_user("user_1".to_string()).await?;
+        user_client.shutdown().await?;
 
-        // Step 1: Validate user via UserService
-        let user_result = self.user_client.get_user(order.user_id.clone()).await;
+        let parents = parents.0.lock().unwrap_or_else(|e| e.into_inner());
+        let handler = ("handle_get_user".to_string(), Some("get_user".to_string()));
+        assert!(parents.contains(&handler), "{:?}", parents);
+        Ok(())
+    }
 
-        let _user = match user_result {
-            Ok(Some(user)) => {
-                info!(user_name = %user.name, "User validation successful");
-                user
-            }
-            Ok(None) => {
-                error!("User not found");
-                send_error!(respond_to, OrderError::InvalidUser(order.user_id.clone()));
-            }
-            Err(e) => {
-                error!(error = %e, "User validation failed");
-                send_error!(
-                    respond_to,
-                    OrderError::InvalidUser(format!("User validation failed: {}", e))
-                );
+    #[tokio::test]
+    async fn test_request_id_flows_from_order_to_user_service() {
+        // A stand-in UserService that reports the request ID of each lookup
+        let (user_sender, mut user_receiver) = mpsc::channel(10);
+        let user_client = UserClient::new(user_sender);
+        let (product_service, product_client) = ProductService::new(10);
+        let _products = tokio::spawn(product_service.run());
+        let (order_service, order_client) = OrderService::new(10, user_client, product_client);
+        let _orders = tokio::spawn(order_service.run());
+
+        let (seen_sender, mut seen) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(request) = user_receiver.recv().await {
+                if let UserRequest::GetUser { id, respond_to } = request {
+                    let _ = seen_sender.send(respond_to.request_id());
+                    let _ = respond_to.send(Err(UserError::NotFound(id)));
+                }
             }
-        };
+        });
 
-        // Step 2: Validate product via ProductService
-        let product_result = self
-            .product_client
-            .get_product(order.product_id.clone())
-            .await;
+        // An ID already in scope is reused by nested calls
+        let order = Order::new("order_1", "user_1", "p1", 1, 10.0);
+        let outer = RequestId(42);
+        let result = RequestId::scope(Some(outer), order_client.create_order(order)).await;
+        assert!(result.is_err());
+        assert_eq!(seen.recv().await, Some(Some(outer)));
+
+        // Otherwise the outermost client call mints one
+        let order = Order::new("order_2", "user_1", "p1", 1, 10.0);
+        assert!(order_client.create_order(order).await.is_err());
+        let minted = seen.recv().await.flatten();
+        assert!(minted.is_some_and(|id| id != outer));
+    }
 
-        let _product = match product_result {
-            Ok(Some(product)) => {
-                info!(product_name = %product.name, price = %product.price, "Product validation successful");
-                product
-            }
-            Ok(None) => {
-                error!("Product not found");
-                send_error!(
-                    respond_to,
-                    OrderError::InvalidProduct(order.product_id.clone())
-                );
-            }
-            Err(e) => {
-                error!(error = %e, "Product validation failed");
-                send_error!(
-                    respond_to,
-                    OrderError::InvalidProduct(format!("Product validation failed: {}", e))
-                );
-            }
-        };
+    #[tokio::test]
+    async fn test_domain_gauges_follow_events() {
+        let metrics: &'static Metrics = Box::leak(Box::default());
+        let (events, receiver) = broadcast::channel(16);
+        let gauges = DomainGauges::new(receiver, metrics);
+
+        for event in [
+            DomainEvent::UserCreated {
+                user_id: "user_1".to_string(),
+            },
+            DomainEvent::StockReserved {
+                product_id: "p1".to_string(),
+                quantity: 3,
+            },
+            DomainEvent::StockReserved {
+                product_id: "p1".to_string(),
+                quantity: 4,
+            },
+            DomainEvent::OrderCreated {
+                order_id: "order_1".to_string(),
+                user_id: "user_1".to_string(),
+                product_id: "p1".to_string(),
+                quantity: 3,
+            },
+        ] {
+            events.send(event).unwrap();
+        }
+        drop(events);
+        gauges.run().await;
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("# TYPE domain_users gauge"));
+        assert!(rendered.contains("domain_users 1\n"));
+        assert!(rendered.contains("domain_units_reserved{product=\"p1\"} 7\n"));
+        assert!(rendered.contains("domain_orders{product=\"p1\"} 1\n"));
+    }
 
-        // Step 3: Reserve stock via ProductService
-        let reserve_result = self
-            .product_client
-            .reserve_stock(order.product_id.clone(), order.quantity)
-            .await;
+    #[tokio::test]
+    async fn test_slow_handler_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::Mutex;
+        use tracing_subscriber::layer::{Context, SubscriberExt};
 
-        if let Err(e) = reserve_result {
-            error!(error = %e, "Stock reservation failed");
-            send_error!(
-                respond_to,
-                OrderError::InsufficientStock(format!("Stock reservation failed: {}", e))
-            );
+        // Records the `request` field of every slow-handler warning
+        #[derive(Clone, Default)]
+        struct SlowRequests(Arc<Mutex<Vec<String>>>);
+
+        impl tracing::field::Visit for SlowRequests {
+            fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+
+            fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                if field.name() == "request" {
+                    let mut requests = self.0.lock().unwrap_or_else(|e| e.into_inner());
+                    requests.push(value.to_string());
+                }
+            }
         }
 
-        info!("Stock reserved successfully");
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SlowRequests {
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                if event.metadata().level() == &tracing::Level::WARN {
+                    event.record(&mut self.clone());
+                }
+            }
+        }
 
-        // Step 4: Create order (local operation)
-        self.orders.insert(order.id.clone(), order.clone());
+        let slow = SlowRequests::default();
+        let subscriber = tracing_subscriber::registry().with(slow.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
 
-        info!("Order created successfully");
-        let _ = respond_to.send(Ok(order.id));
-    }
+        let (user_service, user_client) = UserService::new(10);
+        let user_service = user_service.with_slow_handler(Duration::from_millis(50));
+        let _handle = tokio::spawn(user_service.run());
+        let user = User::new("Alice", "alice@example.com");
+        let id = user_client.create_user(user).await?;
+        user_client
+            .modify_user(id, |_| {
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(())
+            })
+            .await?;
+        user_client.shutdown().await?;
 
-    #[instrument(fields(order_id = %id), skip(self, respond_to))]
-    fn handle_get_order(&self, id: String, respond_to: ServiceResponse<Option<Order>, OrderError>) {
-        debug!("Processing get_order request");
+        let slow = slow.0.lock().unwrap_or_else(|e| e.into_inner());
+        assert_eq!(*slow, vec!["ModifyUser".to_string()]);
 
-        let order = self.orders.get(&id).cloned();
+        // Hand-written client methods are timed in the run loop too
+        let rendered = Metrics::global().render();
+        let handled =
+            "actor_handler_duration_seconds_count{entity=\"User\",request=\"ModifyUser\"}";
+        assert!(rendered.contains(handled));
+        Ok(())
+    }
 
-        match &order {
-            Some(order) => info!(total = %order.total, "Order found"),
-            None => debug!("Order not found"),
+    #[tokio::test]
+    async fn test_health_monitor_reports_every_actor() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::new();
+        let (monitor, health) = system.health_monitor(Duration::from_millis(100));
+        monitor.check().await;
+        {
+            let report = health.borrow();
+            assert!(report.is_healthy());
+            let names: Vec<_> = report.actors.iter().map(|actor| actor.name).collect();
+            assert_eq!(names, ["users", "products", "orders"]);
         }
 
-        let _ = respond_to.send(Ok(order));
+        // A stopped actor fails its next ping and stays failed
+        system.shutdown().await?;
+        monitor.check().await;
+        monitor.check().await;
+        let report = health.borrow();
+        assert!(!report.is_healthy());
+        let user_health = report.actor("users").ok_or("users not reported")?;
+        assert_eq!(user_health.failures, 2);
+        assert!(user_health.last_seen.is_some());
+        Ok(())
     }
-}
 
-#[derive(Clone)]
-pub struct OrderClient {
-    sender: mpsc::Sender<OrderRequest>,
-}
+    #[tokio::test]
+    async fn test_start_waits_for_every_actor() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::start().await?;
+        system.shutdown().await?;
+
+        // A user service whose mailbox is already gone can never become ready
+        let (user_sender, user_receiver) = mpsc::channel(1);
+        drop(user_receiver);
+        let result = OrderSystem::builder()
+            .user_client(UserClient::new(user_sender))
+            .start()
+            .await;
+        assert!(matches!(
+            result,
+            Err(StartupError::ActorNotReady { actor: "users", .. })
+        ));
+        Ok(())
+    }
 
-impl OrderClient {
-    pub fn new(sender: mpsc::Sender<OrderRequest>) -> Self {
-        Self { sender }
+    #[tokio::test]
+    async fn test_audit_log_records_user_history() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::builder().audit_log(true).build();
+        let audit = system.audit.clone().ok_or("audit log not started")?;
+        let id = system
+            .user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        system
+            .user_client
+            .update_user(id.clone(), User::new("Alice", "alice@example.org"))
+            .await?;
+
+        let history = audit.query(AuditQuery::entity("User", id.clone())).await?;
+        let actions: Vec<_> = history.iter().map(|record| record.action.clone()).collect();
+        assert_eq!(actions, [AuditAction::Create, AuditAction::Update]);
+        assert!(history[0].before.is_none());
+        assert_eq!(history[1].before, history[0].after);
+        assert!(history.iter().all(|record| record.request_id.is_some()));
+
+        // Nothing was recorded in the first second of the epoch
+        let query = AuditQuery::entity("User", id).between(0, 1);
+        assert!(audit.query(query).await?.is_empty());
+
+        system.shutdown().await?;
+        Ok(())
     }
 
-    #[instrument(skip(self))]
-    pub async fn shutdown(&self) -> Result<(), String> {
-        debug!("Sending shutdown request");
-        self.sender
-            .send(OrderRequest::Shutdown)
-            .await
-            .map_err(|e| e.to_string())?;
+    #[tokio::test]
+    async fn test_audit_log_retention_per_entity() -> Result<(), Box<dyn std::error::Error>> {
+        let (audit_service, audit) = AuditService::new(10);
+        let audit_service = audit_service
+            .with_retention(2)
+            .with_entity_retention("Order", usize::MAX);
+        let _handle = tokio::spawn(audit_service.run());
+        for i in 0..3 {
+            let id = format!("{}", i);
+            audit
+                .record(AuditRecord::new(
+                    "User",
+                    &id,
+                    AuditAction::Create,
+                    None,
+                    None,
+                ))
+                .await;
+            audit
+                .record(AuditRecord::new(
+                    "Order",
+                    &id,
+                    AuditAction::Create,
+                    None,
+                    None,
+                ))
+                .await;
+        }
+
+        let kept = |entity| {
+            let audit = audit.clone();
+            async move {
+                let query = AuditQuery {
+                    entity: Some(entity),
+                    ..AuditQuery::default()
+                };
+                let records = audit.query(query).await?;
+                Ok::<_, String>(
+                    records
+                        .into_iter()
+                        .map(|record| record.id)
+                        .collect::<Vec<_>>(),
+                )
+            }
+        };
+        assert_eq!(kept("User").await?, ["1", "2"]);
+        assert_eq!(kept("Order").await?, ["0", "1", "2"]);
+
+        audit.shutdown().await?;
         Ok(())
     }
-}
 
-// Generate order client methods
-client_method!(OrderClient => fn create_order(order: Order) -> String as OrderRequest::CreateOrder);
-client_method!(OrderClient => fn get_order(id: String) -> Option<Order> as OrderRequest::GetOrder);
+    #[tokio::test]
+    async fn test_shell_commands_drive_the_system() -> Result<(), Box<dyn std::error::Error>> {
+        let system = OrderSystem::new();
+        let mut shell = Shell::new(&system);
+
+        let user_id = shell.execute("user create Alice alice@example.com").await?;
+        shell.execute("product add p1 Widget 2.50 10").await?;
+        let order_id = shell
+            .execute(&format!("order create {} p1 4", user_id))
+            .await?;
+        assert_eq!(order_id, "order_1");
+        assert_eq!(shell.execute("product stock p1").await?, "p1 in stock: 6");
+        assert_eq!(
+            shell.execute("order get order_1").await?,
+            format!("order_1 user={} product=p1 quantity=4 total=10.00", user_id)
+        );
+        assert!(shell.execute("order create nobody p1 1").await.is_err());
+        assert!(shell.execute("frobnicate").await.is_err());
 
-// =============================================================================
-// INGREDIENT 8: SYSTEM COORDINATOR
-// =============================================================================
+        system.shutdown().await?;
+        Ok(())
+    }
 
-/// ## Ingredient 8: System Coordinator
-///
-/// **Pattern:** The coordinator manages the lifecycle of the entire actor system.
-/// It handles startup, dependency injection, and graceful shutdown.
-///
-/// **Responsibilities:**
-/// - **Start sub-actors first** - Ensure dependencies are available
-/// - **Inject dependencies** - Pass sub-actor clients to root actors
-/// - **Manage handles** - Track all spawned tasks for proper cleanup
-/// - **Graceful shutdown** - Shutdown in dependency order and wait for completion
-///
-/// **Benefits:**
-/// - **Single point of control** - Easy to manage the entire system
-/// - **Proper initialization order** - Dependencies started before dependents
-/// - **Clean shutdown** - No zombie processes or resource leaks
-/// - **Error handling** - Centralized error handling for system-wide issues
-pub struct OrderSystem {
-    pub order_client: OrderClient,
-    pub user_client: UserClient,
-    pub product_client: ProductClient,
-    handles: Vec<tokio::task::JoinHandle<()>>,
-}
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot_codec_reads_unversioned_files() -> Result<(), Box<dyn std::error::Error>> {
+        let mut snapshot = SystemSnapshot::default();
+        snapshot.users.users.insert(
+            "user_1".to_string(),
+            User::new("Alice", "alice@example.com"),
+        );
+        let codec = SnapshotCodec::new();
 
-impl Default for OrderSystem {
-    fn default() -> Self {
-        Self::new()
+        // Files written before versioning are the bare snapshot
+        let legacy = serde_json::to_string(&snapshot)?;
+        assert_eq!(codec.decode(&legacy)?.users.users.len(), 1);
+
+        let current = codec.encode(&snapshot)?;
+        assert!(current.contains("\"version\": 1"));
+        assert_eq!(codec.decode(&current)?.users.users.len(), 1);
+        assert!(codec.decode(r#"{"version":99,"snapshot":null}"#).is_err());
+        Ok(())
     }
-}
 
-impl OrderSystem {
-    /// Create and start the entire actor system
-    ///
-    /// **Startup Order:**
-    /// 1. Start sub-actors (UserService, ProductService)
-    /// 2. Start root actors (OrderService) with sub-actor clients
-    /// 3. Return coordinator with all clients for external use
-    #[instrument(name = "order_system")]
-    pub fn new() -> Self {
-        let mut handles = Vec::new();
+    #[tokio::test]
+    async fn test_validation_reports_every_bad_field() -> Result<(), Box<dyn std::error::Error>> {
+        let user = User::new(" ", "not-an-email");
+        let fields: Vec<_> = user
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, ["name", "email"]);
+
+        let (product_service, product_client) = ProductService::new(10);
+        let _handle = tokio::spawn(product_service.run());
+        let catalog = ProductSnapshot {
+            products: HashMap::from([("p1".to_string(), Product::new("p1", "Widget", -1.0))]),
+            stock: HashMap::from([("p1".to_string(), 5)]),
+            ..Default::default()
+        };
+        let error = product_client.import(catalog).await.unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid product: p1.price: must be a non-negative number"
+        );
+        assert!(product_client
+            .get_product("p1".to_string())
+            .await?
+            .is_none());
 
-        info!("Starting order system");
+        product_client.shutdown().await?;
+        Ok(())
+    }
 
-        // Start sub-actors first (no dependencies)
-        let (user_service, user_client) = UserService::new(100);
-        handles.push(tokio::spawn(user_service.run()));
+    #[tokio::test]
+    async fn test_get_or_create_user_creates_once() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(32);
+        let _handle = tokio::spawn(user_service.run());
 
-        let (product_service, product_client) = ProductService::new(100);
-        handles.push(tokio::spawn(product_service.run()));
+        let user = User::new("Alice", "alice@example.com");
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                let (client, user) = (user_client.clone(), user.clone());
+                tokio::spawn(async move { client.get_or_create_user(user).await })
+            })
+            .collect();
+        let mut ids = Vec::new();
+        for call in calls {
+            ids.push(call.await??);
+        }
 
-        // Start root actor with sub-actor clients (dependency injection)
-        let (order_service, order_client) =
-            OrderService::new(100, user_client.clone(), product_client.clone());
-        handles.push(tokio::spawn(order_service.run()));
+        assert!(ids.iter().all(|id| *id == ids[0]));
+        assert_eq!(user_client.list_users().await?.len(), 1);
 
-        info!("Order system started successfully");
+        user_client.shutdown().await?;
+        Ok(())
+    }
 
-        Self {
-            order_client,
-            user_client,
-            product_client,
-            handles,
+    #[tokio::test]
+    async fn test_get_products_merges_across_shards() -> Result<(), Box<dyn std::error::Error>> {
+        let (product_service, product_client) = ShardedProductService::new(4, 32);
+        let _handle = tokio::spawn(product_service.run());
+
+        let ids: Vec<String> = (0..10).map(|i| format!("p{}", i)).collect();
+        let mut snapshot = ProductSnapshot::default();
+        for id in &ids[..6] {
+            let product = Product::new(id.clone(), "Widget", 1.0);
+            snapshot.products.insert(id.clone(), product);
         }
+        product_client.import(snapshot).await?;
+
+        let found = product_client.get_products(ids).await?;
+        assert_eq!(found.len(), 6);
+        assert!(found.contains_key("p0") && !found.contains_key("p9"));
+
+        product_client.shutdown().await?;
+        Ok(())
     }
 
-    /// Gracefully shutdown the entire actor system
-    ///
-    /// **Shutdown Order:**
-    /// 1. Shutdown root actors first (they depend on sub-actors)
-    /// 2. Shutdown sub-actors  
-    /// 3. Wait for all tasks to complete
-    ///
-    /// **Error Handling:** Log errors but continue shutdown to prevent hangs
-    #[instrument(skip(self))]
-    pub async fn shutdown(self) -> Result<(), String> {
-        info!("Shutting down order system");
+    #[tokio::test]
+    async fn test_create_users_is_all_or_nothing() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(32);
+        let _handle = tokio::spawn(user_service.run());
 
-        // Shutdown in dependency order (root actors first)
-        let _ = self.order_client.shutdown().await;
-        let _ = self.user_client.shutdown().await;
-        let _ = self.product_client.shutdown().await;
+        let good = User::new("Alice", "alice@example.com");
+        let bad = User::new("", "not-an-email");
+        let rejected = user_client.create_users(vec![good.clone(), bad]).await;
+        let error = rejected.unwrap_err();
+        assert!(error.contains("1.name"), "{}", error);
+        assert!(user_client.list_users().await?.is_empty());
 
-        // Wait for all services to finish
-        for handle in self.handles {
-            if let Err(e) = handle.await {
-                error!(error = ?e, "Service shutdown error");
-            }
-        }
+        let bob = User::new("Bob", "bob@example.com");
+        let ids = user_client.create_users(vec![good, bob]).await?;
+        assert_eq!(ids, ["user_1", "user_2"]);
 
-        info!("Order system shutdown complete");
+        user_client.shutdown().await?;
         Ok(())
     }
-}
 
-// =============================================================================
-// INGREDIENT 9: TRACING SETUP
-// =============================================================================
+    #[tokio::test]
+    async fn test_update_products_reports_per_item() -> Result<(), Box<dyn std::error::Error>> {
+        let (product_service, product_client) = ShardedProductService::new(3, 32);
+        let _handle = tokio::spawn(product_service.run());
+
+        let mut snapshot = ProductSnapshot::default();
+        for id in ["p1", "p2", "p3"] {
+            let product = Product::new(id, "Widget", 10.0);
+            snapshot.products.insert(id.to_string(), product);
+        }
+        product_client.import(snapshot).await?;
+
+        let patches = vec![
+            ("p1".to_string(), ProductPatch::default().price(12.0)),
+            ("missing".to_string(), ProductPatch::default().price(1.0)),
+            ("p2".to_string(), ProductPatch::default().price(-1.0)),
+            ("p3".to_string(), ProductPatch::default().name("Gadget")),
+        ];
+        let results = product_client.update_products(patches).await?;
+
+        assert!(results[0].is_ok() && results[3].is_ok());
+        assert!(matches!(results[1], Err(ProductError::NotFound(_))));
+        assert!(matches!(results[2], Err(ProductError::InvalidFields(_))));
+        let products = product_client.snapshot().await?.products;
+        assert_eq!(products["p1"].price, 12.0);
+        assert_eq!(products["p2"].price, 10.0);
+        assert_eq!(products["p3"].name, "Gadget");
+
+        product_client.shutdown().await?;
+        Ok(())
+    }
 
-/// ## Ingredient 9: Production-Ready Tracing Setup
-///
-/// **Pattern:** Configure tracing once at application startup for the entire process.
-/// All actors and spans automatically use this configuration.
-///
-/// **Key Features:**
-/// - **Environment-based filtering** - Use `RUST_LOG` env var to control verbosity
-/// - **Built-in timing** - See how long each operation takes
-/// - **Structured output** - Easy to parse and search logs
-/// - **Compact format** - Readable but not verbose
-///
-/// **Usage:**
-/// ```bash
-/// RUST_LOG=debug cargo run    # Show debug logs
-/// RUST_LOG=info cargo run     # Show info logs only  
-/// RUST_LOG=warn cargo run     # Show warnings and errors only
-///
-/// # For per-module logging, organize services into separate modules:
-/// # RUST_LOG=my_app::user_service=debug,my_app::order_service=info cargo run
-/// ```
-fn setup_tracing() {
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    #[tokio::test]
+    async fn test_email_index_follows_updates() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(32);
+        let _handle = tokio::spawn(user_service.run());
 
-    tracing_subscriber::fmt()
-        .with_env_filter(env_filter)
-        .with_timer(tracing_subscriber::fmt::time::uptime())
-        .compact()
-        .init();
-}
+        let id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        let found = user_client
+            .find_users_by_email("alice@example.com".to_string())
+            .await?;
+        assert_eq!(found.len(), 1);
+
+        user_client
+            .update_user(id.clone(), User::new("Alice", "alice@work.example"))
+            .await?;
+        let old = user_client
+            .find_users_by_email("alice@example.com".to_string())
+            .await?;
+        assert!(old.is_empty());
+        let new = user_client
+            .find_users_by_email("alice@work.example".to_string())
+            .await?;
+        assert_eq!(new[0].name, "Alice");
 
-// =============================================================================
-// INGREDIENT 10: HANDLER PATTERNS
-// =============================================================================
+        user_client.shutdown().await?;
+        Ok(())
+    }
 
-/// ## Ingredient 10: Advanced Handler Patterns
-///
-/// Beyond basic request-response, actors often need to handle different types of operations:
-///
-/// ### Sync vs Async Handlers
-///
-/// **Sync Handlers** (fast, in-memory):
-/// ```rust
-/// fn handle_get_user_sync(&self, id: String, respond_to: oneshot::Sender<...>) {
-///     let result = self.users.get(&id).cloned(); // No await!
-///     let _ = respond_to.send(Ok(result));
-/// }
-/// ```
-///
-/// **Async Handlers** (I/O, validation):
-/// ```rust
-/// async fn handle_create_user_async(&mut self, user: User, respond_to: oneshot::Sender<...>) {
-///     // Async email validation
-///     validate_email_externally(&user.email).await?;
-///     let id = self.create_user_internal(user);
-///     let _ = respond_to.send(Ok(id));
-/// }
-/// ```
-///
-/// ### Background Operations
-///
-/// **Return Immediately, Work Continues:**
-/// ```rust
-/// fn handle_send_email_background(&self, user_id: String, respond_to: oneshot::Sender<...>) {
-///     // Return success immediately
-///     let _ = respond_to.send(Ok(()));
-///     
-///     // Spawn background work
-///     tokio::spawn(async move {
-///         send_welcome_email(user_id).await;
-///     });
-/// }
-/// ```
-///
-/// **Return Job ID, Work Continues:**
-/// ```rust
-/// fn handle_generate_report(&self, user_id: String, respond_to: oneshot::Sender<...>) {
-///     let job_id = generate_job_id();
-///     let _ = respond_to.send(Ok(job_id.clone()));
-///     
-///     tokio::spawn(async move {
-///         let report = generate_report(user_id).await;
-///         save_report(job_id, report).await;
-///     });
-/// }
-/// ```
-///
-/// ### When to Use Each Pattern
-///
-/// - **Sync**: Fast lookups, in-memory operations, simple computations
-/// - **Async**: Database calls, external APIs, file I/O, complex validation
-/// - **Background**: Email sending, report generation, cleanup tasks, analytics
-///
-/// Example of a background operation that returns immediately and continues work
-impl UserService {
-    /// **Background Handler Example** - Task owns the response channel
-    ///
-    /// This pattern shows how the spawned task can take ownership of respond_to
-    /// and send the response after the work completes.
-    #[instrument(fields(user_id = %user_id), skip(self, respond_to))]
-    pub async fn handle_send_welcome_email_background(
-        &self,
-        user_id: String,
-        respond_to: ServiceResponse<(), UserError>,
-    ) {
-        debug!("Processing send_welcome_email request");
+    #[tokio::test]
+    async fn test_email_is_unique() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(32);
+        let _handle = tokio::spawn(user_service.run());
+
+        user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        let bob = user_client
+            .create_user(User::new("Bob", "bob@example.com"))
+            .await?;
+
+        let dup = user_client
+            .create_user(User::new("Alice 2", "alice@example.com"))
+            .await;
+        assert_eq!(dup.unwrap_err(), "User already exists: alice@example.com");
+        let stolen = user_client
+            .update_user(bob.clone(), User::new("Bob", "alice@example.com"))
+            .await;
+        assert!(stolen.is_err());
+        // Keeping your own email is not a conflict
+        user_client
+            .update_user(bob, User::new("Robert", "bob@example.com"))
+            .await?;
+        assert_eq!(user_client.count().await?, 2);
 
-        // Spawn background task - it takes ownership of respond_to
-        tokio::spawn(async move {
-            info!(user_id = %user_id, "Starting background email send");
+        user_client.shutdown().await?;
+        Ok(())
+    }
 
-            // Simulate slow email sending
-            tokio::time::sleep(Duration::from_millis(500)).await;
+    #[tokio::test(start_paused = true)]
+    async fn test_stock_hold_expires_unless_confirmed() -> Result<(), Box<dyn std::error::Error>> {
+        let (bus_service, bus) = EventBusService::new(16, 16);
+        let _bus_handle = tokio::spawn(bus_service.run());
+        let mut observed = bus.subscribe().await?;
+        let (product_service, product_client) = ProductService::new(16);
+        let product_service = product_service
+            .with_clock(Arc::new(TestClock::default()))
+            .with_tick_interval(Duration::from_secs(1))
+            .with_event_bus(bus.clone());
+        let _handle = tokio::spawn(product_service.run());
+        let mut snapshot = ProductSnapshot::default();
+        snapshot.stock.insert("p1".to_string(), 10);
+        snapshot
+            .products
+            .insert("p1".to_string(), Product::new("p1", "Widget", 1.0));
+        product_client.import(snapshot).await?;
+
+        let kept = product_client
+            .hold_stock("p1".to_string(), 3, Duration::from_secs(60))
+            .await?;
+        let lapsed = product_client
+            .hold_stock("p1".to_string(), 4, Duration::from_secs(60))
+            .await?;
+        assert_eq!(product_client.check_stock("p1".to_string()).await?, 3);
+        product_client.confirm_hold("p1".to_string(), kept).await?;
+
+        tokio::time::sleep(Duration::from_secs(61)).await;
+        assert_eq!(product_client.check_stock("p1".to_string()).await?, 7);
+        let late = product_client
+            .confirm_hold("p1".to_string(), lapsed)
+            .await
+            .unwrap_err();
+        assert_eq!(late, format!("Stock hold not found: {}", lapsed));
 
-            // Simulate email service call
-            let success = true; // In real code, this would be an actual email API call
+        let expired = loop {
+            if let DomainEvent::HoldExpired { hold, quantity, .. } = observed.recv().await? {
+                break (hold, quantity);
+            }
+        };
+        assert_eq!(expired, (lapsed, 4));
 
-            let result = if success {
-                info!(user_id = %user_id, "Welcome email sent successfully");
-                Ok(())
-            } else {
-                error!(user_id = %user_id, "Failed to send welcome email");
-                Err(UserError::DatabaseError("Email service failed".to_string()))
-            };
+        product_client.shutdown().await?;
+        bus.shutdown().await?;
+        Ok(())
+    }
 
-            // Task responds when work is actually done
-            let _ = respond_to.send(result);
-        });
+    #[tokio::test]
+    async fn test_zero_tick_interval_is_clamped() -> Result<(), Box<dyn std::error::Error>> {
+        let (product_service, product_client) = ProductService::new(10);
+        let product_service = product_service.with_tick_interval(Duration::ZERO);
+        let handle = tokio::spawn(product_service.run());
+
+        // A zero interval would panic as soon as the run loop starts
+        product_client.ping().await?;
+        product_client.shutdown().await?;
+        handle.await?;
+        Ok(())
     }
 
-    /// **Alternative Background Pattern** - Return job ID immediately
-    ///
-    /// Shows another way: return a job ID immediately, do work in background.
-    /// Caller can use the job ID to check status later.
-    #[instrument(fields(user_id = %user_id), skip(self, respond_to))]
-    pub async fn handle_generate_report_background(
-        &self,
-        user_id: String,
-        respond_to: ServiceResponse<String, UserError>,
-    ) {
-        debug!("Processing generate_report request");
+    #[tokio::test]
+    async fn test_capacity_evicts_least_recently_used() -> Result<(), Box<dyn std::error::Error>> {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = evicted.clone();
+        let (user_service, user_client) = UserService::new(16);
+        let user_service = user_service
+            .with_capacity(2)
+            .on_evict(move |id, _| sink.lock().unwrap().push(id));
+        let _handle = tokio::spawn(user_service.run());
 
-        // Generate a job ID and return it immediately
-        let timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        let job_id = format!("job_{}_{}", user_id, timestamp);
+        let alice = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+        let bob = user_client
+            .create_user(User::new("Bob", "bob@example.com"))
+            .await?;
+        // Reading Alice makes Bob the least recently used
+        user_client.get_user(alice.clone()).await?;
+        user_client
+            .create_user(User::new("Carol", "carol@example.com"))
+            .await?;
+
+        assert_eq!(*evicted.lock().unwrap(), [bob.as_str()]);
+        assert_eq!(user_client.count().await?, 2);
+        assert!(user_client.get_user(bob).await?.is_none());
+        assert!(user_client.get_user(alice).await?.is_some());
 
-        info!(job_id = %job_id, "Report generation started");
-        let _ = respond_to.send(Ok(job_id.clone()));
+        user_client.shutdown().await?;
+        Ok(())
+    }
 
-        // Spawn background task for the actual report generation
-        let user_data = self.users.get(&user_id).cloned();
+    #[tokio::test]
+    async fn test_rejected_create_evicts_nobody() -> Result<(), Box<dyn std::error::Error>> {
+        let evicted = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = evicted.clone();
+        let (user_service, _user_client) = UserService::new(16);
+        let mut user_service = user_service
+            .with_capacity(1)
+            .on_evict(move |id, _| sink.lock().unwrap().push(id));
+        let alice = User::new("Alice", "alice@example.com");
+        user_service
+            .insert_new_user("user_1".to_string(), alice)
+            .await?;
+
+        // Written, then rolled back by the invariant check
+        let bob = User::new("Bob", "");
+        let result = user_service
+            .insert_new_user("user_2".to_string(), bob)
+            .await;
+        assert!(result.is_err());
+        assert!(evicted.lock().unwrap().is_empty());
+        assert!(user_service.users.contains_key("user_1"));
+        Ok(())
+    }
 
-        tokio::spawn(async move {
-            info!(job_id = %job_id, "Starting background report generation");
+    #[tokio::test]
+    async fn test_shared_product_survives_patch() -> Result<(), Box<dyn std::error::Error>> {
+        let (product_service, product_client) = ProductService::new(16);
+        let _handle = tokio::spawn(product_service.run());
+        let mut snapshot = ProductSnapshot::default();
+        snapshot
+            .products
+            .insert("p1".to_string(), Product::new("p1", "Widget", 10.0));
+        product_client.import(snapshot).await?;
+
+        let shared = product_client
+            .get_product_shared("p1".to_string())
+            .await?
+            .ok_or("missing")?;
+        let again = product_client
+            .get_product_shared("p1".to_string())
+            .await?
+            .ok_or("missing")?;
+        assert!(Arc::ptr_eq(&shared, &again));
+
+        let patch = ProductPatch::default().price(12.0);
+        product_client
+            .update_products(vec![("p1".to_string(), patch)])
+            .await?;
+        assert_eq!(shared.price, 10.0);
+        let patched = product_client
+            .get_product_shared("p1".to_string())
+            .await?
+            .ok_or("missing")?;
+        assert_eq!(patched.price, 12.0);
+
+        product_client.shutdown().await?;
+        Ok(())
+    }
 
-            // Simulate slow report generation
-            tokio::time::sleep(Duration::from_millis(2000)).await;
+    #[tokio::test]
+    async fn test_read_view_serves_without_the_actor() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut product_service, product_client) = ShardedProductService::new(2, 16);
+        let catalog = product_service.read_view();
+        let _handle = tokio::spawn(product_service.run());
+
+        let mut snapshot = ProductSnapshot::default();
+        for id in ["p1", "p2", "p3"] {
+            snapshot
+                .products
+                .insert(id.to_string(), Product::new(id, "Widget", 1.0));
+        }
+        product_client.import(snapshot).await?;
+        let patch = ProductPatch::default().name("Gadget");
+        product_client
+            .update_products(vec![("p2".to_string(), patch)])
+            .await?;
+        product_client.shutdown().await?;
+
+        // The actor is gone; the last published state is still readable
+        assert_eq!(catalog.len(), 3);
+        assert_eq!(catalog.get("p2").ok_or("missing")?.name, "Gadget");
+        assert!(catalog.get("p9").is_none());
+        Ok(())
+    }
 
-            match user_data {
-                Some(user) => {
-                    info!(
-                        job_id = %job_id,
-                        user_name = %user.name,
-                        "Report generated successfully"
-                    );
-                    // In real code, you would save the report somewhere
-                    // and maybe notify the user that it's ready
-                }
-                None => {
-                    error!(job_id = %job_id, "Cannot generate report: user not found");
-                }
+    #[tokio::test]
+    async fn test_overloaded_actor_sheds_but_answers_pings(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(16);
+        let user_service = user_service.with_load_shedding(4).with_batch_size(1);
+
+        // Queue a burst before the actor starts, then let it loose
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                let user_client = user_client.clone();
+                tokio::spawn(async move { user_client.count().await })
+            })
+            .collect();
+        let ping = {
+            let user_client = user_client.clone();
+            tokio::spawn(async move { user_client.ping().await })
+        };
+        tokio::task::yield_now().await;
+        let _handle = tokio::spawn(user_service.run());
+
+        let mut shed = 0;
+        for call in calls {
+            if let Err(e) = call.await? {
+                assert_eq!(e, "User service is overloaded");
+                shed += 1;
             }
-        });
+        }
+        assert!(shed > 0);
+        ping.await??;
+        // The backlog is gone, so requests are served again
+        assert_eq!(user_client.count().await?, 0);
+
+        user_client.shutdown().await?;
+        Ok(())
     }
-}
 
-/// Example of concurrent monitoring for performance and blocking detection
-///
-/// **Pattern:** Use a background task to periodically check system health
-/// This can be used for automated alerting or circuit breaker patterns.
-///
-/// **Blocking Detection:** Uses timeouts to detect when requests hang on the server:
-/// - Normal response: < 100ms (debug log)
-/// - Slow response: 100-500ms (warning - potential overload)
-/// - Timeout: > 500ms (error - likely blocked/hanging)
-pub async fn performance_monitor(user_client: UserClient, interval: Duration) {
-    let mut interval_timer = tokio::time::interval(interval);
+    #[test]
+    fn test_load_shedder_hysteresis() {
+        let mut shedder = LoadShedder::new(10);
+        assert!(!shedder.overloaded(9, USER_TARGET));
+        assert!(shedder.overloaded(10, USER_TARGET));
+        // Stays on while the queue drains towards half the threshold
+        assert!(shedder.overloaded(9, USER_TARGET));
+        assert!(shedder.overloaded(6, USER_TARGET));
+        assert!(!shedder.overloaded(5, USER_TARGET));
+        assert!(!shedder.overloaded(9, USER_TARGET));
+    }
 
-    loop {
-        interval_timer.tick().await;
-        check_health(&user_client).await;
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_per_caller() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+        let limiter = Arc::new(RateLimiter::new(RateLimitConfig {
+            per_second: 10.0,
+            burst: 2,
+        }));
+        let alice = user_client
+            .clone()
+            .with_rate_limit(limiter.clone())
+            .with_caller("alice");
+        let bob = user_client
+            .with_rate_limit(limiter.clone())
+            .with_caller("bob");
+
+        alice.count().await?;
+        alice.count().await?;
+        let limited = alice.count().await.unwrap_err();
+        assert_eq!(limited, "Rate limited: retry after 100ms");
+
+        // Bob draws from a separate bucket, and Alice's refills with time
+        bob.count().await?;
+        tokio::time::advance(Duration::from_millis(100)).await;
+        alice.count().await?;
+
+        // Once Alice's bucket has refilled it is forgotten
+        tokio::time::advance(Duration::from_secs(1)).await;
+        bob.count().await?;
+        let buckets = limiter.buckets.lock().map_err(|e| e.to_string())?;
+        assert_eq!(buckets.by_caller.keys().collect::<Vec<_>>(), ["bob"]);
+        Ok(())
     }
-}
 
-async fn check_health(user_client: &UserClient) {
-    let start = std::time::Instant::now();
-    let timeout = Duration::from_millis(500);
+    #[tokio::test(start_paused = true)]
+    async fn test_send_after_delivers_and_cancels() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _users = tokio::spawn(user_service.run());
+        let (scheduler, scheduler_client) = SchedulerService::new(10);
+        let _scheduler = tokio::spawn(scheduler.run());
+
+        let create = |name: &str| {
+            let (respond_to, _) = ServiceResponse::channel();
+            UserRequest::CreateUser {
+                user: User::new(name, format!("{}@example.com", name.to_lowercase())),
+                respond_to,
+            }
+        };
+        let kept = user_client
+            .send_after(&scheduler_client, Duration::from_secs(1), create("Alice"))
+            .await?;
+        let cancelled = user_client
+            .send_after(&scheduler_client, Duration::from_secs(2), create("Bob"))
+            .await?;
+        assert_eq!(scheduler_client.pending().await?, 2);
+        assert!(cancelled.cancel().await?);
+
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(user_client.count().await?, 1);
+        assert_eq!(scheduler_client.pending().await?, 0);
+        // Both have left the queue: one fired, one was cancelled
+        assert!(!kept.cancel().await?);
+        assert!(!cancelled.cancel().await?);
+        Ok(())
+    }
 
-    match tokio::time::timeout(timeout, user_client.get_user("health_check".to_string())).await {
-        Ok(Ok(_)) => log_response_time(start.elapsed()),
-        Ok(Err(e)) => {
-            error!(error = %e, duration_ms = start.elapsed().as_millis(), "Health check failed")
-        }
-        Err(_) => error!(
-            timeout_ms = timeout.as_millis(),
-            "Health check timed out - server may be blocked/overloaded"
-        ),
+    #[test]
+    fn test_cron_schedule_next_after() {
+        // 2024-01-01 00:00:00 UTC was a Monday
+        let monday = 1_704_067_200;
+        let hour = 3600;
+
+        let quarter_hours = CronSchedule::parse("*/15 9-17 * * 1-5").unwrap();
+        assert_eq!(quarter_hours.next_after(monday), Some(monday + 9 * hour));
+        assert_eq!(
+            quarter_hours.next_after(monday + 9 * hour),
+            Some(monday + 9 * hour + 15 * 60)
+        );
+        // Friday 17:45 rolls over the weekend to Monday 09:00
+        let friday_close = monday + 4 * 24 * hour + 17 * hour + 45 * 60;
+        assert_eq!(
+            quarter_hours.next_after(friday_close),
+            Some(monday + 7 * 24 * hour + 9 * hour)
+        );
+
+        // Leap day, and a date that never exists
+        let leap_day = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(leap_day.next_after(monday), Some(1_709_164_800));
+        assert_eq!(
+            CronSchedule::parse("0 0 30 2 *")
+                .unwrap()
+                .next_after(monday),
+            None
+        );
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
     }
-}
 
-fn log_response_time(duration: Duration) {
-    let duration_ms = duration.as_millis();
-    if duration > Duration::from_millis(100) {
-        warn!(
-            duration_ms,
-            "Health check slow but completed - potential server overload"
+    #[tokio::test(start_paused = true)]
+    async fn test_recurring_job_runs_until_cancelled() -> Result<(), Box<dyn std::error::Error>> {
+        let (scheduler, scheduler_client) = SchedulerService::new(10);
+        let _scheduler = tokio::spawn(scheduler.run());
+        let runs = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let counter = runs.clone();
+        let job = scheduler_client
+            .recurring(Recurrence::Every(Duration::from_secs(1)), move || {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async {}
+            })
+            .await?;
+
+        tokio::time::sleep(Duration::from_millis(3500)).await;
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // Unlike a one-off delivery, a recurring job is still pending after running
+        assert!(job.cancel().await?);
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(runs.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert_eq!(scheduler_client.pending().await?, 0);
+
+        let zero = scheduler_client
+            .recurring(Recurrence::Every(Duration::ZERO), || async {})
+            .await;
+        assert_eq!(
+            zero.err().as_deref(),
+            Some("Recurring period must be greater than zero")
         );
-    } else {
-        debug!(duration_ms, "Health check completed normally");
+        assert_eq!(scheduler_client.pending().await?, 0);
+        Ok(())
     }
-}
 
-// =============================================================================
-// USAGE EXAMPLE AND DEMO
-// =============================================================================
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_call_stops_actor_work() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::Mutex;
+        use tracing_subscriber::layer::{Context, SubscriberExt};
 
-/// ## Complete Usage Example
-///
-/// This example demonstrates all the patterns working together:
-/// - System startup and coordination
-/// - Cross-actor request flows
-/// - Error handling and tracing
-/// - Graceful shutdown
+        // Records every report section built and every event code logged
+        #[derive(Clone, Default)]
+        struct ReportTrace(Arc<Mutex<(Vec<String>, Vec<String>)>>);
 
-#[tokio::main]
-async fn main() -> Result<(), String> {
-    // Setup tracing once for the entire application
-    setup_tracing();
+        impl tracing::field::Visit for ReportTrace {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "code" {
+                    let mut trace = self.0.lock().unwrap_or_else(|e| e.into_inner());
+                    trace.1.push(format!("{:?}", value));
+                }
+            }
 
-    info!("Starting application with complete order system");
+            fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                if field.name() == "section" {
+                    let mut trace = self.0.lock().unwrap_or_else(|e| e.into_inner());
+                    trace.0.push(value.to_string());
+                }
+            }
+        }
 
-    // Create the entire order system (starts all services)
-    let system = OrderSystem::new();
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for ReportTrace {
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                event.record(&mut self.clone());
+            }
+        }
 
-    // Create test user
-    let user = User::new("Alice", "alice@example.com");
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+        let id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+
+        let report = user_client.generate_report(id.clone()).await?;
+        assert!(report.ends_with("- summary: ok"));
+
+        let trace = ReportTrace::default();
+        let subscriber = tracing_subscriber::registry().with(trace.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = user_client.clone();
+        let call = CallHandle::spawn(async move { client.generate_report(id).await });
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        call.cancel();
+        assert_eq!(call.join().await.unwrap_err(), "Request cancelled");
+
+        // The actor's work stops at its next check instead of finishing for nobody
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let trace = trace.0.lock().unwrap_or_else(|e| e.into_inner());
+        let (sections, codes) = &*trace;
+        assert!(!sections.is_empty());
+        assert!(sections.len() < REPORT_SECTIONS.len());
+        assert!(codes.contains(&"SYS-009".to_string()));
+        Ok(())
+    }
 
-    let span = tracing::info_span!("user_creation");
-    let user_id = async {
-        info!("Creating test user");
-        system.user_client.create_user(user).await
+    #[tokio::test(start_paused = true)]
+    async fn test_report_streams_progress_then_result() -> Result<(), Box<dyn std::error::Error>> {
+        let (user_service, user_client) = UserService::new(10);
+        let _handle = tokio::spawn(user_service.run());
+        let id = user_client
+            .create_user(User::new("Alice", "alice@example.com"))
+            .await?;
+
+        let mut updates = user_client.generate_report_streaming(id).await?;
+        let mut stages = Vec::new();
+        let report = loop {
+            match updates
+                .recv()
+                .await
+                .ok_or("stream ended without a result")?
+            {
+                Progress::Step { done, total, stage } => {
+                    assert_eq!((done, total), (stages.len() + 1, REPORT_SECTIONS.len()));
+                    stages.push(stage);
+                }
+                Progress::Done(result) => break result?,
+            }
+        };
+        assert_eq!(stages, REPORT_SECTIONS);
+        assert!(report.starts_with("Report for Alice"));
+        assert!(updates.recv().await.is_none());
+
+        let mut missing = user_client
+            .generate_report_streaming("user_99".to_string())
+            .await?;
+        assert_eq!(
+            missing.recv().await,
+            Some(Progress::Done(Err("User not found: user_99".to_string())))
+        );
+        Ok(())
     }
-    .instrument(span)
-    .await?;
 
-    info!(user_id = %user_id, "User created successfully");
+    #[tokio::test(start_paused = true)]
+    async fn test_job_lifecycle() -> Result<(), Box<dyn std::error::Error>> {
+        let (jobs, job_client) = JobService::<u32>::new(1, 10);
+        let _handle = tokio::spawn(jobs.run());
+
+        let slow = job_client
+            .submit(async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(42)
+            })
+            .await?;
+        let failing = job_client.submit(async { Err("boom".to_string()) }).await?;
+        let panicking = job_client.submit(async { panic!("kaboom") }).await?;
+
+        // One slot: the first job runs, the rest wait their turn
+        assert_eq!(
+            job_client.get_job_status(slow).await?,
+            Some(JobStatus::Running)
+        );
+        assert_eq!(
+            job_client.get_job_status(failing).await?,
+            Some(JobStatus::Queued)
+        );
 
-    // Create test order - this will flow through multiple actors
-    let order = Order::new("order_1", user_id, "p1", 5, 50.0);
+        assert_eq!(job_client.await_job(slow).await?, JobStatus::Done(42));
+        assert_eq!(
+            job_client.await_job(failing).await?,
+            JobStatus::Failed("boom".to_string())
+        );
+        assert_eq!(
+            job_client.await_job(panicking).await?,
+            JobStatus::Failed("Job panicked: kaboom".to_string())
+        );
+        // Finished results stay available
+        assert_eq!(
+            job_client.get_job_status(slow).await?,
+            Some(JobStatus::Done(42))
+        );
 
-    let span = tracing::info_span!("order_processing");
-    let order_result = async {
-        info!("Processing order through order system");
-        system.order_client.create_order(order).await
+        assert_eq!(job_client.get_job_status(99).await?, None);
+        assert_eq!(
+            job_client.await_job(99).await.unwrap_err(),
+            "Job not found: 99"
+        );
+        job_client.shutdown().await?;
+        Ok(())
     }
-    .instrument(span)
-    .await;
 
-    match order_result {
-        Ok(order_id) => info!(order_id = %order_id, "Order processed successfully"),
-        Err(e) => {
-            error!(error = %e, "Order processing failed (expected - no test products in stock)")
+    #[tokio::test]
+    async fn test_blocking_checksum_tracks_store() -> Result<(), Box<dyn std::error::Error>> {
+        let (first, first_client) = UserService::new(10);
+        let (second, second_client) = UserService::new(10);
+        tokio::spawn(first.run());
+        tokio::spawn(second.run());
+
+        for client in [&first_client, &second_client] {
+            client
+                .create_user(User::new("Alice", "alice@example.com"))
+                .await?;
         }
+        let checksum = first_client.checksum().await?;
+        assert_eq!(checksum, second_client.checksum().await?);
+
+        second_client
+            .create_user(User::new("Bob", "bob@example.com"))
+            .await?;
+        assert_ne!(checksum, second_client.checksum().await?);
+        Ok(())
     }
 
-    // Demonstrate additional operations
-    let users = system.user_client.list_users().await?;
-    info!(user_count = users.len(), "Retrieved user list");
+    #[tokio::test]
+    async fn test_saga_compensates_completed_steps_in_reverse() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let entry = |text: &'static str| {
+            let log = log.clone();
+            move || async move { log.lock().unwrap().push(text) }
+        };
+        let ok = |text: &'static str| {
+            let record = entry(text);
+            move || async move {
+                record().await;
+                Ok::<(), String>(())
+            }
+        };
 
-    // Shutdown system gracefully
-    system.shutdown().await?;
+        let result = Saga::new("test")
+            .step("check", ok("check"))
+            .compensated_step("first", ok("first"), entry("undo first"))
+            .compensated_step("second", ok("second"), entry("undo second"))
+            .step("fail", || async { Err("boom".to_string()) })
+            .compensated_step("never", ok("never"), entry("undo never"))
+            .run()
+            .await;
 
-    info!("Application completed successfully");
-    Ok(())
-}
+        assert_eq!(result.err(), Some("boom".to_string()));
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["check", "first", "second", "undo second", "undo first"]
+        );
+    }
 
-// =============================================================================
-// RECIPE SUMMARY
-// =============================================================================
+    #[tokio::test]
+    async fn test_order_saga_releases_reserved_stock() -> Result<(), Box<dyn std::error::Error>> {
+        let mut snapshot = SystemSnapshot::default();
+        let alice = User {
+            id: "user_1".to_string(),
+            ..User::new("Alice", "alice@example.com")
+        };
+        snapshot.users.users.insert(alice.id.clone(), alice);
+        let product = Product::new("p1", "Widget", 10.0);
+        snapshot.products.products.insert("p1".to_string(), product);
+        snapshot.products.stock.insert("p1".to_string(), 5);
+        let system = OrderSystem::restore(snapshot);
+
+        let order = Order::new("order_1", "user_1", "p1", 2, 20.0);
+        system.order_client.create_order(order.clone()).await?;
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            3
+        );
 
-/// This recipe provides a solid foundation for building production actor systems in Rust!
-///
-/// ## To Run This Example
-///
-/// ```bash
-/// # Basic run
-/// cargo run
-///
-/// # With debug logging
-/// RUST_LOG=debug cargo run
-///
-/// # With warning level only  
-/// RUST_LOG=warn cargo run
-///
-/// # Generate documentation
-/// cargo doc --open
-/// ```
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // A duplicate is refused before the saga reserves anything
+        let duplicate = system.order_client.create_order(order).await;
+        assert_eq!(duplicate.unwrap_err(), "Order already exists: order_1");
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            3
+        );
 
-    /// Demonstrates test-only messages for extracting internal actor state
-    #[tokio::test]
-    async fn test_user_service_internal_state() -> Result<(), Box<dyn std::error::Error>> {
-        // Start just the UserService for testing
-        let (user_service, user_client) = UserService::new(10);
-        let _handle = tokio::spawn(user_service.run());
+        // Compensating the order's reservation saga hands the units back
+        let (reserve, release) = (system.product_client.clone(), system.product_client.clone());
+        let reservation = Saga::new("create_order")
+            .compensated_step(
+                "reserve_stock",
+                move || OrderService::reserve_stock(reserve, "p1".to_string(), 2),
+                move || OrderService::release_stock(release, "p1".to_string(), 2),
+            )
+            .run()
+            .await?;
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            1
+        );
+        reservation.compensate().await;
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            3
+        );
 
-        // Initially should have 0 users
-        let count = user_client.get_user_count().await?;
-        assert_eq!(count, 0);
+        // Only the order's own two units are still reserved
+        let excess = system
+            .product_client
+            .release_stock("p1".to_string(), 3)
+            .await;
+        assert_eq!(
+            excess.unwrap_err(),
+            "Cannot release 3 units, only 2 reserved"
+        );
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            3
+        );
+        let missing = system
+            .product_client
+            .release_stock("p9".to_string(), 1)
+            .await;
+        assert_eq!(missing.unwrap_err(), "Product not found: p9");
 
-        // Create a user
-        let user = User::new("Test User", "test@example.com");
-        let _user_id = user_client.create_user(user).await?;
+        system.shutdown().await?;
+        Ok(())
+    }
 
-        // Now should have 1 user
-        let count = user_client.get_user_count().await?;
-        assert_eq!(count, 1);
+    #[tokio::test(start_paused = true)]
+    async fn test_two_phase_commit_aborts_prepared_participants(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut snapshot = SystemSnapshot::default();
+        let product = Product::new("p1", "Widget", 10.0);
+        snapshot.products.products.insert("p1".to_string(), product);
+        snapshot.products.stock.insert("p1".to_string(), 5);
+        let system = OrderSystem::restore(snapshot);
+        let stock = |products: ProductClient| {
+            let releaser = products.clone();
+            Participant::new("stock", move || async move {
+                products.reserve_stock("p1".to_string(), 2).await
+            })
+            .on_abort(move || async move {
+                let _ = releaser.release_stock("p1".to_string(), 2).await;
+            })
+        };
 
-        // Shutdown
-        user_client.shutdown().await?;
+        let refused = TwoPhaseCommit::new("checkout")
+            .participant(stock(system.product_client.clone()))
+            .participant(Participant::new("payment", || async {
+                Err("card declined".to_string())
+            }))
+            .run()
+            .await;
+        assert_eq!(
+            refused.unwrap_err().to_string(),
+            "Transaction aborted by payment: card declined"
+        );
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            5
+        );
+
+        let timed_out = TwoPhaseCommit::new("checkout")
+            .participant(stock(system.product_client.clone()))
+            .participant(
+                Participant::new("payment", || async {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    Ok(())
+                })
+                .with_timeout(Duration::from_secs(1)),
+            )
+            .run()
+            .await;
+        assert!(matches!(
+            timed_out,
+            Err(TransactionError::Aborted {
+                participant: "payment",
+                ..
+            })
+        ));
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            5
+        );
+
+        let captured = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let capture = captured.clone();
+        TwoPhaseCommit::new("checkout")
+            .participant(stock(system.product_client.clone()))
+            .participant(Participant::new("payment", || async { Ok(()) }).on_commit(
+                move || async move {
+                    capture.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Ok(())
+                },
+            ))
+            .run()
+            .await?;
+        assert!(captured.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(
+            system.product_client.check_stock("p1".to_string()).await?,
+            3
+        );
+
+        system.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_outbox_keeps_events_until_the_bus_takes_them(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut catalog = ProductSnapshot::default();
+        let product = Product::new("p1", "Widget", 10.0);
+        catalog.products.insert("p1".to_string(), product);
+        catalog.stock.insert("p1".to_string(), 5);
+
+        // The bus is gone, so the first reservation's event fills the outbox
+        let (bus_service, bus) = EventBusService::new(10, 16);
+        drop(bus_service);
+        let (product_service, product_client) = ProductService::new(10);
+        let product_service = product_service
+            .with_event_bus(bus)
+            .with_outbox_capacity(1)
+            .restore(catalog);
+        let _handle = tokio::spawn(product_service.run());
+        product_client.reserve_stock("p1".to_string(), 1).await?;
+        let refused = product_client.reserve_stock("p1".to_string(), 2).await;
+        assert_eq!(refused, Err("Product service is overloaded".to_string()));
+        let snapshot = product_client.snapshot().await?;
+        assert_eq!(snapshot.outbox.len(), 1);
+        assert_eq!(snapshot.stock["p1"], 4);
+        let rendered = Metrics::global().render();
+        assert!(rendered.contains("# TYPE actor_outbox_dropped_total counter"));
+        assert!(rendered.contains("actor_outbox_dropped_total{entity=\"Product\"}"));
+        assert!(rendered.contains("actor_outbox_pending{entity=\"Product\"}"));
+
+        // Restored next to a live bus, the kept event is delivered
+        let (bus_service, bus) = EventBusService::new(10, 16);
+        let _bus_handle = tokio::spawn(bus_service.run());
+        let mut events = bus.subscribe().await?;
+        let (product_service, product_client) = ProductService::new(10);
+        let product_service = product_service.with_event_bus(bus).restore(snapshot);
+        let _handle = tokio::spawn(product_service.run());
+        product_client.ping().await?;
+        match events.recv().await? {
+            DomainEvent::StockReserved {
+                product_id,
+                quantity,
+            } => assert_eq!((product_id.as_str(), quantity), ("p1", 1)),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(product_client.snapshot().await?.outbox.is_empty());
         Ok(())
     }
 }